@@ -1,5 +1,7 @@
+#[cfg(windows)]
 use windows_icons::{DllIcon, get_icon_base64_by_dll, get_icon_by_dll};
 
+#[cfg(windows)]
 fn main() {
     let _ = std::fs::create_dir("output");
 
@@ -19,3 +21,8 @@ fn main() {
     let base64 = get_icon_base64_by_dll(explorer).unwrap();
     println!("Explorer: {}", base64);
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("this example requires Windows");
+}
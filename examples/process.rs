@@ -1,5 +1,7 @@
+#[cfg(windows)]
 use windows_icons::{get_icon_base64_by_process_id, get_icon_by_process_id};
 
+#[cfg(windows)]
 fn main() {
     let _ = std::fs::create_dir("output");
 
@@ -12,3 +14,8 @@ fn main() {
     let base64 = get_icon_base64_by_process_id(process_id).unwrap();
     println!("Process {}: {}", process_id, base64);
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("this example requires Windows");
+}
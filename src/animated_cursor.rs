@@ -0,0 +1,416 @@
+use std::{error::Error, fs, path::Path, time::Duration};
+
+use crate::{
+    Cursor, CursorFrame, CursorImage, IconGroup,
+    utils::{
+        binary_utils::{invalid_data, read_ansiz, read_u16, read_u32, truncated},
+        image_utils::{encode_apng, encode_gif},
+    },
+};
+
+const ANIHEADER_SIZE: usize = 36;
+/// `AF_ICON`: frames are `.ico`/`.cur` files rather than raw bitmaps.
+const AF_ICON: u32 = 0x1;
+const JIFFIES_PER_SECOND: u32 = 60;
+
+/// One step of an animation: which frame is shown, and for how many jiffies (1/60 s).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AniStep {
+    pub frame: usize,
+    pub jiffies: u32,
+}
+
+impl AniStep {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(u64::from(self.jiffies)) / JIFFIES_PER_SECOND
+    }
+}
+
+/// An animated cursor (`.ani`): a RIFF `ACON` file of cursor frames played in a
+/// sequence of steps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnimatedCursor {
+    pub frames: Vec<Cursor>,
+    pub steps: Vec<AniStep>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl AnimatedCursor {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path.as_ref())?)
+    }
+
+    /// Parses the `anih` header, the `rate` and `seq ` chunks and the `fram` list.
+    /// Without `seq `, each frame plays once, in order; without `rate`, at the header's
+    /// rate.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.get(..4) != Some(b"RIFF".as_slice()) || data.get(8..12) != Some(b"ACON".as_slice())
+        {
+            return Err(invalid_data("not a RIFF ACON file"));
+        }
+        let riff_size = read_u32(data, 4).ok_or_else(|| truncated("RIFF header"))? as usize;
+        let end = (8 + riff_size).min(data.len());
+
+        let mut header = None;
+        let mut rates = None;
+        let mut sequence = None;
+        let mut frames = Vec::new();
+        let mut cursor = AnimatedCursor::default();
+        for (id, chunk) in chunks(&data[12..end]) {
+            match id {
+                b"anih" => header = Some(AniHeader::parse(chunk)?),
+                b"rate" => rates = Some(read_u32s(chunk)),
+                b"seq " => sequence = Some(read_u32s(chunk)),
+                b"LIST" if chunk.starts_with(b"fram") => {
+                    frames.extend(
+                        chunks(&chunk[4..])
+                            .filter(|(id, _)| *id == b"icon")
+                            .map(|(_, icon)| icon),
+                    );
+                }
+                b"LIST" if chunk.starts_with(b"INFO") => {
+                    for (id, text) in chunks(&chunk[4..]) {
+                        let text = read_ansiz(text, 0)
+                            .map(|text| text.trim().to_owned())
+                            .filter(|text| !text.is_empty());
+                        match id {
+                            b"INAM" => cursor.title = text,
+                            b"IART" => cursor.author = text,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let header = header.ok_or_else(|| invalid_data("ANI file has no anih chunk"))?;
+        if header.flags & AF_ICON == 0 {
+            return Err(invalid_data(
+                "ANI frames stored as raw bitmaps are not supported",
+            ));
+        }
+        cursor.frames = frames
+            .into_iter()
+            .map(frame_to_cursor)
+            .collect::<Result<_, _>>()?;
+        if cursor.frames.is_empty() {
+            return Err(invalid_data("ANI file has no frames"));
+        }
+
+        let step_count = sequence
+            .as_ref()
+            .map(Vec::len)
+            .unwrap_or((header.steps as usize).clamp(1, cursor.frames.len()));
+        cursor.steps = (0..step_count)
+            .map(|step| {
+                let frame = match &sequence {
+                    Some(sequence) => sequence[step] as usize,
+                    None => step,
+                };
+                if frame >= cursor.frames.len() {
+                    return Err(invalid_data(format!(
+                        "ANI step {step} shows missing frame {frame}"
+                    )));
+                }
+                let jiffies = rates
+                    .as_ref()
+                    .and_then(|rates| rates.get(step).copied())
+                    .unwrap_or(header.display_rate);
+                Ok(AniStep { frame, jiffies })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(cursor)
+    }
+
+    /// The total length of one loop.
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(AniStep::duration).sum()
+    }
+
+    /// Every step decoded, in play order, with its hotspot and duration. With a `size`
+    /// frames are resampled to it; otherwise the largest image of each frame is used,
+    /// resampled to the first one's size when they differ.
+    pub fn to_images(
+        &self,
+        size: Option<u32>,
+    ) -> Result<Vec<(CursorImage, AniStep)>, Box<dyn Error>> {
+        let decoded: Vec<CursorImage> = self
+            .frames
+            .iter()
+            .map(|frame| match size {
+                Some(size) => frame.to_image_with_size(size),
+                None => frame.to_image(),
+            })
+            .collect::<Result<_, _>>()?;
+        let (width, height) = decoded[0].image.dimensions();
+
+        Ok(self
+            .steps
+            .iter()
+            .map(|step| {
+                let image = &decoded[step.frame];
+                let image = if image.image.dimensions() == (width, height) {
+                    image.clone()
+                } else {
+                    image.resize(width, height)
+                };
+                (image, *step)
+            })
+            .collect())
+    }
+
+    /// Encodes the animation as an endlessly looping APNG.
+    pub fn to_apng_bytes(&self, size: Option<u32>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let frames: Vec<_> = self
+            .to_images(size)?
+            .into_iter()
+            .map(|(cursor, step)| {
+                let jiffies = u16::try_from(step.jiffies).unwrap_or(u16::MAX);
+                (cursor.image, jiffies, JIFFIES_PER_SECOND as u16)
+            })
+            .collect();
+        encode_apng(&frames)
+    }
+
+    /// Encodes the animation as an endlessly looping GIF.
+    pub fn to_gif_bytes(&self, size: Option<u32>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let frames: Vec<_> = self
+            .to_images(size)?
+            .into_iter()
+            .map(|(cursor, step)| (cursor.image, step.duration()))
+            .collect();
+        encode_gif(&frames)
+    }
+
+    /// Writes the animation as APNG (`.png`, `.apng`) or GIF (`.gif`), by extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P, size: Option<u32>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let data = match extension.as_str() {
+            "png" | "apng" => self.to_apng_bytes(size)?,
+            "gif" => self.to_gif_bytes(size)?,
+            _ => return Err(invalid_data(format!("unknown animation format: {path:?}"))),
+        };
+        fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+/// `ANIHEADER`, of which only the fields that matter for `AF_ICON` files are kept.
+struct AniHeader {
+    steps: u32,
+    display_rate: u32,
+    flags: u32,
+}
+
+impl AniHeader {
+    fn parse(chunk: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if chunk.len() < ANIHEADER_SIZE {
+            return Err(truncated("anih chunk"));
+        }
+        let field = |index: usize| read_u32(chunk, index * 4).unwrap_or_default();
+        Ok(AniHeader {
+            steps: field(2),
+            display_rate: field(7),
+            flags: field(8),
+        })
+    }
+}
+
+/// The chunks of a RIFF list: a four-character ID, a size and the data, padded to
+/// an even length. A truncated last chunk is cut to what is there.
+fn chunks(data: &[u8]) -> impl Iterator<Item = (&[u8; 4], &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let id: &[u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+        let size = read_u32(data, offset + 4)? as usize;
+        let start = offset + 8;
+        let end = start.saturating_add(size).min(data.len());
+        offset = start.saturating_add(size).saturating_add(size % 2);
+        Some((id, &data[start..end]))
+    })
+}
+
+fn read_u32s(chunk: &[u8]) -> Vec<u32> {
+    chunk
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// Frames are `.cur` files, or `.ico` files for animations without a hotspot.
+fn frame_to_cursor(data: &[u8]) -> Result<Cursor, Box<dyn Error>> {
+    if read_u16(data, 2) == Some(1) {
+        let icon = IconGroup::from_ico_bytes(data)?;
+        return Ok(Cursor {
+            frames: icon
+                .frames
+                .into_iter()
+                .map(|frame| CursorFrame {
+                    width: frame.width,
+                    height: frame.height,
+                    hotspot_x: 0,
+                    hotspot_y: 0,
+                    data: frame.data,
+                })
+                .collect(),
+        });
+    }
+    Cursor::from_cur_bytes(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    use crate::IconFrame;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn list(kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        data.extend(chunks.concat());
+        chunk(b"LIST", &data)
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn anih(frames: u32, steps: u32, rate: u32, flags: u32) -> Vec<u8> {
+        chunk(
+            b"anih",
+            &u32s(&[
+                ANIHEADER_SIZE as u32,
+                frames,
+                steps,
+                0,
+                0,
+                0,
+                0,
+                rate,
+                flags,
+            ]),
+        )
+    }
+
+    fn cur_file(size: u32, hotspot: u16) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(size, size, image::Rgba([9, 9, 9, 255]));
+        Cursor {
+            frames: vec![CursorFrame {
+                width: size,
+                height: size,
+                hotspot_x: hotspot,
+                hotspot_y: hotspot,
+                data: IconFrame::from_image(&image).unwrap().data,
+            }],
+        }
+        .to_cur_bytes()
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = b"ACON".to_vec();
+        body.extend(chunks.concat());
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn reads_sequence_rates_and_info() {
+        let data = riff(&[
+            list(
+                b"INFO",
+                &[chunk(b"INAM", b"Spin\0"), chunk(b"IART", b" Me \0")],
+            ),
+            anih(2, 3, 10, AF_ICON),
+            chunk(b"rate", &u32s(&[6, 12, 30])),
+            chunk(b"seq ", &u32s(&[1, 0, 1])),
+            list(
+                b"fram",
+                &[
+                    chunk(b"icon", &cur_file(32, 4)),
+                    chunk(b"icon", &cur_file(16, 2)),
+                ],
+            ),
+        ]);
+        let cursor = AnimatedCursor::from_bytes(&data).unwrap();
+
+        assert_eq!(cursor.title.as_deref(), Some("Spin"));
+        assert_eq!(cursor.author.as_deref(), Some("Me"));
+        assert_eq!(cursor.frames.len(), 2);
+        assert_eq!(
+            cursor.steps,
+            [
+                AniStep {
+                    frame: 1,
+                    jiffies: 6
+                },
+                AniStep {
+                    frame: 0,
+                    jiffies: 12
+                },
+                AniStep {
+                    frame: 1,
+                    jiffies: 30
+                },
+            ]
+        );
+        assert_eq!(cursor.duration(), Duration::from_millis(800));
+
+        // The 16 pixel frame is resampled to the first frame's size
+        let images = cursor.to_images(None).unwrap();
+        assert_eq!(images[0].0.image.dimensions(), (32, 32));
+        assert_eq!((images[0].0.hotspot_x, images[1].0.hotspot_x), (4, 4));
+    }
+
+    #[test]
+    fn plays_frames_in_order_without_a_sequence() {
+        let icon = IconGroup::from_images(&[RgbaImage::new(16, 16)])
+            .unwrap()
+            .to_ico_bytes();
+        let data = riff(&[
+            anih(2, u32::MAX, 5, AF_ICON),
+            list(b"fram", &[chunk(b"icon", &icon), chunk(b"icon", &icon)]),
+        ]);
+        let cursor = AnimatedCursor::from_bytes(&data).unwrap();
+
+        let frames: Vec<usize> = cursor.steps.iter().map(|s| s.frame).collect();
+        assert_eq!(frames, [0, 1]);
+        assert!(cursor.steps.iter().all(|s| s.jiffies == 5));
+        assert_eq!(cursor.frames[0].frames[0].hotspot_x, 0);
+    }
+
+    #[test]
+    fn rejects_unsupported_and_broken_files() {
+        let frames = list(b"fram", &[chunk(b"icon", &cur_file(16, 0))]);
+        let raw_bitmaps = riff(&[anih(1, 1, 5, 0), frames.clone()]);
+        let missing_frame = riff(&[
+            anih(1, 1, 5, AF_ICON),
+            chunk(b"seq ", &u32s(&[3])),
+            frames.clone(),
+        ]);
+        let no_header = riff(std::slice::from_ref(&frames));
+        let no_frames = riff(&[anih(0, 1, 5, AF_ICON)]);
+
+        for data in [raw_bitmaps, missing_frame, no_header, no_frames] {
+            assert!(AnimatedCursor::from_bytes(&data).is_err());
+        }
+        assert!(AnimatedCursor::from_bytes(b"RIFF\0\0\0\0WAVE").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::utils::binary_utils::invalid_data;
+
+use std::{error::Error, path::PathBuf};
+#[cfg(windows)]
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    os::windows::{
+        fs::{MetadataExt, OpenOptionsExt},
+        io::AsRawHandle,
+    },
+    path::Path,
+};
+
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::HANDLE,
+    Storage::FileSystem::{
+        FILE_ATTRIBUTE_REPARSE_POINT, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+        FILE_READ_ATTRIBUTES, MAXIMUM_REPARSE_DATA_BUFFER_SIZE,
+    },
+    System::{IO::DeviceIoControl, Ioctl::FSCTL_GET_REPARSE_POINT},
+};
+
+pub const IO_REPARSE_TAG_APPEXECLINK: u32 = 0x8000_001B;
+
+const REPARSE_HEADER_LEN: usize = 8;
+const APPEXECLINK_VERSION: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppExecAlias {
+    pub package_family_name: String,
+    pub app_user_model_id: String,
+    pub target_path: PathBuf,
+    pub app_type: Option<String>,
+}
+
+impl AppExecAlias {
+    /// Parses a `REPARSE_DATA_BUFFER` as returned by `FSCTL_GET_REPARSE_POINT`.
+    pub fn from_reparse_buffer(buffer: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if buffer.len() < REPARSE_HEADER_LEN {
+            return Err(invalid_data("reparse buffer is too short"));
+        }
+
+        let tag = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        if tag != IO_REPARSE_TAG_APPEXECLINK {
+            return Err(invalid_data(format!("unexpected reparse tag: {tag:#010x}")));
+        }
+
+        let data_len = usize::from(u16::from_le_bytes([buffer[4], buffer[5]]));
+        let data = buffer
+            .get(REPARSE_HEADER_LEN..REPARSE_HEADER_LEN + data_len)
+            .ok_or_else(|| invalid_data("reparse data length exceeds buffer"))?;
+
+        Self::from_reparse_data(data)
+    }
+
+    /// Parses the tag-specific part of an AppExecLink reparse point.
+    pub fn from_reparse_data(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let version = data
+            .get(..4)
+            .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            .ok_or_else(|| invalid_data("AppExecLink data is too short"))?;
+        if version != APPEXECLINK_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported AppExecLink version: {version}"
+            )));
+        }
+
+        let mut strings = split_wide_strings(&data[4..]).into_iter();
+        let mut next = |field: &str| {
+            strings
+                .next()
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| invalid_data(format!("AppExecLink is missing the {field}")))
+        };
+
+        let package_family_name = next("package family name")?;
+        let app_user_model_id = next("app user model id")?;
+        let target_path = PathBuf::from(next("target path")?);
+        let app_type = next("app type").ok();
+
+        Ok(AppExecAlias {
+            package_family_name,
+            app_user_model_id,
+            target_path,
+            app_type,
+        })
+    }
+}
+
+fn split_wide_strings(data: &[u8]) -> Vec<String> {
+    let wide: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+
+    let mut strings: Vec<String> = wide
+        .split(|&c| c == 0)
+        .map(String::from_utf16_lossy)
+        .collect();
+
+    // The buffer ends with a terminator, which leaves an empty trailing slice
+    if strings.last().is_some_and(String::is_empty) {
+        strings.pop();
+    }
+    strings
+}
+
+#[cfg(windows)]
+pub fn find_app_exec_alias(path: &Path) -> Option<AppExecAlias> {
+    let is_reparse_point = fs::symlink_metadata(path)
+        .is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT.0 != 0);
+
+    is_reparse_point
+        .then(|| read_app_exec_alias(path).ok())
+        .flatten()
+}
+
+#[cfg(windows)]
+pub fn read_app_exec_alias(path: &Path) -> Result<AppExecAlias, Box<dyn Error>> {
+    let file = OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES.0)
+        .custom_flags((FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS).0)
+        .open(path)?;
+
+    let mut buffer = vec![0u8; MAXIMUM_REPARSE_DATA_BUFFER_SIZE as usize];
+    let mut bytes_returned = 0u32;
+    unsafe {
+        DeviceIoControl(
+            HANDLE(file.as_raw_handle()),
+            FSCTL_GET_REPARSE_POINT,
+            None,
+            0,
+            Some(buffer.as_mut_ptr().cast()),
+            MAXIMUM_REPARSE_DATA_BUFFER_SIZE,
+            Some(&mut bytes_returned),
+            None,
+        )
+    }
+    .map_err(|e| io::Error::other(format!("failed to read reparse point: {path:?} - {e}")))?;
+
+    buffer.truncate(usize::try_from(bytes_returned)?);
+    AppExecAlias::from_reparse_buffer(&buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reparse_buffer(version: u32, strings: &[&str]) -> Vec<u8> {
+        let mut data = version.to_le_bytes().to_vec();
+        for string in strings {
+            for unit in string.encode_utf16().chain(Some(0)) {
+                data.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        let mut buffer = IO_REPARSE_TAG_APPEXECLINK.to_le_bytes().to_vec();
+        buffer.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        buffer.extend_from_slice(&[0, 0]);
+        buffer.extend_from_slice(&data);
+        buffer
+    }
+
+    #[test]
+    fn parses_all_fields() {
+        let buffer = reparse_buffer(
+            3,
+            &[
+                "Microsoft.WindowsTerminal_8wekyb3d8bbwe",
+                "Microsoft.WindowsTerminal_8wekyb3d8bbwe!App",
+                r"C:\Program Files\WindowsApps\Terminal\wt.exe",
+                "0",
+            ],
+        );
+        let alias = AppExecAlias::from_reparse_buffer(&buffer).unwrap();
+        assert_eq!(
+            alias.package_family_name,
+            "Microsoft.WindowsTerminal_8wekyb3d8bbwe"
+        );
+        assert_eq!(
+            alias.app_user_model_id,
+            "Microsoft.WindowsTerminal_8wekyb3d8bbwe!App"
+        );
+        assert_eq!(
+            alias.target_path,
+            PathBuf::from(r"C:\Program Files\WindowsApps\Terminal\wt.exe")
+        );
+        assert_eq!(alias.app_type.as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn app_type_is_optional() {
+        let buffer = reparse_buffer(3, &["Family", "Family!App", r"C:\app.exe"]);
+        let alias = AppExecAlias::from_reparse_buffer(&buffer).unwrap();
+        assert_eq!(alias.app_type, None);
+    }
+
+    #[test]
+    fn rejects_other_tags_and_versions() {
+        let mut buffer = reparse_buffer(3, &["Family", "Family!App", r"C:\app.exe"]);
+        assert!(AppExecAlias::from_reparse_buffer(&reparse_buffer(2, &["a", "b", "c"])).is_err());
+        buffer[..4].copy_from_slice(&0xA000_000Cu32.to_le_bytes());
+        assert!(AppExecAlias::from_reparse_buffer(&buffer).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_buffers() {
+        let buffer = reparse_buffer(3, &["Family", "Family!App", r"C:\app.exe"]);
+        assert!(AppExecAlias::from_reparse_buffer(&buffer[..6]).is_err());
+        assert!(AppExecAlias::from_reparse_buffer(&buffer[..buffer.len() - 2]).is_err());
+        assert!(AppExecAlias::from_reparse_buffer(&reparse_buffer(3, &["Family"])).is_err());
+    }
+}
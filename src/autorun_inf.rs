@@ -0,0 +1,193 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+#[cfg(windows)]
+use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+
+#[cfg(windows)]
+use windows::{Win32::Storage::FileSystem::GetDriveTypeW, core::HSTRING};
+
+use crate::{
+    DllIcon, IconSource, StockIcon,
+    utils::{
+        ini_utils::Ini,
+        path_utils::{find_file_ignore_case, resolve_icon_path, split_icon_location},
+    },
+};
+
+/// The icon and label a volume declares in `autorun.inf` at its root.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AutorunInf {
+    pub icon: Option<String>,
+    pub label: Option<String>,
+}
+
+impl AutorunInf {
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, Box<dyn Error>> {
+        let root = root.as_ref();
+        let path = find_file_ignore_case(root, "autorun.inf").ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no autorun.inf in volume root: {root:?}"),
+            )
+        })?;
+        Ok(Self::from_bytes(&fs::read(path)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::from_bytes_for_architecture(data, current_architecture())
+    }
+
+    /// Keys in `[autorun.<architecture>]` (`x86`, `amd64`, `arm64`, `ia64`) override
+    /// the ones in `[autorun]`, as they do on a machine of that architecture.
+    pub fn from_bytes_for_architecture(data: &[u8], architecture: &str) -> Self {
+        let ini = Ini::from_bytes(data);
+        let architecture_section = format!("autorun.{architecture}");
+        let get = |key| {
+            ini.get(&architecture_section, key)
+                .or_else(|| ini.get("autorun", key))
+                .map(str::to_owned)
+        };
+
+        AutorunInf {
+            icon: get("icon"),
+            label: get("label"),
+        }
+    }
+
+    pub fn icon_source(&self, root: &Path) -> Option<IconSource> {
+        let (path, index) = split_icon_location(self.icon.as_deref()?);
+        let path = resolve_icon_path(&path, Some(root));
+        let icon = DllIcon::new().with_icon_location(path, index);
+        Some(IconSource::Resource(icon))
+    }
+}
+
+fn current_architecture() -> &'static str {
+    if cfg!(target_arch = "x86_64") {
+        "amd64"
+    } else if cfg!(target_arch = "aarch64") {
+        "arm64"
+    } else {
+        "x86"
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DriveType {
+    Unknown,
+    Removable,
+    Fixed,
+    Remote,
+    CdRom,
+    RamDisk,
+}
+
+impl DriveType {
+    /// Asks Windows for the type of the volume at `root`. Elsewhere the type cannot be
+    /// queried and every volume is reported as `Fixed`.
+    pub fn of<P: AsRef<Path>>(root: P) -> Self {
+        #[cfg(windows)]
+        {
+            let mut wide_root: Vec<u16> = OsStr::new(root.as_ref()).encode_wide().collect();
+            if wide_root.last() != Some(&u16::from(b'\\')) {
+                wide_root.push(u16::from(b'\\'));
+            }
+            let root = HSTRING::from_wide(&wide_root);
+
+            // DRIVE_* values from WinBase.h
+            match unsafe { GetDriveTypeW(&root) } {
+                2 => DriveType::Removable,
+                3 => DriveType::Fixed,
+                4 => DriveType::Remote,
+                5 => DriveType::CdRom,
+                6 => DriveType::RamDisk,
+                _ => DriveType::Unknown,
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = root;
+            DriveType::Fixed
+        }
+    }
+
+    pub fn icon_source(self) -> IconSource {
+        let icon = match self {
+            DriveType::Unknown | DriveType::Fixed => StockIcon::DriveFixed,
+            DriveType::Removable => StockIcon::DriveRemove,
+            DriveType::Remote => StockIcon::DriveNet,
+            DriveType::CdRom => StockIcon::DriveCd,
+            DriveType::RamDisk => StockIcon::DriveRam,
+        };
+        IconSource::Resource(DllIcon::stock(icon))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTORUN: &[u8] = b"[AutoRun]\r\nicon=setup.exe,1\r\nlabel=Install Disk\r\n\r\n\
+                             [autorun.amd64]\r\nIcon=setup64.exe\r\n";
+
+    #[test]
+    fn architecture_sections_override_autorun() {
+        let amd64 = AutorunInf::from_bytes_for_architecture(AUTORUN, "amd64");
+        assert_eq!(amd64.icon.as_deref(), Some("setup64.exe"));
+        assert_eq!(amd64.label.as_deref(), Some("Install Disk"));
+
+        let x86 = AutorunInf::from_bytes_for_architecture(AUTORUN, "x86");
+        assert_eq!(x86.icon.as_deref(), Some("setup.exe,1"));
+        assert_eq!(
+            AutorunInf::from_bytes(b"[autorun]\nlabel=Data\n"),
+            AutorunInf {
+                icon: None,
+                label: Some("Data".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn resolves_icons_against_the_volume_root() {
+        let root = std::env::temp_dir().join(format!("autorun-inf-{}", std::process::id()));
+        fs::create_dir_all(root.join("icons")).unwrap();
+        fs::write(root.join("icons").join("disk.ico"), b"").unwrap();
+        fs::write(
+            root.join("AUTORUN.INF"),
+            b"[autorun]\r\nicon=icons\\disk.ico,2\r\n",
+        )
+        .unwrap();
+
+        let source = AutorunInf::open(&root).unwrap().icon_source(&root);
+        let expected = DllIcon::new().with_icon_location(root.join("icons/disk.ico"), 2);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(source, Some(IconSource::Resource(expected)));
+
+        let absolute =
+            AutorunInf::from_bytes_for_architecture(b"[autorun]\nicon=C:\\disk.ico\n", "x86");
+        assert_eq!(
+            absolute.icon_source(&root),
+            Some(IconSource::Resource(
+                DllIcon::new().with_icon_location(r"C:\disk.ico", 0)
+            ))
+        );
+        assert_eq!(AutorunInf::default().icon_source(&root), None);
+        assert!(AutorunInf::open(&root).is_err());
+    }
+
+    #[test]
+    fn falls_back_to_drive_type_icons() {
+        assert_eq!(
+            DriveType::CdRom.icon_source(),
+            IconSource::Resource(DllIcon::stock(StockIcon::DriveCd))
+        );
+        assert_eq!(
+            DriveType::Unknown.icon_source(),
+            IconSource::Resource(DllIcon::stock(StockIcon::DriveFixed))
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{error::Error, fs, path::Path};
+
+use image::{RgbaImage, imageops::FilterType};
+
+use crate::{
+    IconFrame,
+    icon_group::{ICONDIR_SIZE, ICONDIRENTRY_SIZE, parse_dir_header},
+    utils::binary_utils::{invalid_data, read_i32, read_u8, read_u16, read_u32, truncated},
+};
+
+const CURSOR_TYPE: u16 = 2;
+const GRPCURSORDIRENTRY_SIZE: usize = 14;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// One image of a cursor: a PNG stream or a DIB with its AND mask, and the hotspot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CursorFrame {
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: u16,
+    pub hotspot_y: u16,
+    pub data: Vec<u8>,
+}
+
+/// A decoded cursor frame and its hotspot, in pixels from the top left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CursorImage {
+    pub image: RgbaImage,
+    pub hotspot_x: u32,
+    pub hotspot_y: u32,
+}
+
+impl CursorImage {
+    /// Resamples the image, moving the hotspot with it.
+    pub fn resize(&self, width: u32, height: u32) -> CursorImage {
+        let scale = |hotspot: u32, from: u32, to: u32| {
+            if from == 0 || to == 0 {
+                return 0;
+            }
+            let scaled =
+                (u64::from(hotspot) * u64::from(to) + u64::from(from) / 2) / u64::from(from);
+            (scaled as u32).min(to - 1)
+        };
+        CursorImage {
+            image: image::imageops::resize(&self.image, width, height, FilterType::Lanczos3),
+            hotspot_x: scale(self.hotspot_x, self.image.width(), width),
+            hotspot_y: scale(self.hotspot_y, self.image.height(), height),
+        }
+    }
+}
+
+impl CursorFrame {
+    pub fn is_png(&self) -> bool {
+        self.data.starts_with(PNG_SIGNATURE)
+    }
+
+    pub fn to_image(&self) -> Result<CursorImage, Box<dyn Error>> {
+        // Cursor images are icon images; only the directory entries differ
+        let frame = IconFrame {
+            width: self.width,
+            height: self.height,
+            color_count: 0,
+            planes: 1,
+            bit_count: bit_count(&self.data),
+            data: self.data.clone(),
+        };
+        Ok(CursorImage {
+            image: frame.to_image()?,
+            hotspot_x: u32::from(self.hotspot_x),
+            hotspot_y: u32::from(self.hotspot_y),
+        })
+    }
+}
+
+/// A cursor: the frames of one `.cur` file or one `RT_GROUP_CURSOR` resource.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub frames: Vec<CursorFrame>,
+}
+
+impl Cursor {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_cur_bytes(&fs::read(path.as_ref())?)
+    }
+
+    /// Parses a `.cur` file, whose directory entries hold the hotspot in place of the
+    /// planes and bit count of an `.ico` file.
+    pub fn from_cur_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let count = parse_dir_header(data, CURSOR_TYPE)?;
+        let frames = (0..count)
+            .map(|i| {
+                let entry = ICONDIR_SIZE + i * ICONDIRENTRY_SIZE;
+                let field = |offset| read_u16(data, entry + offset);
+                let size =
+                    read_u32(data, entry + 8).ok_or_else(|| truncated("cursor directory"))?;
+                let offset =
+                    read_u32(data, entry + 12).ok_or_else(|| truncated("cursor directory"))?;
+                let image = data
+                    .get(offset as usize..offset as usize + size as usize)
+                    .ok_or_else(|| truncated("cursor image"))?;
+
+                let dimension = |offset| match read_u8(data, entry + offset) {
+                    Some(0) => 256,
+                    value => u32::from(value.unwrap_or_default()),
+                };
+                let (width, height) =
+                    image_size(image).unwrap_or_else(|| (dimension(0), dimension(1)));
+                Ok(CursorFrame {
+                    width,
+                    height,
+                    hotspot_x: field(4).ok_or_else(|| truncated("cursor directory"))?,
+                    hotspot_y: field(6).ok_or_else(|| truncated("cursor directory"))?,
+                    data: image.to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        Ok(Cursor { frames })
+    }
+
+    /// Parses an `RT_GROUP_CURSOR` resource, looking each frame up by its `RT_CURSOR`
+    /// ID. `RT_CURSOR` data starts with the hotspot.
+    pub fn from_group_bytes<F>(data: &[u8], mut load_cursor: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: FnMut(u16) -> Option<Vec<u8>>,
+    {
+        let count = parse_dir_header(data, CURSOR_TYPE)?;
+        let frames: Vec<CursorFrame> = (0..count)
+            .filter_map(|i| {
+                let entry = ICONDIR_SIZE + i * GRPCURSORDIRENTRY_SIZE;
+                let id = read_u16(data, entry + 12)?;
+                let resource = load_cursor(id)?;
+                let image = resource.get(4..)?.to_vec();
+                // The entry's height counts the AND mask, so prefer the image's own size
+                let (width, height) = image_size(&image).unwrap_or((
+                    u32::from(read_u16(data, entry)?),
+                    u32::from(read_u16(data, entry + 2)?) / 2,
+                ));
+                Some(CursorFrame {
+                    width,
+                    height,
+                    hotspot_x: read_u16(&resource, 0)?,
+                    hotspot_y: read_u16(&resource, 2)?,
+                    data: image,
+                })
+            })
+            .collect();
+
+        if frames.is_empty() {
+            return Err(invalid_data("cursor group has no frames"));
+        }
+        Ok(Cursor { frames })
+    }
+
+    pub fn to_cur_bytes(&self) -> Vec<u8> {
+        let count = self.frames.len();
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&CURSOR_TYPE.to_le_bytes());
+        data.extend_from_slice(&(count as u16).to_le_bytes());
+
+        let dimension = |value: u32| if value >= 256 { 0 } else { value as u8 };
+        let mut offset = ICONDIR_SIZE + count * ICONDIRENTRY_SIZE;
+        for frame in &self.frames {
+            data.extend_from_slice(&[dimension(frame.width), dimension(frame.height), 0, 0]);
+            data.extend_from_slice(&frame.hotspot_x.to_le_bytes());
+            data.extend_from_slice(&frame.hotspot_y.to_le_bytes());
+            data.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += frame.data.len();
+        }
+        for frame in &self.frames {
+            data.extend_from_slice(&frame.data);
+        }
+        data
+    }
+
+    pub fn largest_frame(&self) -> Option<&CursorFrame> {
+        self.frames
+            .iter()
+            .max_by_key(|f| (u64::from(f.width) * u64::from(f.height), bit_count(&f.data)))
+    }
+
+    /// The smallest frame at least `size` pixels wide, or the largest one if none is.
+    pub fn frame_for_size(&self, size: u32) -> Option<&CursorFrame> {
+        self.frames
+            .iter()
+            .filter(|f| f.width >= size)
+            .min_by_key(|f| (f.width, u16::MAX - bit_count(&f.data)))
+            .or_else(|| self.largest_frame())
+    }
+
+    pub fn to_image(&self) -> Result<CursorImage, Box<dyn Error>> {
+        self.largest_frame()
+            .ok_or_else(|| invalid_data("cursor has no frames"))?
+            .to_image()
+    }
+
+    /// The frame closest to `size`, resampled to exactly `size` pixels wide when it is
+    /// not, with the hotspot scaled to match.
+    pub fn to_image_with_size(&self, size: u32) -> Result<CursorImage, Box<dyn Error>> {
+        let cursor = self
+            .frame_for_size(size)
+            .ok_or_else(|| invalid_data("cursor has no frames"))?
+            .to_image()?;
+        let (width, height) = cursor.image.dimensions();
+        if width == size || width == 0 {
+            return Ok(cursor);
+        }
+        let scaled_height = (u64::from(height) * u64::from(size) / u64::from(width)).max(1);
+        Ok(cursor.resize(size, scaled_height as u32))
+    }
+}
+
+/// The size stated by a PNG stream or a DIB header, whose height counts the AND mask.
+fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(PNG_SIGNATURE) {
+        let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+        let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+        return Some((width, height));
+    }
+    let width = read_i32(data, 4)?;
+    let height = read_i32(data, 8)?;
+    Some((width.unsigned_abs(), height.unsigned_abs() / 2))
+}
+
+fn bit_count(data: &[u8]) -> u16 {
+    if data.starts_with(PNG_SIGNATURE) {
+        32
+    } else {
+        read_u16(data, 14).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(size: u32, hotspot: u16) -> CursorFrame {
+        let image = RgbaImage::from_pixel(size, size, image::Rgba([0, 0, 0, 255]));
+        CursorFrame {
+            width: size,
+            height: size,
+            hotspot_x: hotspot,
+            hotspot_y: hotspot / 2,
+            data: IconFrame::from_image(&image).unwrap().data,
+        }
+    }
+
+    #[test]
+    fn compares_frame_areas_without_overflowing() {
+        let mut huge = frame(16, 0);
+        huge.width = 70000;
+        huge.height = 70000;
+        let cursor = Cursor {
+            frames: vec![frame(32, 0), huge],
+        };
+        assert_eq!(cursor.largest_frame().unwrap().width, 70000);
+    }
+
+    #[test]
+    fn round_trips_hotspots_through_cur_files() {
+        let cursor = Cursor {
+            frames: vec![frame(32, 10), frame(48, 20)],
+        };
+        let read = Cursor::from_cur_bytes(&cursor.to_cur_bytes()).unwrap();
+        assert_eq!(read, cursor);
+
+        let image = read.to_image().unwrap();
+        assert_eq!(image.image.dimensions(), (48, 48));
+        assert_eq!((image.hotspot_x, image.hotspot_y), (20, 10));
+    }
+
+    #[test]
+    fn reads_hotspots_from_cursor_resources() {
+        let cur = Cursor {
+            frames: vec![frame(32, 0)],
+        };
+        // GRPCURSORDIRENTRY: width, doubled height, planes, bit count, size, ID
+        let mut group = vec![0, 0, 2, 0, 1, 0];
+        for value in [32u16, 64, 1, 32] {
+            group.extend_from_slice(&value.to_le_bytes());
+        }
+        group.extend_from_slice(&(cur.frames[0].data.len() as u32 + 4).to_le_bytes());
+        group.extend_from_slice(&7u16.to_le_bytes());
+
+        let read = Cursor::from_group_bytes(&group, |id| {
+            let mut resource = vec![5, 0, 9, 0];
+            resource.extend_from_slice(&cur.frames[0].data);
+            (id == 7).then_some(resource)
+        })
+        .unwrap();
+        assert_eq!(read.frames[0].width, 32);
+        assert_eq!(read.frames[0].height, 32);
+        assert_eq!((read.frames[0].hotspot_x, read.frames[0].hotspot_y), (5, 9));
+        assert!(Cursor::from_group_bytes(&group, |_| None).is_err());
+    }
+
+    #[test]
+    fn scales_the_hotspot_when_resampling() {
+        let cursor = Cursor {
+            frames: vec![frame(32, 31)],
+        };
+        let image = cursor.to_image_with_size(64).unwrap();
+        assert_eq!(image.image.dimensions(), (64, 64));
+        assert_eq!((image.hotspot_x, image.hotspot_y), (62, 30));
+        let image = cursor.to_image_with_size(16).unwrap();
+        assert_eq!((image.hotspot_x, image.hotspot_y), (15, 8));
+    }
+}
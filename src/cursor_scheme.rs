@@ -0,0 +1,719 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    AnimatedCursor, Cursor, CursorImage,
+    utils::{
+        binary_utils::invalid_data,
+        ini_utils::{Ini, decode_text},
+        path_utils::{find_file_ignore_case, unquote},
+    },
+};
+
+const SCHEMES_KEY: &str = "Control Panel\\Cursors\\Schemes";
+const CURSORS_KEY: &str = "Control Panel\\Cursors";
+const XCURSOR_MAGIC: &[u8] = b"Xcur";
+const XCURSOR_FILE_HEADER_SIZE: u32 = 16;
+const XCURSOR_IMAGE_TYPE: u32 = 0xFFFD_0002;
+const XCURSOR_IMAGE_HEADER_SIZE: u32 = 36;
+
+/// The pointers of a Windows cursor scheme, named after their `Control Panel\Cursors`
+/// values. Scheme strings list them in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CursorRole {
+    Arrow,
+    Help,
+    AppStarting,
+    Wait,
+    Crosshair,
+    IBeam,
+    NWPen,
+    No,
+    SizeNS,
+    SizeWE,
+    SizeNWSE,
+    SizeNESW,
+    SizeAll,
+    UpArrow,
+    Hand,
+    Pin,
+    Person,
+}
+
+const ROLES: [CursorRole; 17] = [
+    CursorRole::Arrow,
+    CursorRole::Help,
+    CursorRole::AppStarting,
+    CursorRole::Wait,
+    CursorRole::Crosshair,
+    CursorRole::IBeam,
+    CursorRole::NWPen,
+    CursorRole::No,
+    CursorRole::SizeNS,
+    CursorRole::SizeWE,
+    CursorRole::SizeNWSE,
+    CursorRole::SizeNESW,
+    CursorRole::SizeAll,
+    CursorRole::UpArrow,
+    CursorRole::Hand,
+    CursorRole::Pin,
+    CursorRole::Person,
+];
+
+impl CursorRole {
+    /// Every role, in scheme string order.
+    pub fn all() -> &'static [CursorRole] {
+        &ROLES
+    }
+
+    /// The `Control Panel\Cursors` value name.
+    pub fn registry_name(self) -> &'static str {
+        match self {
+            CursorRole::Arrow => "Arrow",
+            CursorRole::Help => "Help",
+            CursorRole::AppStarting => "AppStarting",
+            CursorRole::Wait => "Wait",
+            CursorRole::Crosshair => "Crosshair",
+            CursorRole::IBeam => "IBeam",
+            CursorRole::NWPen => "NWPen",
+            CursorRole::No => "No",
+            CursorRole::SizeNS => "SizeNS",
+            CursorRole::SizeWE => "SizeWE",
+            CursorRole::SizeNWSE => "SizeNWSE",
+            CursorRole::SizeNESW => "SizeNESW",
+            CursorRole::SizeAll => "SizeAll",
+            CursorRole::UpArrow => "UpArrow",
+            CursorRole::Hand => "Hand",
+            CursorRole::Pin => "Pin",
+            CursorRole::Person => "Person",
+        }
+    }
+
+    pub fn from_registry_name(name: &str) -> Option<Self> {
+        ROLES
+            .into_iter()
+            .find(|role| role.registry_name().eq_ignore_ascii_case(name))
+    }
+
+    /// The Xcursor names toolkits ask for: the file written first, then the names
+    /// linked to it.
+    pub fn xcursor_names(self) -> &'static [&'static str] {
+        match self {
+            CursorRole::Arrow => &[
+                "left_ptr",
+                "default",
+                "arrow",
+                "top_left_arrow",
+                "left_arrow",
+            ],
+            CursorRole::Help => &[
+                "help",
+                "question_arrow",
+                "whats_this",
+                "left_ptr_help",
+                "5c6cd98b3f3ebcb1f9c7f1c204630408",
+                "d9ce0ab605698f320427677b458ad60b",
+            ],
+            CursorRole::AppStarting => &[
+                "progress",
+                "left_ptr_watch",
+                "half-busy",
+                "00000000000000020006000e7e9ffc3f",
+                "08e8e1c95fe2fc01f976f1e063a24ccd",
+                "3ecb610c1bf2410f44200f48c40d3599",
+            ],
+            CursorRole::Wait => &["wait", "watch"],
+            CursorRole::Crosshair => &[
+                "crosshair",
+                "cross",
+                "tcross",
+                "cross_reverse",
+                "diamond_cross",
+            ],
+            CursorRole::IBeam => &["text", "xterm", "ibeam"],
+            CursorRole::NWPen => &["pencil", "draft"],
+            CursorRole::No => &[
+                "not-allowed",
+                "no-drop",
+                "forbidden",
+                "circle",
+                "crossed_circle",
+                "03b6e0fcb3499374a867c041f52298f0",
+            ],
+            CursorRole::SizeNS => &[
+                "ns-resize",
+                "size_ver",
+                "v_double_arrow",
+                "sb_v_double_arrow",
+                "n-resize",
+                "s-resize",
+                "row-resize",
+                "top_side",
+                "bottom_side",
+                "00008160000006810000408080010102",
+            ],
+            CursorRole::SizeWE => &[
+                "ew-resize",
+                "size_hor",
+                "h_double_arrow",
+                "sb_h_double_arrow",
+                "e-resize",
+                "w-resize",
+                "col-resize",
+                "left_side",
+                "right_side",
+                "028006030e0e7ebffc7f7070c0600140",
+            ],
+            CursorRole::SizeNWSE => &[
+                "nwse-resize",
+                "size_fdiag",
+                "bd_double_arrow",
+                "nw-resize",
+                "se-resize",
+                "top_left_corner",
+                "bottom_right_corner",
+                "c7088f0f3e6c8088236ef8e1e3e70000",
+            ],
+            CursorRole::SizeNESW => &[
+                "nesw-resize",
+                "size_bdiag",
+                "fd_double_arrow",
+                "ne-resize",
+                "sw-resize",
+                "top_right_corner",
+                "bottom_left_corner",
+                "fcf1c3c7cd4491d801f1e1c78f100000",
+            ],
+            CursorRole::SizeAll => &[
+                "move",
+                "fleur",
+                "size_all",
+                "all-scroll",
+                "4498f0e0c1937ffe01fd06f973665830",
+                "9081237383d90e509aa00f00170e968f",
+            ],
+            CursorRole::UpArrow => &["up-arrow", "center_ptr", "sb_up_arrow"],
+            CursorRole::Hand => &[
+                "pointer",
+                "hand",
+                "hand1",
+                "hand2",
+                "pointing_hand",
+                "e29285e634086352946a0e7090d73106",
+                "9d800788f1b08800ae810202380a0822",
+            ],
+            CursorRole::Pin => &["pin"],
+            CursorRole::Person => &["person"],
+        }
+    }
+
+    /// Guesses the role of a cursor file from words in its name, as schemes are
+    /// usually named (`aero_arrow.cur`, `busy.ani`, `Link Select.cur`).
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+        let mut words: Vec<String> = stem
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect();
+        // `Diagonal Resize 1.cur` is matched as a whole
+        words.push(words.concat());
+        let has = |candidates: &[&str]| words.iter().any(|w| candidates.contains(&w.as_str()));
+
+        FILE_NAME_WORDS
+            .iter()
+            .find(|(_, candidates)| has(candidates))
+            .map(|(role, _)| *role)
+    }
+}
+
+/// Words that identify a role in cursor file names, checked in order so that, e.g.,
+/// `working` wins over `arrow` in `working_arrow.ani`.
+const FILE_NAME_WORDS: [(CursorRole, &[&str]); 17] = [
+    (
+        CursorRole::AppStarting,
+        &[
+            "appstarting",
+            "working",
+            "background",
+            "progress",
+            "starting",
+        ],
+    ),
+    (CursorRole::Wait, &["wait", "busy", "watch", "hourglass"]),
+    (CursorRole::Help, &["help", "helpsel", "question"]),
+    (CursorRole::Hand, &["hand", "link"]),
+    (CursorRole::Crosshair, &["crosshair", "cross", "precision"]),
+    (CursorRole::IBeam, &["ibeam", "beam", "text"]),
+    (
+        CursorRole::NWPen,
+        &["nwpen", "pen", "handwriting", "handwrt"],
+    ),
+    (
+        CursorRole::No,
+        &["no", "unavail", "unavailable", "forbidden", "notallowed"],
+    ),
+    (
+        CursorRole::SizeNWSE,
+        &["sizenwse", "nwse", "dgn1", "diagonal1", "diagonalresize1"],
+    ),
+    (
+        CursorRole::SizeNESW,
+        &["sizenesw", "nesw", "dgn2", "diagonal2", "diagonalresize2"],
+    ),
+    (CursorRole::SizeNS, &["sizens", "ns", "vert", "vertical"]),
+    (
+        CursorRole::SizeWE,
+        &["sizewe", "ew", "we", "horz", "horizontal"],
+    ),
+    (CursorRole::SizeAll, &["sizeall", "move", "fleur"]),
+    (CursorRole::UpArrow, &["uparrow", "up", "alternate", "alt"]),
+    (CursorRole::Pin, &["pin", "location"]),
+    (CursorRole::Person, &["person"]),
+    (
+        CursorRole::Arrow,
+        &["arrow", "pointer", "normal", "default"],
+    ),
+];
+
+/// A Windows cursor scheme: a cursor or animated cursor file per role.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CursorScheme {
+    pub name: String,
+    pub cursors: Vec<(CursorRole, PathBuf)>,
+}
+
+impl CursorScheme {
+    /// Reads a scheme from an `.inf` installer, or from a folder: its `.inf` when it
+    /// has one, otherwise its `.cur` and `.ani` files matched by name.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if path.is_file() {
+            return Self::from_inf(path);
+        }
+        let mut infs: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| has_extension(path, "inf"))
+            .collect();
+        infs.sort();
+        match infs.first() {
+            Some(inf) => Self::from_inf(inf),
+            None => Self::from_folder(path),
+        }
+    }
+
+    /// Matches the `.cur` and `.ani` files of `folder` to roles by file name. The
+    /// first file, by name, wins for each role.
+    pub fn from_folder<P: AsRef<Path>>(folder: P) -> Result<Self, Box<dyn Error>> {
+        let folder = folder.as_ref();
+        let mut files: Vec<PathBuf> = fs::read_dir(folder)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| has_extension(path, "cur") || has_extension(path, "ani"))
+            .collect();
+        files.sort();
+
+        let mut cursors: Vec<(CursorRole, PathBuf)> = Vec::new();
+        for file in files {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(role) = CursorRole::from_file_name(&name)
+                && !cursors.iter().any(|(r, _)| *r == role)
+            {
+                cursors.push((role, file));
+            }
+        }
+        cursors.sort();
+        if cursors.is_empty() {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                format!("no cursors found in: {folder:?}"),
+            )));
+        }
+
+        Ok(CursorScheme {
+            name: folder
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            cursors,
+        })
+    }
+
+    /// Reads the scheme an installer `.inf` registers: the `Control Panel\Cursors`
+    /// values it writes, or else its `Schemes` entry. Files are looked up next to the
+    /// `.inf`, by name.
+    pub fn from_inf<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = decode_text(&fs::read(path)?);
+        let strings = Ini::parse(&text);
+        let expand = |value: &str| expand_inf_strings(value, &strings);
+        let folder = path.parent().unwrap_or(Path::new("."));
+        let locate = |value: &str| {
+            let value = expand(value);
+            let file_name = value.rsplit(['\\', '/']).next().unwrap_or_default().trim();
+            find_file_ignore_case(folder, file_name)
+        };
+
+        let mut name = None;
+        let mut values: Vec<(CursorRole, PathBuf)> = Vec::new();
+        let mut scheme: Vec<(CursorRole, PathBuf)> = Vec::new();
+        for line in text.lines() {
+            let fields = split_inf_fields(line);
+            let [_, key, value_name, _, data, ..] = fields.as_slice() else {
+                continue;
+            };
+            let key = expand(key);
+            if key.eq_ignore_ascii_case(SCHEMES_KEY) {
+                name.get_or_insert_with(|| expand(value_name));
+                scheme = expand(data)
+                    .split(',')
+                    .zip(ROLES)
+                    .filter_map(|(file, role)| Some((role, locate(file)?)))
+                    .collect();
+            } else if key.eq_ignore_ascii_case(CURSORS_KEY)
+                && let Some(role) = CursorRole::from_registry_name(&expand(value_name))
+                && let Some(file) = locate(data)
+            {
+                values.retain(|(r, _)| *r != role);
+                values.push((role, file));
+            }
+        }
+
+        let mut cursors = if values.is_empty() { scheme } else { values };
+        cursors.sort();
+        if cursors.is_empty() {
+            return Err(invalid_data(format!("no cursor scheme in: {path:?}")));
+        }
+        let name = name
+            .filter(|name| !name.is_empty())
+            .or_else(|| {
+                strings
+                    .get("Strings", "SCHEME_NAME")
+                    .map(|n| unquote(n).to_owned())
+            })
+            .unwrap_or_else(|| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+        Ok(CursorScheme { name, cursors })
+    }
+
+    pub fn cursor(&self, role: CursorRole) -> Option<&Path> {
+        self.cursors
+            .iter()
+            .find(|(r, _)| *r == role)
+            .map(|(_, path)| path.as_path())
+    }
+
+    /// Writes the scheme as an Xcursor theme in `<output>/<name>`: a file per role
+    /// with every nominal size in `sizes` (animated for `.ani` cursors), links for the
+    /// other names toolkits use, and `index.theme`. Returns the theme folder.
+    pub fn export_xcursor_theme<P: AsRef<Path>>(
+        &self,
+        output: P,
+        sizes: &[u32],
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        if sizes.is_empty() {
+            return Err(invalid_data("no cursor sizes to export"));
+        }
+        let theme = output.as_ref().join(theme_folder_name(&self.name));
+        let cursors_folder = theme.join("cursors");
+        fs::create_dir_all(&cursors_folder)?;
+
+        for (role, path) in &self.cursors {
+            let images = xcursor_images(path, sizes)?;
+            let names = role.xcursor_names();
+            fs::write(cursors_folder.join(names[0]), encode_xcursor(&images))?;
+            for alias in &names[1..] {
+                link_cursor(&cursors_folder, names[0], alias)?;
+            }
+        }
+
+        fs::write(
+            theme.join("index.theme"),
+            format!(
+                "[Icon Theme]\nName={}\nComment=Converted from the Windows cursor scheme {}\n",
+                self.name, self.name
+            ),
+        )?;
+        Ok(theme)
+    }
+}
+
+/// One image of an Xcursor file. Images with the same nominal size play in order,
+/// each for `delay` milliseconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XcursorImage {
+    pub nominal_size: u32,
+    pub cursor: CursorImage,
+    pub delay: u32,
+}
+
+/// Encodes an Xcursor file: a table of contents and an image chunk per image, with
+/// premultiplied ARGB pixels.
+pub fn encode_xcursor(images: &[XcursorImage]) -> Vec<u8> {
+    let toc_size = 12 * images.len() as u32;
+    let mut data = Vec::new();
+    data.extend_from_slice(XCURSOR_MAGIC);
+    data.extend_from_slice(&XCURSOR_FILE_HEADER_SIZE.to_le_bytes());
+    data.extend_from_slice(&0x1_0000u32.to_le_bytes());
+    data.extend_from_slice(&(images.len() as u32).to_le_bytes());
+
+    let mut position = XCURSOR_FILE_HEADER_SIZE + toc_size;
+    for image in images {
+        data.extend_from_slice(&XCURSOR_IMAGE_TYPE.to_le_bytes());
+        data.extend_from_slice(&image.nominal_size.to_le_bytes());
+        data.extend_from_slice(&position.to_le_bytes());
+        let (width, height) = image.cursor.image.dimensions();
+        position += XCURSOR_IMAGE_HEADER_SIZE + 4 * width * height;
+    }
+
+    for image in images {
+        let cursor = &image.cursor;
+        let (width, height) = cursor.image.dimensions();
+        for value in [
+            XCURSOR_IMAGE_HEADER_SIZE,
+            XCURSOR_IMAGE_TYPE,
+            image.nominal_size,
+            1,
+            width,
+            height,
+            cursor.hotspot_x.min(width.saturating_sub(1)),
+            cursor.hotspot_y.min(height.saturating_sub(1)),
+            image.delay,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for pixel in cursor.image.pixels() {
+            let [r, g, b, a] = pixel.0;
+            let premultiply = |c: u8| ((u16::from(c) * u16::from(a) + 127) / 255) as u8;
+            data.extend_from_slice(&[premultiply(b), premultiply(g), premultiply(r), a]);
+        }
+    }
+    data
+}
+
+/// The images of a `.cur` or `.ani` file at each nominal size.
+fn xcursor_images(path: &Path, sizes: &[u32]) -> Result<Vec<XcursorImage>, Box<dyn Error>> {
+    let mut images = Vec::new();
+    if has_extension(path, "ani") {
+        let animation = AnimatedCursor::open(path)?;
+        for &size in sizes {
+            for (cursor, step) in animation.to_images(Some(size))? {
+                let delay = u32::try_from(step.duration().as_millis()).unwrap_or(u32::MAX);
+                images.push(XcursorImage {
+                    nominal_size: size,
+                    cursor,
+                    delay: delay.max(1),
+                });
+            }
+        }
+    } else {
+        let cursor = Cursor::open(path)?;
+        for &size in sizes {
+            images.push(XcursorImage {
+                nominal_size: size,
+                cursor: cursor.to_image_with_size(size)?,
+                delay: 0,
+            });
+        }
+    }
+    Ok(images)
+}
+
+#[cfg(unix)]
+fn link_cursor(folder: &Path, target: &str, alias: &str) -> io::Result<()> {
+    let link = folder.join(alias);
+    if fs::symlink_metadata(&link).is_ok() {
+        fs::remove_file(&link)?;
+    }
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Without symbolic links, aliases are copies.
+#[cfg(not(unix))]
+fn link_cursor(folder: &Path, target: &str, alias: &str) -> io::Result<()> {
+    fs::copy(folder.join(target), folder.join(alias)).map(|_| ())
+}
+
+fn theme_folder_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "Windows".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Splits an `.inf` registry line (`HKCU,"key",value,flags,"data"`) into unquoted
+/// fields. Commas inside quotes belong to the field.
+fn split_inf_fields(line: &str) -> Vec<String> {
+    let line = line.trim();
+    if line.starts_with(';') || line.starts_with('[') {
+        return Vec::new();
+    }
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_owned()),
+            ';' if !quoted => break,
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_owned());
+    fields
+}
+
+/// Replaces `%name%` with the `[Strings]` value; other references, like the `%10%`
+/// directory IDs, are left alone.
+fn expand_inf_strings(value: &str, strings: &Ini) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => {
+                let name = &after[..end];
+                match strings.get("Strings", name) {
+                    Some(text) => result.push_str(unquote(text)),
+                    None => {
+                        result.push('%');
+                        result.push_str(name);
+                        result.push('%');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn lays_out_xcursor_files() {
+        let image = |width, hotspot, delay| XcursorImage {
+            nominal_size: 24,
+            cursor: CursorImage {
+                image: RgbaImage::from_pixel(width, 1, Rgba([200, 100, 0, 128])),
+                hotspot_x: hotspot,
+                hotspot_y: hotspot,
+            },
+            delay,
+        };
+        let data = encode_xcursor(&[image(2, 5, 50), image(3, 1, 70)]);
+
+        assert_eq!(&data[..4], XCURSOR_MAGIC);
+        assert_eq!(u32_at(&data, 4), XCURSOR_FILE_HEADER_SIZE);
+        assert_eq!(u32_at(&data, 12), 2);
+        // Table of contents: type, nominal size and position per image
+        assert_eq!(u32_at(&data, 16), XCURSOR_IMAGE_TYPE);
+        assert_eq!(u32_at(&data, 20), 24);
+        assert_eq!(u32_at(&data, 24), 40);
+        assert_eq!(u32_at(&data, 36), 40 + 36 + 2 * 4);
+
+        let chunk: Vec<u32> = (0..9).map(|i| u32_at(&data, 40 + i * 4)).collect();
+        // The hotspot is clamped into the image
+        assert_eq!(chunk, [36, XCURSOR_IMAGE_TYPE, 24, 1, 2, 1, 1, 0, 50]);
+        assert_eq!(&data[76..80], [0, 50, 100, 128]);
+        assert_eq!(u32_at(&data, 84 + 32), 70);
+        assert_eq!(data.len(), 84 + 36 + 3 * 4);
+    }
+
+    #[test]
+    fn reads_schemes_from_inf_files() {
+        let folder = std::env::temp_dir().join(format!("cursor-scheme-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        for file in ["Arrow.cur", "help.cur", "busy.ani"] {
+            fs::write(folder.join(file), b"").unwrap();
+        }
+        let strings = "[Strings]\r\nCUR_DIR = \"Cursors\\Blue\"\r\nSCHEME_NAME = \"Blue\"\r\n\
+                       pointer = \"arrow.cur\"\r\nhelp = \"help.cur\"\r\n";
+        let schemes = "HKCU,\"Control Panel\\Cursors\\Schemes\",\"%SCHEME_NAME%\",,\
+                       \"%10%\\%CUR_DIR%\\%pointer%,%10%\\%CUR_DIR%\\%help%\"\r\n";
+
+        let inf = folder.join("scheme.inf");
+        fs::write(&inf, format!("[Scheme.Reg]\r\n{schemes}\r\n{strings}")).unwrap();
+        let scheme = CursorScheme::from_inf(&inf).unwrap();
+        assert_eq!(scheme.name, "Blue");
+        assert_eq!(
+            scheme.cursors,
+            [
+                (CursorRole::Arrow, folder.join("Arrow.cur")),
+                (CursorRole::Help, folder.join("help.cur")),
+            ]
+        );
+
+        // Values written to `Control Panel\Cursors` take precedence over the entry
+        let values = "HKCU,\"Control Panel\\Cursors\",Wait,0x00020000,\"%10%\\Cursors\\busy.ani\"\r\n\
+                      HKCU,\"Control Panel\\Cursors\",Arrow,,\"missing.cur\" ; not shipped\r\n\
+                      HKCU,\"Control Panel\\Cursors\",Scheme Source,0x00010001,2\r\n";
+        fs::write(
+            &inf,
+            format!("[Scheme.Reg]\r\n{schemes}{values}\r\n{strings}"),
+        )
+        .unwrap();
+        let scheme = CursorScheme::from_inf(&inf).unwrap();
+        assert_eq!(scheme.name, "Blue");
+        assert_eq!(
+            scheme.cursors,
+            [(CursorRole::Wait, folder.join("busy.ani"))]
+        );
+
+        fs::write(&inf, strings).unwrap();
+        assert!(CursorScheme::from_inf(&inf).is_err());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn guesses_roles_from_file_names() {
+        let role = CursorRole::from_file_name;
+        assert_eq!(role("aero_arrow.cur"), Some(CursorRole::Arrow));
+        assert_eq!(role("busy.ani"), Some(CursorRole::Wait));
+        assert_eq!(role("working_arrow.ani"), Some(CursorRole::AppStarting));
+        assert_eq!(role("Link Select.cur"), Some(CursorRole::Hand));
+        assert_eq!(role("Text Select.cur"), Some(CursorRole::IBeam));
+        assert_eq!(role("Diagonal Resize 1.cur"), Some(CursorRole::SizeNWSE));
+        assert_eq!(role("Diagonal Resize 2.cur"), Some(CursorRole::SizeNESW));
+        assert_eq!(role("aero_ns.cur"), Some(CursorRole::SizeNS));
+        assert_eq!(role("readme.txt"), None);
+    }
+}
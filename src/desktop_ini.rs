@@ -0,0 +1,147 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    DllIcon, IconSource,
+    utils::{
+        ini_utils::Ini,
+        path_utils::{find_file_ignore_case, resolve_icon_path, split_icon_location},
+    },
+};
+
+const SHELL_CLASS_INFO: &str = ".ShellClassInfo";
+
+/// The `[.ShellClassInfo]` section of a customized folder's `desktop.ini`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DesktopIni {
+    pub icon_resource: Option<String>,
+    pub icon_file: Option<String>,
+    pub icon_index: i32,
+    pub localized_resource_name: Option<String>,
+    pub info_tip: Option<String>,
+    pub clsid: Option<String>,
+}
+
+impl DesktopIni {
+    /// Reads `desktop.ini` from `folder`.
+    pub fn open<P: AsRef<Path>>(folder: P) -> Result<Self, Box<dyn Error>> {
+        let folder = folder.as_ref();
+        let path = find_file_ignore_case(folder, "desktop.ini").ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no desktop.ini in folder: {folder:?}"),
+            )
+        })?;
+        Ok(Self::from_bytes(&fs::read(path)?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let ini = Ini::from_bytes(data);
+        let get = |key| ini.get(SHELL_CLASS_INFO, key).map(str::to_owned);
+        DesktopIni {
+            icon_resource: get("IconResource"),
+            icon_file: get("IconFile"),
+            icon_index: get("IconIndex")
+                .and_then(|i| i.parse().ok())
+                .unwrap_or_default(),
+            localized_resource_name: get("LocalizedResourceName"),
+            info_tip: get("InfoTip"),
+            clsid: get("CLSID").or_else(|| get("CLSID2")),
+        }
+    }
+
+    /// `IconResource` (Vista and later) wins over the legacy `IconFile`/`IconIndex` pair.
+    /// Relative paths are resolved against `folder`.
+    pub fn icon_source(&self, folder: Option<&Path>) -> Option<IconSource> {
+        let (path, index) = match (&self.icon_resource, &self.icon_file) {
+            (Some(resource), _) => split_icon_location(resource),
+            (None, Some(file)) => (file.to_owned(), self.icon_index),
+            (None, None) => return None,
+        };
+
+        let path = resolve_icon_path(&path, folder);
+        let icon = DllIcon::new().with_icon_location(path, index);
+        Some(IconSource::Resource(icon))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_resource_wins_over_icon_file() {
+        let ini = DesktopIni::from_bytes(
+            b"[.ShellClassInfo]\r\nIconFile=old.ico\r\nIconIndex=4\r\nIconResource=C:\\Windows\\System32\\imageres.dll,-3\r\n",
+        );
+        assert_eq!(
+            ini.icon_source(None),
+            Some(IconSource::Resource(
+                DllIcon::new().with_icon_location(r"C:\Windows\System32\imageres.dll", -3)
+            ))
+        );
+    }
+
+    #[test]
+    fn reads_the_legacy_icon_file_pair() {
+        let ini = DesktopIni::from_bytes(
+            b"[.shellclassinfo]\nIconFile=C:\\folder.ico\nIconIndex=2\nInfoTip=Tip\nCLSID2={0AFACED1-E828-11D1-9187-B532F1E9575D}\n",
+        );
+        assert_eq!(ini.info_tip.as_deref(), Some("Tip"));
+        assert_eq!(
+            ini.clsid.as_deref(),
+            Some("{0AFACED1-E828-11D1-9187-B532F1E9575D}")
+        );
+        assert_eq!(
+            ini.icon_source(None),
+            Some(IconSource::Resource(
+                DllIcon::new().with_icon_location(r"C:\folder.ico", 2)
+            ))
+        );
+        assert_eq!(
+            DesktopIni::from_bytes(b"[.ShellClassInfo]\n").icon_source(None),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_environment_references() {
+        let folder = std::env::temp_dir();
+        let ini = DesktopIni::from_bytes(
+            b"[.ShellClassInfo]\nIconResource=%SystemRoot%\\System32\\shell32.dll,4\n",
+        );
+        assert_eq!(
+            ini.icon_source(Some(&folder)),
+            Some(IconSource::Resource(
+                DllIcon::new().with_icon_location(r"%SystemRoot%\System32\shell32.dll", 4)
+            ))
+        );
+    }
+
+    #[test]
+    fn resolves_icons_next_to_the_folder() {
+        let folder = std::env::temp_dir().join(format!("desktop-ini-{}", std::process::id()));
+        fs::create_dir_all(folder.join("icons")).unwrap();
+        fs::write(folder.join("icons").join("folder.ico"), b"").unwrap();
+        let text: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "[.ShellClassInfo]\r\nIconResource=icons\\folder.ico,0\r\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        fs::write(folder.join("Desktop.ini"), text).unwrap();
+
+        let source = DesktopIni::open(&folder)
+            .unwrap()
+            .icon_source(Some(&folder));
+        let expected = DllIcon::new().with_icon_location(folder.join("icons/folder.ico"), 0);
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(source, Some(IconSource::Resource(expected)));
+    }
+}
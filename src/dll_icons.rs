@@ -1,34 +1,55 @@
+#[cfg(windows)]
 use crate::utils::image_utils::hicon_to_image;
+use crate::{
+    CatalogEntry, Cursor, IconCatalog, IconGroup, MuiModule, ResourceId, StockIcon,
+    icon_catalog::{check_location, stale, unknown_name},
+};
 
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
 use std::{
     error::Error,
     ffi::OsStr,
     io::{self, ErrorKind},
-    os::windows::ffi::OsStrExt,
     path::{Path, PathBuf},
 };
 
 use image::RgbaImage;
+#[cfg(windows)]
 use windows::{
     Win32::{
         Foundation::{FreeLibrary, HANDLE, HMODULE},
-        System::LibraryLoader::{GetModuleHandleW, LoadLibraryW},
+        System::{
+            LibraryLoader::{GetModuleHandleW, LoadLibraryW},
+            Registry::{HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ, RegGetValueW},
+        },
         UI::{
-            Shell::ExtractIconW,
-            WindowsAndMessaging::{HICON, IMAGE_ICON, LR_CREATEDIBSECTION, LoadImageW},
+            Shell::{
+                ExtractIconW, SHGSI_ICON, SHGSI_LARGEICON, SHGetStockIconInfo, SHSTOCKICONID,
+                SHSTOCKICONINFO,
+            },
+            WindowsAndMessaging::{
+                GetSystemMetrics, HICON, IMAGE_ICON, LR_CREATEDIBSECTION, LoadImageW, SM_CXICON,
+            },
         },
     },
-    core::{HSTRING, PCWSTR},
+    core::{HSTRING, PCWSTR, w},
 };
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum DllResource {
     System(String, u32),
     Other(PathBuf, String, u32),
+    Location(PathBuf, i32),
+    Stock(StockIcon),
+    Catalog(String),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct DllIcon(Option<DllResource>);
+pub struct DllIcon {
+    resource: Option<DllResource>,
+    languages: Vec<u16>,
+}
 
 impl Default for DllIcon {
     fn default() -> Self {
@@ -38,64 +59,250 @@ impl Default for DllIcon {
 
 impl DllIcon {
     pub fn new() -> Self {
-        DllIcon(None)
+        DllIcon {
+            resource: None,
+            languages: Vec::new(),
+        }
+    }
+
+    fn with(self, resource: DllResource) -> Self {
+        DllIcon {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Preferred resource languages (LANGIDs such as `0x0407`), most preferred first.
+    /// Reading resources directly, the module's `.mui` satellites are searched too and
+    /// the language falls back as Windows does: exact, neutral, default, first.
+    pub fn with_languages(self, languages: &[u16]) -> Self {
+        DllIcon {
+            languages: languages.to_vec(),
+            ..self
+        }
+    }
+
+    pub fn languages(&self) -> &[u16] {
+        &self.languages
     }
 
     pub fn with_resource<P: AsRef<Path>>(self, path: P, name: &str, size: u32) -> Self {
         let path = path.as_ref().to_path_buf();
-        DllIcon(Some(DllResource::Other(path, name.to_owned(), size)))
+        self.with(DllResource::Other(path, name.to_owned(), size))
+    }
+
+    /// Uses Windows icon location semantics (`path,index`): a zero-based index into the
+    /// icon groups, or a negative value naming the group by resource ID.
+    pub fn with_icon_location<P: AsRef<Path>>(self, path: P, index: i32) -> Self {
+        let path = path.as_ref().to_path_buf();
+        self.with(DllResource::Location(path, index))
+    }
+
+    /// A shell stock icon, e.g. `DllIcon::stock(StockIcon::Shield)`.
+    pub fn stock(icon: StockIcon) -> Self {
+        DllIcon::new().with(DllResource::Stock(icon))
+    }
+
+    /// A named icon from [`IconCatalog::builtin`], e.g. `"recycle-bin-full"`. Its
+    /// module and resource ID are chosen for the Windows build at extraction time.
+    pub fn from_catalog(name: &str) -> Result<Self, Box<dyn Error>> {
+        if !IconCatalog::builtin().contains(name) {
+            return Err(unknown_name(name));
+        }
+        Ok(DllIcon::new().with(DllResource::Catalog(name.to_ascii_lowercase())))
     }
 
     pub fn with_shell32(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("shell32.dll".to_owned(), index)))
+        self.with(DllResource::System("shell32.dll".to_owned(), index))
     }
 
     pub fn with_imageres(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("imageres.dll".to_owned(), index)))
+        self.with(DllResource::System("imageres.dll".to_owned(), index))
     }
 
     pub fn with_ddores(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("ddores.dll".to_owned(), index)))
+        self.with(DllResource::System("ddores.dll".to_owned(), index))
     }
 
     pub fn with_mmres(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("mmres.dll".to_owned(), index)))
+        self.with(DllResource::System("mmres.dll".to_owned(), index))
     }
 
     pub fn with_wmploc(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("wmploc.dll".to_owned(), index)))
+        self.with(DllResource::System("wmploc.dll".to_owned(), index))
     }
 
     pub fn with_dmdskres(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("dmdskres.dll".to_owned(), index)))
+        self.with(DllResource::System("dmdskres.dll".to_owned(), index))
     }
 
     pub fn with_setupapi(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("setupapi.dll".to_owned(), index)))
+        self.with(DllResource::System("setupapi.dll".to_owned(), index))
     }
 
     pub fn with_explorer(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("explorer.exe".to_owned(), index)))
+        self.with(DllResource::System("explorer.exe".to_owned(), index))
     }
 
     pub fn with_imagesp1(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("imagesp1.dll".to_owned(), index)))
+        self.with(DllResource::System("imagesp1.dll".to_owned(), index))
     }
 
     pub fn with_pifmgr(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System("pifmgr.dll".to_owned(), index)))
+        self.with(DllResource::System("pifmgr.dll".to_owned(), index))
     }
 
     pub fn with_networkexplorer(self, index: u32) -> Self {
-        DllIcon(Some(DllResource::System(
-            "networkexplorer.dll".to_owned(),
-            index,
-        )))
+        self.with(DllResource::System("networkexplorer.dll".to_owned(), index))
     }
 }
 
+/// An icon read from a module or icon file, with where it was found.
+#[derive(Clone, Debug)]
+pub struct ExtractedIcon {
+    pub image: RgbaImage,
+    /// The file the icon was read from.
+    pub path: PathBuf,
+    /// The module that was asked for, when the icon was read from another file at
+    /// `path`: the Windows 11 `SystemResources\*.mun` file its icons were moved to, or
+    /// one of its `.mui` satellites.
+    pub redirected_from: Option<PathBuf>,
+    /// The `RT_GROUP_ICON` the icon came from, for modules.
+    pub group: Option<ResourceId>,
+    /// The LANGID of the icon group, for modules.
+    pub language: Option<u16>,
+}
+
+enum GroupSelector<'a> {
+    Location(i32),
+    Name(ResourceId, u32),
+    Catalog(&'a CatalogEntry),
+}
+
+impl DllIcon {
+    /// Reads the icon straight from the module's resources instead of loading it, so
+    /// it works on any platform. `locate` maps the module path, which may be a bare
+    /// name like `shell32.dll` or an unexpanded Windows path, to a readable file.
+    /// Stock icons are looked up for the Windows `build` the modules come from.
+    pub(crate) fn extract_with<F>(
+        &self,
+        build: u32,
+        locate: F,
+    ) -> Result<ExtractedIcon, Box<dyn Error>>
+    where
+        F: Fn(&Path) -> Option<PathBuf>,
+    {
+        let resource = self
+            .resource
+            .as_ref()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no dll resources added"))?;
+        let locate = |path: &Path| {
+            locate(path).ok_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, format!("module not found: {path:?}"))
+            })
+        };
+
+        let (module, selector) = match resource {
+            DllResource::System(name, index) => {
+                let index = index.checked_sub(1).ok_or("index underflow")?;
+                (
+                    locate(Path::new(name))?,
+                    GroupSelector::Location(index as i32),
+                )
+            }
+            DllResource::Other(path, name, size) => (
+                locate(path)?,
+                GroupSelector::Name(ResourceId::parse(name), *size),
+            ),
+            DllResource::Location(path, index) => {
+                let file = locate(path)?;
+                let extension = file
+                    .extension()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let image = match extension.as_str() {
+                    "ico" => Some(IconGroup::open(&file)?.to_image()?),
+                    "cur" => Some(Cursor::open(&file)?.to_image()?.image),
+                    "png" | "jpg" | "jpeg" | "bmp" | "gif" => Some(image::open(&file)?.to_rgba8()),
+                    _ => None,
+                };
+                if let Some(image) = image {
+                    return Ok(ExtractedIcon {
+                        image,
+                        path: file,
+                        redirected_from: None,
+                        group: None,
+                        language: None,
+                    });
+                }
+                (file, GroupSelector::Location(*index))
+            }
+            DllResource::Stock(icon) => {
+                let entry = icon.catalog_entry(build).ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("no known location for stock icon {icon:?} on build {build}"),
+                    )
+                })?;
+                (
+                    locate(Path::new(&entry.module))?,
+                    GroupSelector::Catalog(entry),
+                )
+            }
+            DllResource::Catalog(name) => {
+                let entry = catalog_entry(name, build)?;
+                (
+                    locate(Path::new(&entry.module))?,
+                    GroupSelector::Catalog(entry),
+                )
+            }
+        };
+
+        let mui_module = MuiModule::open(&module)?;
+        let (_, pe) = mui_module.base();
+        let (name, size) = match selector {
+            GroupSelector::Location(index) => (pe.icon_group_name_at(index)?, None),
+            GroupSelector::Name(name, size) => (name, Some(size)),
+            GroupSelector::Catalog(entry) => {
+                if let Some(reason) = check_location(pe, entry) {
+                    return Err(stale(entry, &reason));
+                }
+                (pe.icon_group_name_at(entry.location)?, None)
+            }
+        };
+
+        let (icon_group, path, language) = mui_module.icon_group(&name, &self.languages)?;
+        let image = match size {
+            Some(size) => icon_group.to_image_with_size(size)?,
+            None => icon_group.to_image()?,
+        };
+        let path = path.to_path_buf();
+        let redirected_from = (path != module).then_some(module);
+
+        Ok(ExtractedIcon {
+            image,
+            path,
+            redirected_from,
+            group: Some(name),
+            language: Some(language),
+        })
+    }
+}
+
+fn catalog_entry(name: &str, build: u32) -> Result<&'static CatalogEntry, Box<dyn Error>> {
+    IconCatalog::builtin().lookup(name, build).ok_or_else(|| {
+        Box::new(io::Error::new(
+            ErrorKind::NotFound,
+            format!("icon '{name}' is not in the catalog for build {build}"),
+        )) as Box<dyn Error>
+    })
+}
+
+#[cfg(windows)]
 struct AutoModule(HMODULE);
 
+#[cfg(windows)]
 impl Drop for AutoModule {
     fn drop(&mut self) {
         if !self.0.0.is_null() {
@@ -104,16 +311,42 @@ impl Drop for AutoModule {
     }
 }
 
+#[cfg(windows)]
 #[allow(non_snake_case)]
 fn MAKEINTRESOURCEW(id: i32) -> PCWSTR {
     unsafe { std::mem::transmute::<_, PCWSTR>(id as usize) }
 }
 
+/// The build of the running system, from `CurrentBuildNumber`.
+#[cfg(windows)]
+fn current_build() -> Option<u32> {
+    let mut buffer = [0u16; 32];
+    let mut size = u32::try_from(std::mem::size_of_val(&buffer)).ok()?;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            w!("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion"),
+            w!("CurrentBuildNumber"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    };
+    if result.is_err() {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len]).trim().parse().ok()
+}
+
+#[cfg(windows)]
 pub fn get_dll_hicon_to_image(dll_icon: DllIcon) -> Result<RgbaImage, Box<dyn Error>> {
     let hicon = unsafe { get_dll_hicon(dll_icon) }?;
     unsafe { hicon_to_image(hicon) }
 }
 
+#[cfg(windows)]
 unsafe fn get_hicon_handle(
     dll_name: &HSTRING,
     name: PCWSTR,
@@ -143,9 +376,10 @@ unsafe fn get_hicon_handle(
     }
 }
 
+#[cfg(windows)]
 unsafe fn get_dll_hicon(dll_icon: DllIcon) -> Result<HICON, Box<dyn Error>> {
     let resource = dll_icon
-        .0
+        .resource
         .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no dll resources added"))?;
 
     match resource {
@@ -155,10 +389,9 @@ unsafe fn get_dll_hicon(dll_icon: DllIcon) -> Result<HICON, Box<dyn Error>> {
             let hicon = unsafe { ExtractIconW(None, &dll_name, index) };
             if hicon.0.is_null() {
                 let last_error = windows::core::Error::from_win32();
-                Err(Box::new(io::Error::new(
-                    ErrorKind::Other,
-                    format!("failed to extract icon from index - {last_error}"),
-                )))
+                Err(Box::new(io::Error::other(format!(
+                    "failed to extract icon from index - {last_error}"
+                ))))
             } else {
                 Ok(hicon)
             }
@@ -178,13 +411,64 @@ unsafe fn get_dll_hicon(dll_icon: DllIcon) -> Result<HICON, Box<dyn Error>> {
 
             if hicon_handle.0.is_null() {
                 let last_error = windows::core::Error::from_win32();
-                Err(Box::new(io::Error::new(
-                    ErrorKind::Other,
-                    format!("failed to get hIcon from resource: {name} - {last_error}."),
-                )))
+                Err(Box::new(io::Error::other(format!(
+                    "failed to get hIcon from resource: {name} - {last_error}."
+                ))))
+            } else {
+                Ok(HICON(hicon_handle.0))
+            }
+        }
+        // ExtractIconW returns the icon count for -1, so resource IDs are loaded by ID
+        DllResource::Location(path, index) if index < 0 => {
+            let id = index.checked_neg().ok_or("invalid icon resource ID")?;
+            let wide_path: Vec<u16> = OsStr::new(&path).encode_wide().chain(Some(0)).collect();
+            let file_name = HSTRING::from_wide(&wide_path);
+            // The size ExtractIconW returns icons at
+            let size = u32::try_from(unsafe { GetSystemMetrics(SM_CXICON) }).unwrap_or(32);
+            let hicon_handle =
+                unsafe { get_hicon_handle(&file_name, MAKEINTRESOURCEW(id), size, size) }?;
+            if hicon_handle.0.is_null() {
+                let last_error = windows::core::Error::from_win32();
+                Err(Box::new(io::Error::other(format!(
+                    "failed to load icon: {path:?},{index} - {last_error}"
+                ))))
             } else {
                 Ok(HICON(hicon_handle.0))
             }
         }
+        DllResource::Location(path, index) => {
+            let wide_path: Vec<u16> = OsStr::new(&path).encode_wide().chain(Some(0)).collect();
+            let file_name = HSTRING::from_wide(&wide_path);
+            let hicon = unsafe { ExtractIconW(None, &file_name, index as u32) };
+            if hicon.0.is_null() {
+                let last_error = windows::core::Error::from_win32();
+                Err(Box::new(io::Error::other(format!(
+                    "failed to extract icon: {path:?},{index} - {last_error}"
+                ))))
+            } else {
+                Ok(hicon)
+            }
+        }
+        DllResource::Stock(icon) => {
+            let mut info = SHSTOCKICONINFO {
+                cbSize: u32::try_from(std::mem::size_of::<SHSTOCKICONINFO>())?,
+                ..Default::default()
+            };
+            unsafe {
+                SHGetStockIconInfo(
+                    SHSTOCKICONID(icon.id()),
+                    SHGSI_ICON | SHGSI_LARGEICON,
+                    &mut info,
+                )
+            }
+            .map_err(|e| io::Error::other(format!("failed to get stock icon {icon:?} - {e}")))?;
+            Ok(info.hIcon)
+        }
+        DllResource::Catalog(name) => {
+            let entry = catalog_entry(&name, current_build().unwrap_or(u32::MAX))?;
+            let location = DllIcon::new().with_icon_location(&entry.module, entry.location);
+            // A missing resource ID fails instead of falling back to another icon
+            unsafe { get_dll_hicon(location) }.map_err(|e| stale(entry, &e.to_string()))
+        }
     }
 }
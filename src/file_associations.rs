@@ -0,0 +1,240 @@
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{DllIcon, IconSource, RegistrySource, utils::path_utils::split_icon_location};
+
+const FILE_EXTS: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\FileExts";
+
+/// Resolves file-type icons the way Explorer does, from offline hives or Wine registry files.
+///
+/// The hives are consulted like the merged `HKEY_CLASSES_ROOT` view: per-user classes
+/// (`UsrClass.dat`, or `Software\Classes` in `NTUSER.DAT`) before machine classes
+/// (`SOFTWARE\Classes`).
+#[derive(Debug, Default)]
+pub struct FileAssociations {
+    software: Option<Box<dyn RegistrySource>>,
+    ntuser: Option<Box<dyn RegistrySource>>,
+    usrclass: Option<Box<dyn RegistrySource>>,
+}
+
+impl FileAssociations {
+    pub fn new() -> Self {
+        FileAssociations::default()
+    }
+
+    /// The `SOFTWARE` hive (`Windows\System32\config\SOFTWARE`).
+    pub fn with_software<R: RegistrySource + 'static>(mut self, hive: R) -> Self {
+        self.software = Some(Box::new(hive));
+        self
+    }
+
+    /// A user's `NTUSER.DAT`, for `UserChoice` and `Software\Classes`.
+    pub fn with_ntuser<R: RegistrySource + 'static>(mut self, hive: R) -> Self {
+        self.ntuser = Some(Box::new(hive));
+        self
+    }
+
+    /// A user's `AppData\Local\Microsoft\Windows\UsrClass.dat`.
+    pub fn with_usrclass<R: RegistrySource + 'static>(mut self, hive: R) -> Self {
+        self.usrclass = Some(Box::new(hive));
+        self
+    }
+
+    fn class_roots(&self) -> Vec<(&dyn RegistrySource, &'static str)> {
+        [
+            self.usrclass.as_deref().map(|h| (h, "")),
+            self.ntuser.as_deref().map(|h| (h, "Software\\Classes\\")),
+            self.software.as_deref().map(|h| (h, "Classes\\")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn class_value(&self, key_path: &str, name: &str) -> Option<String> {
+        self.class_roots().into_iter().find_map(|(hive, prefix)| {
+            hive.string_value(&format!("{prefix}{key_path}"), name)
+                .filter(|v| !v.is_empty())
+        })
+    }
+
+    fn class_value_names(&self, key_path: &str) -> Vec<String> {
+        self.class_roots()
+            .into_iter()
+            .filter_map(|(hive, prefix)| hive.values(&format!("{prefix}{key_path}")))
+            .flatten()
+            .map(|value| value.name)
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// ProgIDs registered for `extension`: the user's `UserChoice`, then the class set
+    /// as the extension's default value, then the `OpenWithProgids` lists of the user's
+    /// `FileExts` key and of the class roots.
+    pub fn prog_ids(&self, extension: &str) -> Vec<String> {
+        let extension = normalize_extension(extension);
+        let mut prog_ids = Vec::new();
+        let file_exts = format!("{FILE_EXTS}\\{extension}");
+
+        if let Some(ntuser) = &self.ntuser {
+            for choice in ["UserChoiceLatest", "UserChoice"] {
+                prog_ids.extend(ntuser.string_value(&format!("{file_exts}\\{choice}"), "ProgId"));
+            }
+        }
+
+        prog_ids.extend(self.class_value(&extension, ""));
+
+        if let Some(ntuser) = &self.ntuser
+            && let Some(values) = ntuser.values(&format!("{file_exts}\\OpenWithProgids"))
+        {
+            prog_ids.extend(values.into_iter().map(|value| value.name));
+        }
+        prog_ids.extend(self.class_value_names(&format!("{extension}\\OpenWithProgids")));
+
+        let mut seen = Vec::new();
+        prog_ids.retain(|id| {
+            let id = id.to_ascii_lowercase();
+            let is_new = !id.is_empty() && !seen.contains(&id);
+            seen.push(id);
+            is_new
+        });
+        prog_ids
+    }
+
+    /// The raw `DefaultIcon` value for `extension`, after following `CurVer`.
+    pub fn default_icon(&self, extension: &str) -> Option<String> {
+        let extension = normalize_extension(extension);
+
+        self.prog_ids(&extension)
+            .iter()
+            .find_map(|prog_id| {
+                let prog_id = self
+                    .class_value(&format!("{prog_id}\\CurVer"), "")
+                    .unwrap_or_else(|| prog_id.to_owned());
+                self.class_value(&format!("{prog_id}\\DefaultIcon"), "")
+            })
+            .or_else(|| self.class_value(&format!("{extension}\\DefaultIcon"), ""))
+    }
+
+    /// Resolves the icon for files with `extension` (with or without the leading dot).
+    ///
+    /// Types such as `.exe` and `.ico` use `%1`, meaning each file is its own icon;
+    /// those need [`FileAssociations::icon_source_for_file`].
+    pub fn icon_source(&self, extension: &str) -> Result<IconSource, Box<dyn Error>> {
+        self.resolve(extension, None)
+    }
+
+    /// Like [`FileAssociations::icon_source`], substituting `%1` with `file_path`.
+    pub fn icon_source_for_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<IconSource, Box<dyn Error>> {
+        let file_path = file_path.as_ref();
+        let extension = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("file has no extension: {file_path:?}"),
+                )
+            })?;
+        self.resolve(extension, Some(file_path))
+    }
+
+    fn resolve(
+        &self,
+        extension: &str,
+        file_path: Option<&Path>,
+    ) -> Result<IconSource, Box<dyn Error>> {
+        let default_icon = self.default_icon(extension).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("no icon registered for extension: {extension}"),
+            )
+        })?;
+
+        let (path, index) = split_icon_location(&default_icon);
+        if path.trim() == "%1" {
+            return file_path
+                .map(|file| IconSource::File(file.to_path_buf()))
+                .ok_or_else(|| {
+                    Box::new(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("files with extension {extension} are their own icon (%1)"),
+                    )) as Box<dyn Error>
+                });
+        }
+
+        Ok(IconSource::Resource(
+            DllIcon::new().with_icon_location(path, index),
+        ))
+    }
+}
+
+fn normalize_extension(extension: &str) -> String {
+    format!(".{}", extension.trim().trim_start_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{REG_SZ, RegistryValue};
+
+    /// Keys mapped to their values, with key paths compared ignoring case.
+    #[derive(Debug)]
+    struct Registry(Vec<(String, Vec<(&'static str, &'static str)>)>);
+
+    impl RegistrySource for Registry {
+        fn values(&self, key_path: &str) -> Option<Vec<RegistryValue>> {
+            let (_, values) = self
+                .0
+                .iter()
+                .find(|(path, _)| path.eq_ignore_ascii_case(key_path))?;
+            let values = values
+                .iter()
+                .map(|(name, data)| RegistryValue {
+                    name: (*name).to_owned(),
+                    value_type: REG_SZ,
+                    data: data.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+                })
+                .collect();
+            Some(values)
+        }
+    }
+
+    #[test]
+    fn orders_prog_ids_like_explorer() {
+        let file_exts = |key: &str| format!("{FILE_EXTS}\\.txt\\{key}");
+        let ntuser = Registry(vec![
+            (
+                file_exts("UserChoice"),
+                vec![("ProgId", "Applications\\editor.exe")],
+            ),
+            (file_exts("OpenWithProgids"), vec![("user.txt", "")]),
+        ]);
+        let software = Registry(vec![
+            ("Classes\\.txt".to_owned(), vec![("", "txtfile")]),
+            (
+                "Classes\\.txt\\OpenWithProgids".to_owned(),
+                vec![("machine.txt", ""), ("TXTFILE", "")],
+            ),
+        ]);
+        let associations = FileAssociations::new()
+            .with_ntuser(ntuser)
+            .with_software(software);
+
+        assert_eq!(
+            associations.prog_ids("TXT"),
+            [
+                "Applications\\editor.exe",
+                "txtfile",
+                "user.txt",
+                "machine.txt"
+            ]
+        );
+    }
+}
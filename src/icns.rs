@@ -0,0 +1,202 @@
+use std::error::Error;
+
+use image::RgbaImage;
+
+use crate::utils::{
+    binary_utils::invalid_data,
+    image_utils::{encode_png, image_for_size, square_image},
+};
+
+const ICNS_HEADER_SIZE: usize = 8;
+/// The most bytes one RLE control byte covers: literal runs and repeats.
+const MAX_LITERAL: usize = 128;
+const MAX_REPEAT: usize = 130;
+const MIN_REPEAT: usize = 3;
+
+/// PNG entries by OSType and pixel size. The `@2x` variants share pixel sizes with
+/// the plain ones of twice the point size.
+const PNG_ENTRIES: [(&[u8; 4], u32); 8] = [
+    (b"ic11", 32),
+    (b"ic12", 64),
+    (b"ic07", 128),
+    (b"ic13", 256),
+    (b"ic08", 256),
+    (b"ic14", 512),
+    (b"ic09", 512),
+    (b"ic10", 1024),
+];
+
+/// Legacy 24-bit RLE entries and their 8-bit masks, for older readers.
+const LEGACY_ENTRIES: [(&[u8; 4], &[u8; 4], u32); 2] =
+    [(b"is32", b"s8mk", 16), (b"il32", b"l8mk", 32)];
+
+/// Encodes an Apple icon file (`.icns`) from images of any sizes, such as the frames
+/// of an icon family. The 16 and 32 pixel legacy entries are always written; PNG
+/// entries are written up to the size of the largest image, so small icons are not
+/// blown up. Each entry is resampled from the closest larger image.
+pub fn encode_icns(images: &[RgbaImage]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut sources: Vec<RgbaImage> = images.iter().map(square_image).collect();
+    sources.sort_by_key(RgbaImage::width);
+    sources.dedup_by_key(|image| image.width());
+    let largest = sources
+        .last()
+        .map(RgbaImage::width)
+        .ok_or_else(|| invalid_data("no images to encode"))?;
+    let image_for = |size| image_for_size(&sources, size).unwrap_or_default();
+
+    let mut entries: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+    for (color_type, mask_type, size) in LEGACY_ENTRIES {
+        let image = image_for(size);
+        let mut color = Vec::new();
+        for channel in 0..3 {
+            let values: Vec<u8> = image.pixels().map(|pixel| pixel.0[channel]).collect();
+            pack_bits(&values, &mut color);
+        }
+        entries.push((color_type, color));
+        entries.push((mask_type, image.pixels().map(|pixel| pixel.0[3]).collect()));
+    }
+    for (png_type, size) in PNG_ENTRIES {
+        if size <= largest {
+            entries.push((png_type, encode_png(&image_for(size))?));
+        }
+    }
+
+    let total: usize = ICNS_HEADER_SIZE
+        + entries
+            .iter()
+            .map(|(_, data)| ICNS_HEADER_SIZE + data.len())
+            .sum::<usize>();
+    let mut data = Vec::with_capacity(total);
+    data.extend_from_slice(b"icns");
+    data.extend_from_slice(&u32::try_from(total)?.to_be_bytes());
+    for (entry_type, entry) in entries {
+        data.extend_from_slice(entry_type);
+        data.extend_from_slice(&u32::try_from(ICNS_HEADER_SIZE + entry.len())?.to_be_bytes());
+        data.extend_from_slice(&entry);
+    }
+    Ok(data)
+}
+
+/// The RLE of legacy color entries, applied to one channel at a time: a control byte
+/// below 0x80 is followed by that many plus one literal bytes, and one from 0x80 is
+/// followed by a byte repeated that many minus 0x7D times.
+fn pack_bits(values: &[u8], output: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < values.len() {
+        let run = values[i..]
+            .iter()
+            .take(MAX_REPEAT)
+            .take_while(|&&value| value == values[i])
+            .count();
+        if run >= MIN_REPEAT {
+            output.push((0x80 + run - MIN_REPEAT) as u8);
+            output.push(values[i]);
+            i += run;
+            continue;
+        }
+
+        // Literals run up to the next repeat worth encoding
+        let start = i;
+        while i < values.len() && i - start < MAX_LITERAL {
+            if i + 2 < values.len() && values[i] == values[i + 1] && values[i] == values[i + 2] {
+                break;
+            }
+            i += 1;
+        }
+        output.push((i - start - 1) as u8);
+        output.extend_from_slice(&values[start..i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpack_bits(data: &[u8]) -> Vec<u8> {
+        let mut values = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let control = usize::from(data[i]);
+            if control < 0x80 {
+                values.extend_from_slice(&data[i + 1..i + 2 + control]);
+                i += control + 2;
+            } else {
+                values.extend(std::iter::repeat_n(data[i + 1], control - 0x7D));
+                i += 2;
+            }
+        }
+        values
+    }
+
+    fn pack(values: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        pack_bits(values, &mut output);
+        output
+    }
+
+    /// The entries of an icon file, by OSType.
+    fn entries(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut entries = Vec::new();
+        let mut offset = ICNS_HEADER_SIZE;
+        while offset < data.len() {
+            let size = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            let end = offset + size as usize;
+            entries.push((&data[offset..offset + 4], &data[offset + 8..end]));
+            offset = end;
+        }
+        entries
+    }
+
+    #[test]
+    fn packs_runs_and_literals() {
+        assert_eq!(pack(&[1, 2, 3]), [2, 1, 2, 3]);
+        assert_eq!(pack(&[7, 7, 7]), [0x80, 7]);
+        assert_eq!(pack(&[1, 7, 7, 7, 7, 2]), [0, 1, 0x81, 7, 0, 2]);
+        // Pairs are cheaper as literals
+        assert_eq!(pack(&[1, 1, 2, 2]), [3, 1, 1, 2, 2]);
+        assert_eq!(pack(&[9; MAX_REPEAT]), [0xFF, 9]);
+        assert_eq!(pack(&[9; MAX_REPEAT + 1]), [0xFF, 9, 0, 9]);
+        assert!(pack(&[]).is_empty());
+
+        let literals: Vec<u8> = (0..=255).collect();
+        let packed = pack(&literals);
+        assert_eq!((packed[0], packed[MAX_LITERAL + 1]), (0x7F, 0x7F));
+        assert_eq!(packed.len(), literals.len() + 2);
+    }
+
+    #[test]
+    fn round_trips_through_unpacking() {
+        let mut values: Vec<u8> = (0..1000u32).map(|i| (i * i / 7 % 5) as u8).collect();
+        values.extend([3; 300]);
+        values.extend([1, 2, 2, 3, 3, 3]);
+        assert_eq!(unpack_bits(&pack(&values)), values);
+    }
+
+    #[test]
+    fn writes_legacy_and_png_entries() {
+        let image = RgbaImage::from_pixel(64, 64, image::Rgba([10, 20, 30, 128]));
+        let data = encode_icns(&[image]).unwrap();
+        assert_eq!(&data[..4], b"icns");
+        assert_eq!(
+            u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+            data.len()
+        );
+
+        let entries = entries(&data);
+        let types: Vec<&[u8]> = entries.iter().map(|(entry_type, _)| *entry_type).collect();
+        assert_eq!(
+            types,
+            [&b"is32"[..], b"s8mk", b"il32", b"l8mk", b"ic11", b"ic12"]
+        );
+
+        let color = unpack_bits(entries[0].1);
+        assert_eq!(color.len(), 16 * 16 * 3);
+        assert_eq!(&color[..256], [10; 256]);
+        assert_eq!(&color[512..], [30; 256]);
+        assert_eq!(entries[1].1, [128; 16 * 16]);
+        assert_eq!(entries[3].1.len(), 32 * 32);
+        assert!(entries[4].1.starts_with(b"\x89PNG"));
+
+        assert!(encode_icns(&[]).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    sync::OnceLock,
+};
+
+use crate::{PeFile, ResourceId, WindowsRoot, utils::binary_utils::invalid_data};
+
+const BUILTIN_CATALOG: &str = include_str!("data/icon_catalog.tsv");
+
+/// One row of the catalog: where an icon lives from a given Windows build on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub module: String,
+    pub since_build: u32,
+    /// A negative resource ID, or a zero-based index.
+    pub location: i32,
+    pub tags: Vec<String>,
+}
+
+impl CatalogEntry {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// A catalog entry that no longer matches the module it points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleEntry {
+    pub entry: CatalogEntry,
+    pub reason: String,
+}
+
+/// Named system icons and their locations per module and Windows build, so callers
+/// can ask for `"recycle-bin-full"` instead of an index that shifts between releases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconCatalog {
+    version: u32,
+    entries: Vec<CatalogEntry>,
+}
+
+impl IconCatalog {
+    /// The catalog shipped with the crate.
+    pub fn builtin() -> &'static IconCatalog {
+        static CATALOG: OnceLock<IconCatalog> = OnceLock::new();
+        CATALOG.get_or_init(|| {
+            IconCatalog::parse(BUILTIN_CATALOG).expect("the built-in icon catalog is valid")
+        })
+    }
+
+    /// Parses a catalog in the built-in format: a `version` line, then tab-separated
+    /// rows of name, module, first build, icon location and comma-separated tags.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut version = None;
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let invalid = || invalid_data(format!("invalid catalog line {}", number + 1));
+
+            match fields.as_slice() {
+                ["version", value] => version = Some(value.parse().map_err(|_| invalid())?),
+                [name, module, since_build, location, rest @ ..] if rest.len() <= 1 => entries
+                    .push(CatalogEntry {
+                        name: name.to_ascii_lowercase(),
+                        module: (*module).to_owned(),
+                        since_build: since_build.parse().map_err(|_| invalid())?,
+                        location: location.parse().map_err(|_| invalid())?,
+                        tags: rest
+                            .first()
+                            .map(|tags| {
+                                tags.split(',')
+                                    .map(str::trim)
+                                    .filter(|tag| !tag.is_empty())
+                                    .map(str::to_owned)
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    }),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(IconCatalog {
+            version: version.ok_or_else(|| invalid_data("icon catalog has no version line"))?,
+            entries,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    /// Every icon name, sorted, without duplicates.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.entries.iter().map(|e| e.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The entry for `name` on the given build: the row with the latest first build
+    /// not after it.
+    pub fn lookup(&self, name: &str, build: u32) -> Option<&CatalogEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.name.eq_ignore_ascii_case(name) && entry.since_build <= build)
+            .max_by_key(|entry| entry.since_build)
+    }
+
+    /// Names whose name or one of whose tags contains `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Vec<&str> {
+        let query = query.trim().to_ascii_lowercase();
+        let mut names: Vec<&str> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.name.contains(&query)
+                    || entry
+                        .tags
+                        .iter()
+                        .any(|tag| tag.to_ascii_lowercase().contains(&query))
+            })
+            .map(|entry| entry.name.as_str())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Names carrying exactly `tag`.
+    pub fn with_tag(&self, tag: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.has_tag(tag))
+            .map(|entry| entry.name.as_str())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Checks every name against the modules of an offline installation, reporting
+    /// entries whose module or icon group is missing there. An unknown build checks
+    /// the newest rows.
+    pub fn find_stale(&self, root: &WindowsRoot) -> Vec<StaleEntry> {
+        let build = root.build().unwrap_or(u32::MAX);
+        self.names()
+            .into_iter()
+            .filter_map(|name| self.lookup(name, build))
+            .filter_map(|entry| {
+                let reason = match root.find_module(&entry.module) {
+                    None => format!("{} not found", entry.module),
+                    Some(path) => match PeFile::open_for_icons(path) {
+                        Err(e) => format!("{} cannot be read: {e}", entry.module),
+                        Ok((pe, _)) => check_location(&pe, entry)?,
+                    },
+                };
+                Some(StaleEntry {
+                    entry: entry.clone(),
+                    reason,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns why `entry` does not match `pe`, or `None` when its icon group is there.
+pub(crate) fn check_location(pe: &PeFile, entry: &CatalogEntry) -> Option<String> {
+    let groups = pe.icon_groups();
+    if entry.location < 0 {
+        let Ok(id) = u16::try_from(entry.location.unsigned_abs()) else {
+            return Some(format!("{} is not a valid resource ID", entry.location));
+        };
+        let id = ResourceId::Id(id);
+        (!groups.iter().any(|group| group.matches(&id)))
+            .then(|| format!("{} has no icon group {id}", entry.module))
+    } else {
+        (entry.location as usize >= groups.len()).then(|| {
+            format!(
+                "{} has {} icon groups, index {} is out of range",
+                entry.module,
+                groups.len(),
+                entry.location
+            )
+        })
+    }
+}
+
+pub(crate) fn unknown_name(name: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        ErrorKind::NotFound,
+        format!("no icon named '{name}' in the icon catalog"),
+    ))
+}
+
+pub(crate) fn stale(entry: &CatalogEntry, reason: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        ErrorKind::NotFound,
+        format!(
+            "icon catalog entry '{}' (version {}) is stale: {reason}",
+            entry.name,
+            IconCatalog::builtin().version()
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StockIcon;
+
+    #[test]
+    fn looks_names_up_per_build() {
+        let catalog = IconCatalog::builtin();
+        let location = |name, build| {
+            catalog
+                .lookup(name, build)
+                .map(|entry| (entry.module.as_str(), entry.since_build, entry.location))
+        };
+        assert_eq!(location("Folder", 2600), Some(("shell32.dll", 0, 3)));
+        assert_eq!(location("folder", 7601), Some(("imageres.dll", 6000, -3)));
+        // Later builds fall back to the newest row at or below them
+        assert_eq!(location("folder", 26100), Some(("imageres.dll", 6000, -3)));
+        assert_eq!(
+            location("quick-access", 22631),
+            Some(("shell32.dll", 10240, -51380))
+        );
+        assert_eq!(location("quick-access", 7601), None);
+        assert_eq!(location("missing", u32::MAX), None);
+    }
+
+    #[test]
+    fn resolves_stock_icons_through_the_catalog() {
+        assert_eq!(StockIcon::Folder.location(2600), Some(("shell32.dll", 3)));
+        assert_eq!(
+            StockIcon::RecyclerFull.location(22631),
+            Some(("imageres.dll", -54))
+        );
+        assert_eq!(StockIcon::Shield.location(2600), None);
+        assert_eq!(StockIcon::MediaBluRay.location(u32::MAX), None);
+    }
+
+    #[test]
+    fn searches_names_and_tags() {
+        let catalog = IconCatalog::parse(
+            "version\t2\nfolder\tshell32.dll\t0\t3\tdirectory,Shell\nprinter\tshell32.dll\t0\t16\tdevice\n",
+        )
+        .unwrap();
+        assert_eq!(catalog.version(), 2);
+        assert_eq!(catalog.search("SHELL"), ["folder"]);
+        assert_eq!(catalog.search("print"), ["printer"]);
+        assert_eq!(catalog.with_tag("device"), ["printer"]);
+        assert_eq!(catalog.names(), ["folder", "printer"]);
+    }
+
+    #[test]
+    fn rejects_malformed_catalogs() {
+        assert!(IconCatalog::parse("folder\tshell32.dll\t0\t3\n").is_err());
+        assert!(IconCatalog::parse("version\t1\nfolder\tshell32.dll\tvista\t3\n").is_err());
+        assert!(IconCatalog::parse("version\t1\nfolder\tshell32.dll\n").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{error::Error, fs, io::Cursor, path::Path};
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::utils::{
+    binary_utils::{invalid_data, read_u8, read_u16, read_u32, truncated},
+    image_utils::encode_png,
+};
+
+pub(crate) const ICONDIR_SIZE: usize = 6;
+pub(crate) const ICONDIRENTRY_SIZE: usize = 16;
+const GRPICONDIRENTRY_SIZE: usize = 14;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const BITMAPINFOHEADER_SIZE: usize = 40;
+
+/// One image of an icon: a PNG stream or a DIB with its AND mask, as stored in
+/// `RT_ICON` resources and `.ico` files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconFrame {
+    pub width: u32,
+    pub height: u32,
+    pub color_count: u8,
+    pub planes: u16,
+    pub bit_count: u16,
+    pub data: Vec<u8>,
+}
+
+impl IconFrame {
+    pub fn is_png(&self) -> bool {
+        self.data.starts_with(PNG_SIGNATURE)
+    }
+
+    /// Encodes an image as a frame the way resource compilers store them: a PNG
+    /// stream from 256 pixels up, and a 32-bit DIB with its AND mask below that.
+    pub fn from_image(image: &RgbaImage) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(invalid_data("icon images cannot be empty"));
+        }
+        let data = if width >= 256 || height >= 256 {
+            encode_png(image)?
+        } else {
+            encode_dib(image)
+        };
+        Ok(IconFrame {
+            width,
+            height,
+            color_count: 0,
+            planes: 1,
+            bit_count: 32,
+            data,
+        })
+    }
+
+    pub fn to_image(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        IconGroup {
+            frames: vec![self.clone()],
+        }
+        .decode_ico()
+    }
+}
+
+/// An icon family: the frames of one `RT_GROUP_ICON` resource or one `.ico` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IconGroup {
+    pub frames: Vec<IconFrame>,
+}
+
+impl IconGroup {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_ico_bytes(&fs::read(path.as_ref())?)
+    }
+
+    /// Builds a family with one frame per image, in order.
+    pub fn from_images(images: &[RgbaImage]) -> Result<Self, Box<dyn Error>> {
+        let frames = images
+            .iter()
+            .map(IconFrame::from_image)
+            .collect::<Result<Vec<_>, _>>()?;
+        if frames.is_empty() {
+            return Err(invalid_data("icon group has no frames"));
+        }
+        Ok(IconGroup { frames })
+    }
+
+    /// Parses an `.ico` file.
+    pub fn from_ico_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let count = parse_dir_header(data, 1)?;
+        let frames = (0..count)
+            .map(|i| {
+                let entry = ICONDIR_SIZE + i * ICONDIRENTRY_SIZE;
+                let size = read_u32(data, entry + 8).ok_or_else(|| truncated("icon directory"))?;
+                let offset =
+                    read_u32(data, entry + 12).ok_or_else(|| truncated("icon directory"))?;
+                let image = data
+                    .get(offset as usize..offset as usize + size as usize)
+                    .ok_or_else(|| truncated("icon image"))?;
+                read_frame(data, entry, image.to_vec())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(IconGroup { frames })
+    }
+
+    /// Parses a `GRPICONDIR` from an `RT_GROUP_ICON` resource, looking each frame up
+    /// by its `RT_ICON` ID.
+    pub fn from_group_bytes<F>(data: &[u8], mut load_icon: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: FnMut(u16) -> Option<Vec<u8>>,
+    {
+        let count = parse_dir_header(data, 1)?;
+        let frames = (0..count)
+            .filter_map(|i| {
+                let entry = ICONDIR_SIZE + i * GRPICONDIRENTRY_SIZE;
+                let id = read_u16(data, entry + 12)?;
+                // Missing frames are skipped, as LoadImage does
+                Some(read_frame(data, entry, load_icon(id)?))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if frames.is_empty() {
+            return Err(invalid_data("icon group has no frames"));
+        }
+        Ok(IconGroup { frames })
+    }
+
+    pub fn to_ico_bytes(&self) -> Vec<u8> {
+        let count = self.frames.len();
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0, 0, 1, 0]);
+        data.extend_from_slice(&(count as u16).to_le_bytes());
+
+        let mut offset = ICONDIR_SIZE + count * ICONDIRENTRY_SIZE;
+        for frame in &self.frames {
+            data.extend_from_slice(&dir_entry_prefix(frame));
+            data.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += frame.data.len();
+        }
+        for frame in &self.frames {
+            data.extend_from_slice(&frame.data);
+        }
+        data
+    }
+
+    /// Encodes the `GRPICONDIR` of an `RT_GROUP_ICON` resource, whose frames are the
+    /// `RT_ICON` resources `ids`, in frame order.
+    pub(crate) fn to_group_bytes(&self, ids: &[u16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0, 0, 1, 0]);
+        data.extend_from_slice(&(self.frames.len() as u16).to_le_bytes());
+        for (frame, id) in self.frames.iter().zip(ids) {
+            data.extend_from_slice(&dir_entry_prefix(frame));
+            data.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
+    }
+
+    /// The largest frame, preferring more colors between frames of the same size.
+    pub fn largest_frame(&self) -> Option<&IconFrame> {
+        self.frames
+            .iter()
+            .max_by_key(|f| (u64::from(f.width) * u64::from(f.height), f.bit_count))
+    }
+
+    /// The smallest frame at least `size` pixels wide, or the largest one if none is.
+    pub fn frame_for_size(&self, size: u32) -> Option<&IconFrame> {
+        self.frames
+            .iter()
+            .filter(|f| f.width >= size)
+            .min_by_key(|f| (f.width, u16::MAX - f.bit_count))
+            .or_else(|| self.largest_frame())
+    }
+
+    pub fn to_image(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        self.largest_frame()
+            .ok_or_else(|| invalid_data("icon group has no frames"))?
+            .to_image()
+    }
+
+    pub fn to_image_with_size(&self, size: u32) -> Result<RgbaImage, Box<dyn Error>> {
+        self.frame_for_size(size)
+            .ok_or_else(|| invalid_data("icon group has no frames"))?
+            .to_image()
+    }
+
+    /// One decoded image per frame size, largest first. Of frames the same size, the
+    /// one with the most colors is used.
+    pub fn to_images(&self) -> Result<Vec<RgbaImage>, Box<dyn Error>> {
+        let mut frames: Vec<&IconFrame> = self.frames.iter().collect();
+        frames.sort_by_key(|f| {
+            let area = u64::from(f.width) * u64::from(f.height);
+            std::cmp::Reverse((area, f.width, f.bit_count))
+        });
+        frames.dedup_by_key(|f| (f.width, f.height));
+        frames.into_iter().map(IconFrame::to_image).collect()
+    }
+
+    /// Encodes the family as an Apple `.icns` file; see [`crate::encode_icns`].
+    pub fn to_icns_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        crate::encode_icns(&self.to_images()?)
+    }
+
+    fn decode_ico(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let image = image::load(Cursor::new(self.to_ico_bytes()), ImageFormat::Ico)?;
+        Ok(image.to_rgba8())
+    }
+}
+
+pub(crate) fn parse_dir_header(data: &[u8], expected_type: u16) -> Result<usize, Box<dyn Error>> {
+    let reserved = read_u16(data, 0).ok_or_else(|| truncated("icon directory"))?;
+    let kind = read_u16(data, 2).ok_or_else(|| truncated("icon directory"))?;
+    if reserved != 0 || kind != expected_type {
+        return Err(invalid_data("not an icon directory"));
+    }
+    Ok(usize::from(
+        read_u16(data, 4).ok_or_else(|| truncated("icon directory"))?,
+    ))
+}
+
+fn read_frame(dir: &[u8], entry: usize, data: Vec<u8>) -> Result<IconFrame, Box<dyn Error>> {
+    let field = |offset| read_u8(dir, entry + offset).ok_or_else(|| truncated("icon directory"));
+    let dimension = |value: u8| if value == 0 { 256 } else { u32::from(value) };
+
+    let mut frame = IconFrame {
+        width: dimension(field(0)?),
+        height: dimension(field(1)?),
+        color_count: field(2)?,
+        planes: read_u16(dir, entry + 4).ok_or_else(|| truncated("icon directory"))?,
+        bit_count: read_u16(dir, entry + 6).ok_or_else(|| truncated("icon directory"))?,
+        data,
+    };
+
+    // PNG frames larger than 256 pixels can only state their real size in the stream
+    if frame.is_png()
+        && let (Some(width), Some(height)) =
+            (read_u32_be(&frame.data, 16), read_u32_be(&frame.data, 20))
+    {
+        frame.width = width;
+        frame.height = height;
+    }
+    Ok(frame)
+}
+
+/// A bottom-up 32-bit `BITMAPINFOHEADER` DIB, with the height doubled for the AND
+/// mask that follows the colors. The mask hides fully transparent pixels.
+fn encode_dib(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let mask_stride = width.div_ceil(32) as usize * 4;
+    let image_size = (width * height * 4) as usize + mask_stride * height as usize;
+
+    let mut data = Vec::with_capacity(BITMAPINFOHEADER_SIZE + image_size);
+    data.extend_from_slice(&(BITMAPINFOHEADER_SIZE as u32).to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    data.extend_from_slice(&(height as i32 * 2).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&(image_size as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 16]);
+
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            data.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+    for y in (0..height).rev() {
+        let mut row = vec![0u8; mask_stride];
+        for x in 0..width {
+            if image.get_pixel(x, y).0[3] == 0 {
+                row[x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        data.extend_from_slice(&row);
+    }
+    data
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn dir_entry_prefix(frame: &IconFrame) -> [u8; 8] {
+    let dimension = |value: u32| if value >= 256 { 0 } else { value as u8 };
+    let planes = frame.planes.to_le_bytes();
+    let bit_count = frame.bit_count.to_le_bytes();
+    [
+        dimension(frame.width),
+        dimension(frame.height),
+        frame.color_count,
+        0,
+        planes[0],
+        planes[1],
+        bit_count[0],
+        bit_count[1],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32, bit_count: u16) -> IconFrame {
+        IconFrame {
+            width,
+            height,
+            color_count: 0,
+            planes: 1,
+            bit_count,
+            data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn compares_frame_areas_without_overflowing() {
+        let group = IconGroup {
+            frames: vec![
+                frame(256, 256, 32),
+                frame(70000, 70000, 8),
+                frame(16, 16, 32),
+            ],
+        };
+        assert_eq!(group.largest_frame(), Some(&group.frames[1]));
+        assert_eq!(group.frame_for_size(20), Some(&group.frames[0]));
+    }
+
+    #[test]
+    fn round_trips_through_ico_files() {
+        let images = [
+            RgbaImage::from_pixel(16, 16, image::Rgba([255, 0, 0, 255])),
+            RgbaImage::from_pixel(256, 256, image::Rgba([0, 0, 255, 128])),
+        ];
+        let group = IconGroup::from_images(&images).unwrap();
+        assert!(!group.frames[0].is_png());
+        assert!(group.frames[1].is_png());
+
+        let read = IconGroup::from_ico_bytes(&group.to_ico_bytes()).unwrap();
+        assert_eq!(read, group);
+        let decoded = read.to_images().unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0], images[1]);
+        assert_eq!(decoded[1], images[0]);
+    }
+
+    #[test]
+    fn skips_missing_group_frames() {
+        let images = [
+            RgbaImage::new(16, 16),
+            RgbaImage::from_pixel(32, 32, image::Rgba([1, 2, 3, 255])),
+        ];
+        let group = IconGroup::from_images(&images).unwrap();
+        let data = group.to_group_bytes(&[1, 2]);
+
+        let read = IconGroup::from_group_bytes(&data, |id| {
+            (id == 2).then(|| group.frames[1].data.clone())
+        })
+        .unwrap();
+        assert_eq!(read.frames, [group.frames[1].clone()]);
+        assert!(IconGroup::from_group_bytes(&data, |_| None).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_ico_files() {
+        let data = IconGroup::from_images(&[RgbaImage::new(16, 16)])
+            .unwrap()
+            .to_ico_bytes();
+        assert!(IconGroup::from_ico_bytes(&data[..data.len() - 1]).is_err());
+        assert!(IconGroup::from_ico_bytes(&data[..4]).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{cmp::Ordering, error::Error, fs, path::Path};
+
+use crate::{
+    IconGroup, LANG_NEUTRAL, ResourceId, ResourceTable,
+    pe_writer::{build_rsrc, compare_ids, pe_checksum},
+    utils::binary_utils::invalid_data,
+};
+
+const PE_OFFSET: usize = 0x80;
+const OPTIONAL_HEADER_SIZE: usize = 224;
+const FILE_ALIGNMENT: u32 = 0x200;
+const SECTION_ALIGNMENT: u32 = 0x1000;
+const IMAGE_BASE: u32 = 0x1000_0000;
+const IMAGE_FILE_MACHINE_I386: u16 = 0x14C;
+/// `IMAGE_FILE_EXECUTABLE_IMAGE | IMAGE_FILE_32BIT_MACHINE | IMAGE_FILE_DLL`.
+const FILE_CHARACTERISTICS: u16 = 0x2102;
+/// `DYNAMIC_BASE | NX_COMPAT | NO_SEH`: there is no code, so nothing to relocate.
+const DLL_CHARACTERISTICS: u16 = 0x0540;
+const IMAGE_SUBSYSTEM_WINDOWS_GUI: u16 = 2;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+/// `IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ`.
+const RSRC_CHARACTERISTICS: u32 = 0x4000_0040;
+
+/// A resource-only DLL of icon families, like the icon libraries `shell32.dll` and
+/// `imageres.dll` are. Families are given in the order `ExtractIcon` indexes address
+/// them: named ones first, sorted case-insensitively, then IDs in ascending order.
+/// Each family's frames are numbered on from the previous family's.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconLibrary {
+    icons: Vec<(ResourceId, IconGroup)>,
+    language: u16,
+}
+
+impl Default for IconLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IconLibrary {
+    pub fn new() -> Self {
+        IconLibrary {
+            icons: Vec::new(),
+            language: LANG_NEUTRAL,
+        }
+    }
+
+    /// Adds a family with a numeric resource ID, addressed by `-id` in icon locations.
+    pub fn with_icon(mut self, id: u16, group: IconGroup) -> Self {
+        self.icons.push((ResourceId::Id(id), group));
+        self
+    }
+
+    /// Adds a family with a resource name, stored in upper case.
+    pub fn with_named_icon(mut self, name: &str, group: IconGroup) -> Self {
+        self.icons
+            .push((ResourceId::Name(name.to_uppercase()), group));
+        self
+    }
+
+    /// The language of every resource; language-neutral by default.
+    pub fn with_language(mut self, language: u16) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// The family names, in order: the Nth one is icon index N.
+    pub fn names(&self) -> Vec<ResourceId> {
+        self.icons.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// The resources of the library. Fails when the families are not in the order
+    /// icon indexes would address them, or a name or ID is repeated.
+    pub fn to_resource_table(&self) -> Result<ResourceTable, Box<dyn Error>> {
+        if self.icons.is_empty() {
+            return Err(invalid_data("icon library has no icons"));
+        }
+        for pair in self.icons.windows(2) {
+            let (previous, next) = (&pair[0].0, &pair[1].0);
+            match compare_ids(previous, next) {
+                Ordering::Less => {}
+                Ordering::Equal => {
+                    return Err(invalid_data(format!("icon {next} is added twice")));
+                }
+                Ordering::Greater => {
+                    return Err(invalid_data(format!(
+                        "icon {next} must come before {previous}: names come first, sorted, \
+                         then IDs in ascending order"
+                    )));
+                }
+            }
+        }
+
+        let mut table = ResourceTable::new();
+        for (name, group) in &self.icons {
+            table.add_icon_group(name.clone(), self.language, group)?;
+        }
+        Ok(table)
+    }
+
+    /// Builds a 32-bit PE DLL with no code and no entry point, only a `.rsrc`
+    /// section. It loads as a data file on any architecture.
+    pub fn to_dll_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let table = self.to_resource_table()?;
+        let rsrc = build_rsrc(&table.entries, SECTION_ALIGNMENT);
+        let raw_size = align(rsrc.len() as u32, FILE_ALIGNMENT);
+        let headers_size = align(
+            (PE_OFFSET + 4 + 20 + OPTIONAL_HEADER_SIZE + 40) as u32,
+            FILE_ALIGNMENT,
+        );
+        let image_size = SECTION_ALIGNMENT + align(rsrc.len() as u32, SECTION_ALIGNMENT);
+
+        let mut data = vec![0u8; PE_OFFSET];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3C..0x40].copy_from_slice(&(PE_OFFSET as u32).to_le_bytes());
+        data.extend_from_slice(b"PE\0\0");
+
+        // COFF header
+        push_u16(&mut data, IMAGE_FILE_MACHINE_I386);
+        push_u16(&mut data, 1);
+        data.extend_from_slice(&[0; 12]);
+        push_u16(&mut data, OPTIONAL_HEADER_SIZE as u16);
+        push_u16(&mut data, FILE_CHARACTERISTICS);
+
+        // Optional header
+        let optional = data.len();
+        push_u16(&mut data, 0x10B);
+        data.extend_from_slice(&[14, 0]);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, raw_size);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, SECTION_ALIGNMENT);
+        push_u32(&mut data, SECTION_ALIGNMENT);
+        push_u32(&mut data, IMAGE_BASE);
+        push_u32(&mut data, SECTION_ALIGNMENT);
+        push_u32(&mut data, FILE_ALIGNMENT);
+        for version in [6, 0, 0, 0, 6, 0] {
+            push_u16(&mut data, version);
+        }
+        push_u32(&mut data, 0);
+        push_u32(&mut data, image_size);
+        push_u32(&mut data, headers_size);
+        push_u32(&mut data, 0);
+        push_u16(&mut data, IMAGE_SUBSYSTEM_WINDOWS_GUI);
+        push_u16(&mut data, DLL_CHARACTERISTICS);
+        for size in [0x10_0000, 0x1000, 0x10_0000, 0x1000, 0, 16] {
+            push_u32(&mut data, size);
+        }
+        for directory in 0..16 {
+            let (rva, size) = if directory == IMAGE_DIRECTORY_ENTRY_RESOURCE {
+                (SECTION_ALIGNMENT, rsrc.len() as u32)
+            } else {
+                (0, 0)
+            };
+            push_u32(&mut data, rva);
+            push_u32(&mut data, size);
+        }
+
+        // Section table
+        data.extend_from_slice(b".rsrc\0\0\0");
+        push_u32(&mut data, rsrc.len() as u32);
+        push_u32(&mut data, SECTION_ALIGNMENT);
+        push_u32(&mut data, raw_size);
+        push_u32(&mut data, headers_size);
+        data.extend_from_slice(&[0; 12]);
+        push_u32(&mut data, RSRC_CHARACTERISTICS);
+
+        data.resize(headers_size as usize, 0);
+        data.extend_from_slice(&rsrc);
+        data.resize((headers_size + raw_size) as usize, 0);
+
+        let checksum = pe_checksum(&data, optional + 64);
+        data[optional + 64..optional + 68].copy_from_slice(&checksum.to_le_bytes());
+        Ok(data)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path.as_ref(), self.to_dll_bytes()?)?;
+        Ok(())
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::{PeFile, RT_GROUP_ICON};
+
+    fn group(size: u32, shade: u8) -> IconGroup {
+        IconGroup::from_images(&[RgbaImage::from_pixel(
+            size,
+            size,
+            Rgba([shade, shade, shade, 255]),
+        )])
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_pe_files() {
+        let library = IconLibrary::new()
+            .with_named_icon("app", group(32, 1))
+            .with_named_icon("Zip", group(16, 2))
+            .with_icon(1, group(48, 3))
+            .with_icon(7, group(24, 4))
+            .with_language(0x0409);
+        let data = library.to_dll_bytes().unwrap();
+        let pe = PeFile::from_bytes(data.clone()).unwrap();
+
+        assert_eq!(
+            pe.icon_groups(),
+            [
+                ResourceId::Name("APP".to_owned()),
+                ResourceId::Name("ZIP".to_owned()),
+                ResourceId::Id(1),
+                ResourceId::Id(7),
+            ]
+        );
+        assert_eq!(pe.icon_groups(), library.names());
+        for (index, (size, shade)) in [(32, 1), (16, 2), (48, 3), (24, 4)].into_iter().enumerate() {
+            assert_eq!(pe.icon_group_at(index as i32).unwrap(), group(size, shade));
+        }
+        assert_eq!(pe.icon_group_at(-7).unwrap(), group(24, 4));
+        assert!(
+            pe.resources()
+                .iter()
+                .filter(|r| r.type_id == ResourceId::Id(RT_GROUP_ICON))
+                .all(|r| r.language == 0x0409)
+        );
+
+        let checksum = PE_OFFSET + 4 + 20 + 64;
+        let stored = u32::from_le_bytes(data[checksum..checksum + 4].try_into().unwrap());
+        assert_eq!(stored, pe_checksum(&data, checksum));
+    }
+
+    #[test]
+    fn rejects_families_out_of_index_order() {
+        let library = |ids: &[ResourceId]| {
+            ids.iter()
+                .fold(IconLibrary::new(), |library, id| match id {
+                    ResourceId::Id(id) => library.with_icon(*id, group(16, 0)),
+                    ResourceId::Name(name) => library.with_named_icon(name, group(16, 0)),
+                })
+                .to_resource_table()
+        };
+        let name = |name: &str| ResourceId::Name(name.to_owned());
+
+        assert!(library(&[name("a"), name("B"), ResourceId::Id(2), ResourceId::Id(10)]).is_ok());
+        assert!(library(&[ResourceId::Id(2), ResourceId::Id(1)]).is_err());
+        assert!(library(&[ResourceId::Id(1), name("a")]).is_err());
+        assert!(library(&[name("b"), name("A")]).is_err());
+        assert!(library(&[ResourceId::Id(3), ResourceId::Id(3)]).is_err());
+        assert!(library(&[name("icon"), name("ICON")]).is_err());
+        assert!(library(&[]).is_err());
+    }
+}
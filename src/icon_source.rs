@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use crate::DllIcon;
+
+/// Where an icon comes from once a shortcut, shell file or registry entry is resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IconSource {
+    /// An icon group inside a module or `.ico` file.
+    Resource(DllIcon),
+    /// A file whose own icon is used, e.g. the target of a shortcut.
+    File(PathBuf),
+    /// A packaged application, e.g. `Microsoft.WindowsTerminal_8wekyb3d8bbwe!App`.
+    AppUserModelId(String),
+    /// An icon that only exists online, like an `http` `IconFile`. It cannot be
+    /// extracted offline and is reported as is.
+    Remote(String),
+}
@@ -0,0 +1,312 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+use crate::{
+    IconGroup,
+    utils::{
+        binary_utils::invalid_data,
+        image_utils::{image_for_size, square_image},
+    },
+};
+
+/// The sizes icon themes conventionally provide under `hicolor`.
+pub const HICOLOR_SIZES: [u32; 10] = [16, 22, 24, 32, 48, 64, 96, 128, 256, 512];
+/// Characters that make an `Exec` argument need quoting.
+const EXEC_RESERVED: &str = "\"'\\><~|&;$*?#()`";
+
+/// An application icon laid out as a freedesktop icon theme:
+/// `hicolor/<size>x<size>/apps/<name>.png` for each size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconThemeExport {
+    name: String,
+    images: Vec<RgbaImage>,
+    sizes: Vec<u32>,
+}
+
+impl IconThemeExport {
+    /// An export of the icon `name`, the name `.desktop` files refer to it by, at the
+    /// [`HICOLOR_SIZES`].
+    pub fn new(name: &str) -> Self {
+        IconThemeExport {
+            name: name.to_owned(),
+            images: Vec::new(),
+            sizes: HICOLOR_SIZES.to_vec(),
+        }
+    }
+
+    /// Adds a source image, such as the one `get_icon_by_path` returns.
+    pub fn with_image(mut self, image: RgbaImage) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    /// Adds every frame size of an icon family as a source image.
+    pub fn with_icon_group(mut self, group: &IconGroup) -> Result<Self, Box<dyn Error>> {
+        self.images.extend(group.to_images()?);
+        Ok(self)
+    }
+
+    pub fn with_sizes(mut self, sizes: &[u32]) -> Self {
+        self.sizes = sizes.to_vec();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The image written for each size, smallest first. A source image of exactly
+    /// that size is used as is; other sizes are resampled from the smallest source
+    /// that is at least as large, or else the largest one. Images that are not square
+    /// are centered on a transparent square first.
+    pub fn images_by_size(&self) -> Result<Vec<(u32, RgbaImage)>, Box<dyn Error>> {
+        let mut sources: Vec<RgbaImage> = self.images.iter().map(square_image).collect();
+        sources.sort_by_key(RgbaImage::width);
+        sources.dedup_by_key(|image| image.width());
+        if sources.is_empty() {
+            return Err(invalid_data("no images to export"));
+        }
+
+        let mut sizes: Vec<u32> = self.sizes.iter().copied().filter(|&s| s > 0).collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        Ok(sizes
+            .into_iter()
+            .map(|size| {
+                let image = image_for_size(&sources, size).unwrap_or_default();
+                (size, image)
+            })
+            .collect())
+    }
+
+    /// Writes `hicolor/<size>x<size>/apps/<name>.png` under `icons_dir` (for example
+    /// `~/.local/share/icons`), smallest first, and returns the written paths. The
+    /// same sources always produce the same bytes.
+    pub fn export<P: AsRef<Path>>(&self, icons_dir: P) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        if self.name.is_empty()
+            || self.name.contains(['/', '\\'])
+            || self.name == "."
+            || self.name == ".."
+        {
+            return Err(invalid_data(format!("invalid icon name: {:?}", self.name)));
+        }
+
+        let mut paths = Vec::new();
+        for (size, image) in self.images_by_size()? {
+            let folder = icons_dir
+                .as_ref()
+                .join("hicolor")
+                .join(format!("{size}x{size}"))
+                .join("apps");
+            fs::create_dir_all(&folder)?;
+            let path = folder.join(format!("{}.png", self.name));
+            image.save_with_format(&path, image::ImageFormat::Png)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// A `.desktop` launcher (`Type=Application`) for the Desktop Entry Specification.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DesktopEntry {
+    pub name: String,
+    /// The command line, already quoted; see [`DesktopEntry::exec_line`].
+    pub exec: String,
+    /// An icon theme name, such as [`IconThemeExport::name`], or an absolute path.
+    pub icon: String,
+    pub comment: Option<String>,
+    /// Matched against the window class so the window is grouped with the launcher.
+    /// Wine windows use the lowercase executable name, e.g. `notepad.exe`.
+    pub startup_wm_class: Option<String>,
+    pub categories: Vec<String>,
+    pub terminal: bool,
+}
+
+impl DesktopEntry {
+    pub fn new(name: &str, exec: &str, icon: &str) -> Self {
+        DesktopEntry {
+            name: name.to_owned(),
+            exec: exec.to_owned(),
+            icon: icon.to_owned(),
+            ..DesktopEntry::default()
+        }
+    }
+
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_owned());
+        self
+    }
+
+    pub fn with_startup_wm_class(mut self, class: &str) -> Self {
+        self.startup_wm_class = Some(class.to_owned());
+        self
+    }
+
+    pub fn with_categories(mut self, categories: &[&str]) -> Self {
+        self.categories = categories.iter().map(|&c| c.to_owned()).collect();
+        self
+    }
+
+    pub fn with_terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Quotes arguments for the `Exec` key: arguments with spaces or reserved
+    /// characters are double-quoted, escaping `"`, `` ` ``, `$` and `\` inside, and
+    /// `%` is doubled so it is not read as a field code.
+    pub fn exec_line(args: &[&str]) -> String {
+        args.iter()
+            .map(|arg| {
+                let arg = arg.replace('%', "%%");
+                let reserved = |c: char| c.is_whitespace() || EXEC_RESERVED.contains(c);
+                if !arg.is_empty() && !arg.contains(reserved) {
+                    return arg;
+                }
+                let mut quoted = String::from("\"");
+                for c in arg.chars() {
+                    if matches!(c, '"' | '`' | '$' | '\\') {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+                quoted.push('"');
+                quoted
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The file's text, with keys in a fixed order.
+    pub fn to_desktop_file(&self) -> String {
+        let mut text = String::from("[Desktop Entry]\nType=Application\n");
+        let mut push = |key: &str, value: &str| {
+            text.push_str(key);
+            text.push('=');
+            text.push_str(&escape_value(value));
+            text.push('\n');
+        };
+        push("Name", &self.name);
+        if let Some(comment) = &self.comment {
+            push("Comment", comment);
+        }
+        push("Exec", &self.exec);
+        push("Icon", &self.icon);
+        push("Terminal", if self.terminal { "true" } else { "false" });
+        if let Some(class) = &self.startup_wm_class {
+            push("StartupWMClass", class);
+        }
+        if !self.categories.is_empty() {
+            let categories: String = self.categories.iter().map(|c| format!("{c};")).collect();
+            push("Categories", &categories);
+        }
+        text
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path.as_ref(), self.to_desktop_file())?;
+        Ok(())
+    }
+}
+
+/// Escapes a value for a desktop file: backslashes are doubled and control
+/// characters use their escape sequences.
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn picks_a_source_per_size() {
+        let solid = |width, height, color| RgbaImage::from_pixel(width, height, Rgba(color));
+        let export = IconThemeExport::new("app")
+            .with_image(solid(16, 16, [255, 0, 0, 255]))
+            .with_image(solid(48, 48, [0, 255, 0, 255]))
+            .with_image(solid(100, 50, [0, 0, 255, 255]))
+            .with_sizes(&[48, 16, 24, 0, 64, 256, 16]);
+        let images = export.images_by_size().unwrap();
+
+        let sizes: Vec<u32> = images.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, [16, 24, 48, 64, 256]);
+        let centers: Vec<[u8; 4]> = images
+            .iter()
+            .map(|(size, image)| {
+                assert_eq!(image.dimensions(), (*size, *size));
+                image.get_pixel(size / 2, size / 2).0
+            })
+            .collect();
+        assert_eq!(centers[0], [255, 0, 0, 255]);
+        assert_eq!(centers[1], [0, 255, 0, 255]);
+        assert_eq!(centers[2], [0, 255, 0, 255]);
+        // The wide image, centered on a transparent square, serves the larger sizes
+        for (center, (size, image)) in centers[3..].iter().zip(&images[3..]) {
+            assert_eq!(center, &[0, 0, 255, 255]);
+            assert_eq!(image.get_pixel(size / 2, 0).0[3], 0);
+        }
+
+        assert!(IconThemeExport::new("app").images_by_size().is_err());
+    }
+
+    #[test]
+    fn quotes_exec_arguments() {
+        let line = DesktopEntry::exec_line(&[
+            "wine",
+            r"C:\Program Files\App\app.exe",
+            "--level=50%",
+            "",
+            "$HOME",
+            "a\"b",
+        ]);
+        assert_eq!(
+            line,
+            r#"wine "C:\\Program Files\\App\\app.exe" --level=50%% "" "\$HOME" "a\"b""#
+        );
+    }
+
+    #[test]
+    fn escapes_values() {
+        assert_eq!(escape_value("a\\b\nc\td\re"), r"a\\b\nc\td\re");
+        assert_eq!(escape_value("plain"), "plain");
+    }
+
+    #[test]
+    fn writes_keys_in_order() {
+        let entry = DesktopEntry::new("App", r#"wine "C:\\app.exe""#, "app")
+            .with_categories(&["Wine", "Game"])
+            .with_startup_wm_class("app.exe")
+            .with_comment("An\napp")
+            .with_terminal(true);
+        assert_eq!(
+            entry.to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=App\nComment=An\\napp\n\
+             Exec=wine \"C:\\\\\\\\app.exe\"\nIcon=app\nTerminal=true\n\
+             StartupWMClass=app.exe\nCategories=Wine;Game;\n"
+        );
+        assert_eq!(
+            DesktopEntry::new("App", "app", "/icons/app.png").to_desktop_file(),
+            "[Desktop Entry]\nType=Application\nName=App\nExec=app\n\
+             Icon=/icons/app.png\nTerminal=false\n"
+        );
+    }
+}
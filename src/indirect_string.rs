@@ -0,0 +1,174 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+#[cfg(windows)]
+use windows::{Win32::UI::Shell::SHLoadIndirectString, core::HSTRING};
+
+use crate::MuiModule;
+
+/// A reference to localized text, as found in registry values (`FriendlyTypeName`,
+/// `LocalizedString`), `desktop.ini` `LocalizedResourceName` and manifests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndirectString {
+    /// `@shell32.dll,-21787`: an `RT_STRING` entry of a module. The path may contain
+    /// environment variables.
+    Resource { module: String, id: u16 },
+    /// `@{PackageFullName?ms-resource://...}`: a string from a package resource
+    /// index. `source` may also be the path of a `.pri` file.
+    Package { source: String, resource: String },
+}
+
+impl IndirectString {
+    /// Parses an indirect string, or returns `None` for plain text. A trailing
+    /// `;comment` or version marker after the ID is ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().strip_prefix('@')?;
+
+        if let Some(package) = value.strip_prefix('{') {
+            let (package, _) = package.rsplit_once('}')?;
+            let (source, resource) = package.split_once('?')?;
+            return Some(IndirectString::Package {
+                source: source.trim().to_owned(),
+                resource: resource.trim().to_owned(),
+            });
+        }
+
+        let (module, id) = value.rsplit_once(',')?;
+        let id = id.split(';').next()?.trim();
+        let id = id.parse::<i32>().ok()?.unsigned_abs();
+        Some(IndirectString::Resource {
+            module: module.trim().trim_matches('"').to_owned(),
+            id: u16::try_from(id).ok()?,
+        })
+    }
+
+    /// Reads the text without the shell. `locate` maps the module path to a readable
+    /// file; its `.mui` satellites are searched for the closest of `languages`.
+    pub(crate) fn load_with<F>(
+        &self,
+        languages: &[u16],
+        locate: F,
+    ) -> Result<String, Box<dyn Error>>
+    where
+        F: Fn(&Path) -> Option<PathBuf>,
+    {
+        match self {
+            IndirectString::Resource { module, id } => {
+                let path = locate(Path::new(module)).ok_or_else(|| {
+                    io::Error::new(ErrorKind::NotFound, format!("module not found: {module}"))
+                })?;
+                let (text, _) =
+                    MuiModule::open(&path)?
+                        .string(*id, languages)
+                        .ok_or_else(|| {
+                            io::Error::new(
+                                ErrorKind::NotFound,
+                                format!("no string {id} in module: {path:?}"),
+                            )
+                        })?;
+                Ok(text)
+            }
+            IndirectString::Package { .. } => Err(Box::new(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("package resource strings can only be loaded by the shell: {self}"),
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for IndirectString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndirectString::Resource { module, id } => write!(f, "@{module},-{id}"),
+            IndirectString::Package { source, resource } => write!(f, "@{{{source}?{resource}}}"),
+        }
+    }
+}
+
+/// Loads an indirect string through the shell, which also reads package resources.
+#[cfg(windows)]
+pub(crate) fn load_indirect_string(value: &str) -> Result<String, Box<dyn Error>> {
+    let mut buffer = [0u16; 1024];
+    unsafe { SHLoadIndirectString(&HSTRING::from(value), &mut buffer, None) }
+        .map_err(|e| io::Error::other(format!("failed to load {value} - {e}")))?;
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf16_lossy(&buffer[..len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(module: &str, id: u16) -> Option<IndirectString> {
+        Some(IndirectString::Resource {
+            module: module.to_owned(),
+            id,
+        })
+    }
+
+    #[test]
+    fn parses_module_strings() {
+        assert_eq!(
+            IndirectString::parse(r"@%SystemRoot%\system32\shell32.dll,-21787"),
+            resource(r"%SystemRoot%\system32\shell32.dll", 21787)
+        );
+        assert_eq!(
+            IndirectString::parse(r#" @"C:\Program Files\app.dll", 5 "#),
+            resource(r"C:\Program Files\app.dll", 5)
+        );
+        // Version markers and comments after the ID
+        assert_eq!(
+            IndirectString::parse("@shell32.dll,-8964;v1"),
+            resource("shell32.dll", 8964)
+        );
+        assert_eq!(
+            IndirectString::parse("@wmploc.dll,-1;Windows Media"),
+            resource("wmploc.dll", 1)
+        );
+        assert_eq!(
+            resource("shell32.dll", 8964).unwrap().to_string(),
+            "@shell32.dll,-8964"
+        );
+    }
+
+    #[test]
+    fn parses_package_strings() {
+        let value = "@{Microsoft.WindowsCalculator_11.2210.0.0_x64__8wekyb3d8bbwe?\
+                     ms-resource://Microsoft.WindowsCalculator/Resources/AppStoreName}";
+        let parsed = IndirectString::parse(value).unwrap();
+        assert_eq!(
+            parsed,
+            IndirectString::Package {
+                source: "Microsoft.WindowsCalculator_11.2210.0.0_x64__8wekyb3d8bbwe".to_owned(),
+                resource: "ms-resource://Microsoft.WindowsCalculator/Resources/AppStoreName"
+                    .to_owned(),
+            }
+        );
+        assert_eq!(parsed.to_string(), value);
+        assert_eq!(
+            IndirectString::parse(r"@{C:\App\resources.pri? ms-resource:///Files/logo.png }"),
+            Some(IndirectString::Package {
+                source: r"C:\App\resources.pri".to_owned(),
+                resource: "ms-resource:///Files/logo.png".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        for value in [
+            "Notepad",
+            "@shell32.dll",
+            "@shell32.dll,-70000",
+            "@shell32.dll,name",
+            "@{Package.Name}",
+            "@{Package?ms-resource://x",
+        ] {
+            assert_eq!(IndirectString::parse(value), None, "{value}");
+        }
+    }
+}
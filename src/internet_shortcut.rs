@@ -0,0 +1,239 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    DllIcon, IconSource,
+    utils::{
+        ini_utils::Ini,
+        path_utils::{resolve_icon_path, split_icon_location},
+    },
+};
+
+/// A `.url` or `.website` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InternetShortcut {
+    pub url: Option<String>,
+    pub icon_file: Option<String>,
+    pub icon_index: i32,
+}
+
+impl InternetShortcut {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::parse(&Ini::from_bytes(&fs::read(path.as_ref())?)))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::parse(&Ini::from_bytes(data))
+    }
+
+    fn parse(ini: &Ini) -> Self {
+        let get = |key| ini.get("InternetShortcut", key);
+        InternetShortcut {
+            url: get("URL").map(str::to_owned),
+            icon_file: get("IconFile").map(str::to_owned),
+            icon_index: get("IconIndex")
+                .and_then(|i| i.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn icon_source(&self, shortcut_path: Option<&Path>) -> Option<IconSource> {
+        let base_dir = shortcut_path.and_then(Path::parent);
+        let url = self.url.as_deref().unwrap_or_default();
+
+        if let Some(icon_file) = self.icon_file.as_deref() {
+            if is_remote(icon_file) {
+                return Some(IconSource::Remote(icon_file.to_owned()));
+            }
+
+            let path = resolve_icon_path(icon_file, base_dir);
+            let path = match url_scheme(url).as_deref() {
+                Some("steam") if !path.exists() => find_steam_icon(&path).unwrap_or(path),
+                _ => path,
+            };
+            let icon = DllIcon::new().with_icon_location(path, self.icon_index);
+            return Some(IconSource::Resource(icon));
+        }
+
+        match url_scheme(url)?.as_str() {
+            "file" => file_url_to_path(url).map(IconSource::File),
+            "http" | "https" => Some(IconSource::Remote(url.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+/// A `.scf` shell command file, such as "Show Desktop".
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellCommandFile {
+    pub command: Option<String>,
+    pub icon_file: Option<String>,
+}
+
+impl ShellCommandFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_bytes(&fs::read(path.as_ref())?))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let ini = Ini::from_bytes(data);
+        ShellCommandFile {
+            command: ini.get("Shell", "Command").map(str::to_owned),
+            icon_file: ini.get("Shell", "IconFile").map(str::to_owned),
+        }
+    }
+
+    pub fn icon_source(&self, file_path: Option<&Path>) -> Option<IconSource> {
+        let icon_file = self.icon_file.as_deref()?;
+        if is_remote(icon_file) {
+            return Some(IconSource::Remote(icon_file.to_owned()));
+        }
+
+        // Unlike `.url` files, the index is part of the value: `IconFile=explorer.exe,3`
+        let (path, index) = split_icon_location(icon_file);
+        let path = resolve_icon_path(&path, file_path.and_then(Path::parent));
+        let icon = DllIcon::new().with_icon_location(path, index);
+        Some(IconSource::Resource(icon))
+    }
+}
+
+fn url_scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.trim().split_once(':')?;
+    // A single letter is a drive, not a scheme
+    (scheme.len() > 1).then(|| scheme.to_ascii_lowercase())
+}
+
+fn is_remote(location: &str) -> bool {
+    matches!(url_scheme(location).as_deref(), Some("http" | "https"))
+}
+
+/// `file:///C:/x` and `file://localhost/C:/x` are local paths; any other host, as in
+/// `file://server/share/x` or the legacy `file:////server/share/x`, names a UNC share.
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let url = url.trim();
+    let rest = url
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("file:"))
+        .map(|_| &url[5..])?;
+    let path = match rest.strip_prefix("//") {
+        Some(authority) => {
+            let (host, path) = authority.split_once('/').unwrap_or((authority, ""));
+            if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+                match path.strip_prefix('/') {
+                    Some(unc) => format!("//{}", unc.trim_start_matches('/')),
+                    None => path.to_owned(),
+                }
+            } else if host.len() == 2 && host.ends_with([':', '|']) {
+                // file://C:/x, which browsers accept
+                format!("{}:/{path}", &host[..1])
+            } else {
+                format!("//{host}/{path}")
+            }
+        }
+        None => rest.trim_start_matches('/').to_owned(),
+    };
+    let path = percent_decode(&path);
+    Some(PathBuf::from(path.replace('/', "\\")))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Steam writes `IconFile` as an absolute path into its `steam\games` folder, which
+/// goes stale when the library moves. Look the same `.ico` up in the installed client.
+fn find_steam_icon(icon_path: &Path) -> Option<PathBuf> {
+    let file_name = icon_path
+        .to_str()?
+        .rsplit(['\\', '/'])
+        .next()
+        .filter(|name| !name.is_empty())?;
+
+    ["ProgramFiles(x86)", "ProgramFiles"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .map(|root| {
+            PathBuf::from(root)
+                .join("Steam")
+                .join("steam")
+                .join("games")
+        })
+        .map(|games| games.join(file_name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_file_urls() {
+        let path = |url| file_url_to_path(url).map(|p| p.to_string_lossy().into_owned());
+        assert_eq!(
+            path("file:///C:/Program%20Files/app.ico").as_deref(),
+            Some(r"C:\Program Files\app.ico")
+        );
+        assert_eq!(
+            path("file://localhost/C:/app.ico").as_deref(),
+            Some(r"C:\app.ico")
+        );
+        assert_eq!(path("file:C:/app.ico").as_deref(), Some(r"C:\app.ico"));
+        assert_eq!(path("file://C:/app.ico").as_deref(), Some(r"C:\app.ico"));
+        assert_eq!(
+            path("file://server/share/app.ico").as_deref(),
+            Some(r"\\server\share\app.ico")
+        );
+        assert_eq!(
+            path("file:////server/share/app.ico").as_deref(),
+            Some(r"\\server\share\app.ico")
+        );
+        assert_eq!(path("https://example.com/"), None);
+    }
+
+    #[test]
+    fn reads_icon_file_and_index() {
+        let shortcut = InternetShortcut::from_bytes(
+            b"[InternetShortcut]\r\nURL=https://example.com/\r\nIconFile=C:\\app.dll\r\nIconIndex=2\r\n",
+        );
+        assert_eq!(shortcut.url.as_deref(), Some("https://example.com/"));
+        assert_eq!(
+            shortcut.icon_source(None),
+            Some(IconSource::Resource(
+                DllIcon::new().with_icon_location(r"C:\app.dll", 2)
+            ))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_url() {
+        let shortcut =
+            InternetShortcut::from_bytes(b"[InternetShortcut]\nURL=https://example.com/\n");
+        assert_eq!(
+            shortcut.icon_source(None),
+            Some(IconSource::Remote("https://example.com/".to_owned()))
+        );
+    }
+}
@@ -1,17 +1,109 @@
 mod utils {
+    pub mod binary_utils;
     pub mod image_utils;
+    pub mod ini_utils;
+    pub mod path_utils;
+    #[cfg(windows)]
     pub mod process_utils;
 }
+mod animated_cursor;
+mod app_exec_alias;
+mod autorun_inf;
+mod cursor;
+mod cursor_scheme;
+mod desktop_ini;
 mod dll_icons;
+mod file_associations;
+mod icns;
+mod icon_catalog;
+mod icon_group;
+mod icon_library;
+mod icon_source;
+mod icon_theme;
+mod indirect_string;
+mod internet_shortcut;
+mod mui_resources;
+mod ne_resources;
+mod pe_resources;
+mod pe_writer;
+mod registry_hive;
+mod res_file;
+mod resource_image;
+mod resource_table;
+mod resource_tree;
+mod shell_link;
+mod stock_icons;
 mod uwp_apps;
+mod version_info;
+mod windows_root;
+mod wine_prefix;
+mod wine_registry;
 
-pub use dll_icons::DllIcon;
+pub use animated_cursor::{AniStep, AnimatedCursor};
+#[cfg(windows)]
+use app_exec_alias::find_app_exec_alias;
+#[cfg(windows)]
+pub use app_exec_alias::read_app_exec_alias;
+pub use app_exec_alias::{AppExecAlias, IO_REPARSE_TAG_APPEXECLINK};
+pub use autorun_inf::{AutorunInf, DriveType};
+pub use cursor::{Cursor, CursorFrame, CursorImage};
+pub use cursor_scheme::{CursorRole, CursorScheme, XcursorImage, encode_xcursor};
+pub use desktop_ini::DesktopIni;
+#[cfg(windows)]
 use dll_icons::get_dll_hicon_to_image;
-use utils::image_utils::{get_hicon_to_image, image_to_base64};
+pub use dll_icons::{DllIcon, ExtractedIcon};
+pub use file_associations::FileAssociations;
+pub use icns::encode_icns;
+pub use icon_catalog::{CatalogEntry, IconCatalog, StaleEntry};
+pub use icon_group::{IconFrame, IconGroup};
+pub use icon_library::IconLibrary;
+pub use icon_source::IconSource;
+pub use icon_theme::{DesktopEntry, HICOLOR_SIZES, IconThemeExport};
+pub use indirect_string::IndirectString;
+pub use internet_shortcut::{InternetShortcut, ShellCommandFile};
+pub use mui_resources::{
+    LANG_EN_US, LANG_NEUTRAL, LANG_SYSTEM_DEFAULT, LANG_USER_DEFAULT, MuiModule, mui_paths,
+    select_language,
+};
+pub use pe_resources::{
+    PeFile, RT_BITMAP, RT_CURSOR, RT_GROUP_CURSOR, RT_GROUP_ICON, RT_ICON, RT_MANIFEST, RT_RCDATA,
+    RT_STRING, RT_VERSION, Resource, ResourceId,
+};
+pub use pe_writer::{replace_icon_group, replace_icon_group_at, replace_pe_resources};
+pub use registry_hive::{
+    REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_SZ, RegistryHive, RegistryKey,
+    RegistrySource, RegistryValue,
+};
+pub use resource_image::ResourceImage;
+pub use resource_table::{ResourceEntry, ResourceTable};
+pub use resource_tree::{
+    IconFrameSummary, IconGroupSummary, ResourceLeaf, ResourceNameNode, ResourceTree,
+    ResourceTypeNode,
+};
+pub use shell_link::{KnownFolder, ShellLink};
+pub use stock_icons::StockIcon;
+#[cfg(windows)]
+use utils::image_utils::get_hicon_to_image;
+use utils::image_utils::image_to_base64;
+#[cfg(windows)]
 use utils::process_utils::get_process_path;
-use uwp_apps::{get_uwp_icon, get_uwp_icon_base64};
+use uwp_apps::{
+    get_uwp_icon, get_uwp_icon_base64, get_uwp_icon_base64_by_app_id, get_uwp_icon_by_app_id,
+};
+pub use version_info::{FixedFileInfo, IconWithMetadata, StringTable, VersionInfo};
+pub use windows_root::WindowsRoot;
+use windows_root::{
+    get_root_file_associations, get_root_icon, get_root_icon_by_source, get_root_volume_icon,
+};
+pub use wine_prefix::WinePrefix;
+pub use wine_registry::WineRegistry;
 
-use std::{error::Error, path::Path};
+use std::{
+    error::Error,
+    ffi::OsStr,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
 
 use image::RgbaImage;
 
@@ -25,17 +117,125 @@ fn is_uwp_app(path: &Path) -> bool {
     is_uwp && !is_wsa
 }
 
+/// How many shortcuts, shell files and `desktop.ini` files are followed in a row.
+const MAX_SHELL_FILE_HOPS: usize = 8;
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Extensions of files that carry their own icon rather than their type's.
+const ICON_FILE_EXTENSIONS: [&str; 14] = [
+    "ico", "cur", "png", "jpg", "jpeg", "bmp", "gif", "exe", "dll", "cpl", "ocx", "scr", "mun",
+    "icl",
+];
+
+fn has_own_icon(path: &Path) -> bool {
+    ICON_FILE_EXTENSIONS
+        .iter()
+        .any(|extension| has_extension(path, extension))
+}
+
+fn shell_file_icon_source(path: &Path) -> Option<IconSource> {
+    if path.is_dir() {
+        DesktopIni::open(path).ok()?.icon_source(Some(path))
+    } else if has_extension(path, "lnk") {
+        ShellLink::open(path).ok()?.icon_source(Some(path))
+    } else if has_extension(path, "url") || has_extension(path, "website") {
+        InternetShortcut::open(path).ok()?.icon_source(Some(path))
+    } else if has_extension(path, "scf") {
+        ShellCommandFile::open(path).ok()?.icon_source(Some(path))
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn get_shell_icon(path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
+    get_hicon_to_image(path)
+}
+
+/// Without the shell, only files that carry their own icon can be read: icon files,
+/// images and modules with icon resources.
+#[cfg(not(windows))]
+fn get_shell_icon(path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
+    if !has_own_icon(path) {
+        return Err(Box::new(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("shell icons are only available on Windows: {path:?}"),
+        )));
+    }
+    DllIcon::new()
+        .with_icon_location(path, 0)
+        .extract_with(u32::MAX, locate_module)
+        .map(|icon| icon.image)
+}
+
+/// Finds a module on the live system: expanded paths as they are, bare names in
+/// `%SystemRoot%\System32`.
+fn locate_module(path: &Path) -> Option<PathBuf> {
+    let expanded = utils::path_utils::expand_env_vars(&path.to_string_lossy());
+    let path = PathBuf::from(&expanded);
+    if path.is_file() {
+        return Some(path);
+    }
+    if utils::path_utils::is_relative_windows_path(&expanded) {
+        let system32 = PathBuf::from(utils::path_utils::expand_env_vars("%SystemRoot%\\System32"));
+        return utils::path_utils::find_file_ignore_case(&system32, &expanded);
+    }
+    None
+}
+
 pub fn get_icon_by_path<P: AsRef<Path>>(path: P) -> Result<RgbaImage, Box<dyn Error>> {
-    let path = path.as_ref();
+    icon_by_path(path.as_ref(), MAX_SHELL_FILE_HOPS, true)
+}
+
+/// Like [`get_icon_by_path`], but folders get the plain folder icon instead of the one
+/// their `desktop.ini` customizes.
+pub fn get_icon_by_path_without_desktop_ini<P: AsRef<Path>>(
+    path: P,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    icon_by_path(path.as_ref(), MAX_SHELL_FILE_HOPS, false)
+}
+
+/// `hops` is how many more shell files may be followed, so that shortcuts pointing at
+/// themselves or at each other end.
+fn icon_by_path(path: &Path, hops: usize, desktop_ini: bool) -> Result<RgbaImage, Box<dyn Error>> {
+    #[cfg(windows)]
+    if let Some(alias) = find_app_exec_alias(path) {
+        return get_uwp_icon(&alias.target_path);
+    }
+
+    if !desktop_ini && path.is_dir() {
+        let folder = IconSource::Resource(DllIcon::stock(StockIcon::Folder));
+        return icon_by_source(folder, hops, desktop_ini);
+    }
+
+    // Resolving links ourselves avoids the shortcut arrow the shell bakes into their icons,
+    // and reading desktop.ini directly works for folders the shell has not cached
+    if hops > 0
+        && let Some(source) = shell_file_icon_source(path)
+        && let Ok(icon) = icon_by_source(source, hops - 1, desktop_ini)
+    {
+        return Ok(icon);
+    }
+
     if is_uwp_app(path) {
         get_uwp_icon(path)
     } else {
-        get_hicon_to_image(path)
+        get_shell_icon(path)
     }
 }
 
 pub fn get_icon_base64_by_path<P: AsRef<Path>>(path: P) -> Result<String, Box<dyn Error>> {
     let path = path.as_ref();
+    #[cfg(windows)]
+    if let Some(alias) = find_app_exec_alias(path) {
+        return get_uwp_icon_base64(&alias.target_path);
+    }
+
     if is_uwp_app(path) {
         get_uwp_icon_base64(path)
     } else {
@@ -44,21 +244,315 @@ pub fn get_icon_base64_by_path<P: AsRef<Path>>(path: P) -> Result<String, Box<dy
     }
 }
 
+pub fn get_icon_base64_by_path_without_desktop_ini<P: AsRef<Path>>(
+    path: P,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_path_without_desktop_ini(path)?;
+    image_to_base64(icon_image)
+}
+
+/// The icon of `path` with the version resource of the file behind it: the module
+/// itself, or the target of a shortcut.
+pub fn get_icon_with_metadata_by_path<P: AsRef<Path>>(
+    path: P,
+) -> Result<IconWithMetadata, Box<dyn Error>> {
+    let path = path.as_ref();
+    let image = get_icon_by_path(path)?;
+    let target = if has_extension(path, "lnk") {
+        ShellLink::open(path)
+            .ok()
+            .and_then(|link| link.target_path(Some(path)))
+    } else {
+        Some(path.to_path_buf())
+    };
+    Ok(IconWithMetadata {
+        image,
+        version_info: target.and_then(|target| VersionInfo::open(target).ok()),
+    })
+}
+
+#[cfg(windows)]
 pub fn get_icon_by_process_id(process_id: u32) -> Result<RgbaImage, Box<dyn Error>> {
     let process_path = get_process_path(process_id)?;
     get_icon_by_path(&process_path)
 }
 
+#[cfg(windows)]
 pub fn get_icon_base64_by_process_id(process_id: u32) -> Result<String, Box<dyn Error>> {
     let process_path = get_process_path(process_id)?;
     get_icon_base64_by_path(&process_path)
 }
 
+#[cfg(windows)]
+pub fn get_icon_with_metadata_by_process_id(
+    process_id: u32,
+) -> Result<IconWithMetadata, Box<dyn Error>> {
+    let process_path = get_process_path(process_id)?;
+    get_icon_with_metadata_by_path(&process_path)
+}
+
+/// `LoadImageW` picks the resource language itself, so icons with preferred languages
+/// are read from the module's resources directly.
+#[cfg(windows)]
 pub fn get_icon_by_dll(dll_icon: DllIcon) -> Result<RgbaImage, Box<dyn Error>> {
+    if !dll_icon.languages().is_empty() {
+        return extract_icon_by_dll(dll_icon).map(|icon| icon.image);
+    }
     get_dll_hicon_to_image(dll_icon)
 }
 
+#[cfg(windows)]
 pub fn get_icon_base64_by_dll(dll_icon: DllIcon) -> Result<String, Box<dyn Error>> {
     let dll_image = get_icon_by_dll(dll_icon)?;
     image_to_base64(dll_image)
 }
+
+/// Reads a module icon from the module's resources instead of loading it, on any
+/// platform, and reports where it was found and in which language, following a
+/// Windows 11 `.mun` file and `.mui` satellites.
+pub fn extract_icon_by_dll(dll_icon: DllIcon) -> Result<ExtractedIcon, Box<dyn Error>> {
+    dll_icon.extract_with(u32::MAX, locate_module)
+}
+
+/// Resolves an indirect string such as `@%SystemRoot%\\system32\\shell32.dll,-21787` to
+/// its text, returning plain text as is. Module strings are read from the module and
+/// its `.mui` satellites in the closest of `languages`; with no preference on Windows,
+/// and for package strings, the shell loads them.
+pub fn resolve_indirect_string(value: &str, languages: &[u16]) -> Result<String, Box<dyn Error>> {
+    let Some(indirect) = IndirectString::parse(value) else {
+        return Ok(value.to_owned());
+    };
+    #[cfg(windows)]
+    if languages.is_empty() || matches!(indirect, IndirectString::Package { .. }) {
+        return indirect_string::load_indirect_string(value);
+    }
+    indirect.load_with(languages, locate_module)
+}
+
+pub fn get_icon_by_volume<P: AsRef<Path>>(root: P) -> Result<RgbaImage, Box<dyn Error>> {
+    let root = root.as_ref();
+    if let Some(source) = AutorunInf::open(root)
+        .ok()
+        .and_then(|autorun| autorun.icon_source(root))
+        && let Ok(icon) = get_icon_by_source(source)
+    {
+        return Ok(icon);
+    }
+
+    get_icon_by_source(DriveType::of(root).icon_source())
+}
+
+pub fn get_icon_base64_by_volume<P: AsRef<Path>>(root: P) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_volume(root)?;
+    image_to_base64(icon_image)
+}
+
+pub fn get_icon_by_extension(
+    associations: &FileAssociations,
+    extension: &str,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_icon_by_source(associations.icon_source(extension)?)
+}
+
+pub fn get_icon_base64_by_extension(
+    associations: &FileAssociations,
+    extension: &str,
+) -> Result<String, Box<dyn Error>> {
+    get_icon_base64_by_source(associations.icon_source(extension)?)
+}
+
+pub fn get_icon_by_source(source: IconSource) -> Result<RgbaImage, Box<dyn Error>> {
+    icon_by_source(source, MAX_SHELL_FILE_HOPS, true)
+}
+
+fn icon_by_source(
+    source: IconSource,
+    hops: usize,
+    desktop_ini: bool,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    match source {
+        #[cfg(windows)]
+        IconSource::Resource(dll_icon) => get_icon_by_dll(dll_icon),
+        #[cfg(not(windows))]
+        IconSource::Resource(dll_icon) => extract_icon_by_dll(dll_icon).map(|icon| icon.image),
+        IconSource::File(path) => {
+            let path = utils::path_utils::expand_env_vars(&path.to_string_lossy());
+            icon_by_path(Path::new(&path), hops, desktop_ini)
+        }
+        IconSource::AppUserModelId(app_id) => get_uwp_icon_by_app_id(&app_id),
+        IconSource::Remote(url) => Err(Box::new(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("remote icons cannot be resolved offline: {url}"),
+        ))),
+    }
+}
+
+pub fn get_icon_base64_by_source(source: IconSource) -> Result<String, Box<dyn Error>> {
+    match source {
+        IconSource::AppUserModelId(app_id) => get_uwp_icon_base64_by_app_id(&app_id),
+        source => image_to_base64(get_icon_by_source(source)?),
+    }
+}
+
+/// Resolves an icon inside an offline Windows installation. `path` is a Windows path
+/// such as `%ProgramFiles%\\App\\app.exe`, or a host path below the root. Shortcuts,
+/// modules and file types are all resolved within the root, expanding environment
+/// variables with [`WindowsRoot::expand_env_vars`] rather than the host's.
+pub fn get_icon_by_path_in_root<P: AsRef<Path>>(
+    root: &WindowsRoot,
+    path: P,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_root_icon(root, path.as_ref())
+}
+
+pub fn get_icon_base64_by_path_in_root<P: AsRef<Path>>(
+    root: &WindowsRoot,
+    path: P,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_path_in_root(root, path)?;
+    image_to_base64(icon_image)
+}
+
+pub fn get_icon_by_dll_in_root(
+    root: &WindowsRoot,
+    dll_icon: DllIcon,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_root_icon_by_source(root, IconSource::Resource(dll_icon))
+}
+
+pub fn get_icon_base64_by_dll_in_root(
+    root: &WindowsRoot,
+    dll_icon: DllIcon,
+) -> Result<String, Box<dyn Error>> {
+    let dll_image = get_icon_by_dll_in_root(root, dll_icon)?;
+    image_to_base64(dll_image)
+}
+
+pub fn extract_icon_by_dll_in_root(
+    root: &WindowsRoot,
+    dll_icon: DllIcon,
+) -> Result<ExtractedIcon, Box<dyn Error>> {
+    root.extract(&dll_icon)
+}
+
+/// Resolves an indirect string with the modules of an offline Windows installation.
+pub fn resolve_indirect_string_in_root(
+    root: &WindowsRoot,
+    value: &str,
+    languages: &[u16],
+) -> Result<String, Box<dyn Error>> {
+    match IndirectString::parse(value) {
+        Some(indirect) => root.load_string(&indirect, languages),
+        None => Ok(value.to_owned()),
+    }
+}
+
+pub fn get_icon_by_source_in_root(
+    root: &WindowsRoot,
+    source: IconSource,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_root_icon_by_source(root, source)
+}
+
+pub fn get_icon_base64_by_source_in_root(
+    root: &WindowsRoot,
+    source: IconSource,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_source_in_root(root, source)?;
+    image_to_base64(icon_image)
+}
+
+/// Needs file associations on the root, see [`WindowsRoot::with_registry_hives`].
+pub fn get_icon_by_extension_in_root(
+    root: &WindowsRoot,
+    extension: &str,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    let source = get_root_file_associations(root)?.icon_source(extension)?;
+    get_root_icon_by_source(root, source)
+}
+
+pub fn get_icon_base64_by_extension_in_root(
+    root: &WindowsRoot,
+    extension: &str,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_extension_in_root(root, extension)?;
+    image_to_base64(icon_image)
+}
+
+/// The icon of the root's system volume: its `autorun.inf` icon, or the fixed drive icon.
+pub fn get_icon_by_volume_in_root(root: &WindowsRoot) -> Result<RgbaImage, Box<dyn Error>> {
+    get_root_volume_icon(root)
+}
+
+pub fn get_icon_base64_by_volume_in_root(root: &WindowsRoot) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_volume_in_root(root)?;
+    image_to_base64(icon_image)
+}
+
+/// Resolves an icon inside a Wine prefix. `path` is a Windows path in the prefix, like
+/// `C:\\Program Files\\App\\app.exe`, or a host path below it; shortcuts and
+/// registered paths are followed within the prefix.
+pub fn get_icon_by_wine_path<P: AsRef<Path>>(
+    prefix: &WinePrefix,
+    path: P,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_icon_by_path_in_root(prefix.windows_root(), path)
+}
+
+pub fn get_icon_base64_by_wine_path<P: AsRef<Path>>(
+    prefix: &WinePrefix,
+    path: P,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_wine_path(prefix, path)?;
+    image_to_base64(icon_image)
+}
+
+pub fn get_icon_by_wine_extension(
+    prefix: &WinePrefix,
+    extension: &str,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    get_icon_by_extension_in_root(prefix.windows_root(), extension)
+}
+
+pub fn get_icon_base64_by_wine_extension(
+    prefix: &WinePrefix,
+    extension: &str,
+) -> Result<String, Box<dyn Error>> {
+    let icon_image = get_icon_by_wine_extension(prefix, extension)?;
+    image_to_base64(icon_image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A shortcut whose relative path is `name`, pointing at itself or another link.
+    pub(crate) fn self_link(name: &str) -> Vec<u8> {
+        let mut data = vec![0u8; 0x4C];
+        data[0] = 0x4C;
+        data[4..20].copy_from_slice(&[
+            0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x46,
+        ]);
+        // HasRelativePath | IsUnicode
+        data[20] = 0x88;
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        for unit in name.encode_utf16() {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn shortcut_loops_end() {
+        let dir = std::env::temp_dir().join(format!("windows-icons-loop-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("self.lnk"), self_link("self.lnk")).unwrap();
+        std::fs::write(dir.join("a.lnk"), self_link("b.lnk")).unwrap();
+        std::fs::write(dir.join("b.lnk"), self_link("a.lnk")).unwrap();
+
+        assert!(get_icon_by_path(dir.join("self.lnk")).is_err());
+        assert!(get_icon_by_path(dir.join("a.lnk")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
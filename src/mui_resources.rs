@@ -0,0 +1,243 @@
+use std::{
+    error::Error,
+    ffi::OsStr,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    IconGroup, PeFile, RT_GROUP_ICON, RT_ICON, RT_STRING, Resource, ResourceId,
+    pe_resources::{string_block, string_from_block},
+    utils::path_utils::find_file_ignore_case,
+};
+
+pub const LANG_NEUTRAL: u16 = 0x0000;
+pub const LANG_USER_DEFAULT: u16 = 0x0400;
+pub const LANG_SYSTEM_DEFAULT: u16 = 0x0800;
+pub const LANG_EN_US: u16 = 0x0409;
+
+/// A module together with its resource files: the `.mun` its icons moved to on
+/// Windows 11, and the `<lang>\<name>.mui` satellites next to it. Lookups search all
+/// of them and pick a language the way the resource loader does.
+#[derive(Clone, Debug)]
+pub struct MuiModule {
+    path: PathBuf,
+    files: Vec<(PathBuf, PeFile)>,
+}
+
+impl MuiModule {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let (base, base_path) = PeFile::open_for_icons(&path)?;
+        let redirected = base_path != path;
+        let mut files = vec![(base_path, base)];
+        if redirected {
+            // The module keeps everything but its icons, like its strings
+            files.push((path.clone(), PeFile::open(&path)?));
+        }
+        for satellite in mui_paths(&path) {
+            // A damaged satellite only hides its language
+            if let Ok(pe) = PeFile::open(&satellite) {
+                files.push((satellite, pe));
+            }
+        }
+        Ok(MuiModule { path, files })
+    }
+
+    /// The module that was opened.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file icon indexes refer to: the module, or the `.mun` it was redirected to.
+    pub fn base(&self) -> (&Path, &PeFile) {
+        let (path, pe) = &self.files[0];
+        (path, pe)
+    }
+
+    /// Every file searched: the base, the module itself when redirected, then the
+    /// satellites.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &PeFile)> {
+        self.files.iter().map(|(path, pe)| (path.as_path(), pe))
+    }
+
+    /// The languages resources are available in, sorted.
+    pub fn languages(&self) -> Vec<u16> {
+        let mut languages: Vec<u16> = self
+            .files()
+            .flat_map(|(_, pe)| pe.resources().iter().map(|r| r.language))
+            .collect();
+        languages.sort_unstable();
+        languages.dedup();
+        languages
+    }
+
+    /// Finds a resource in the language closest to `languages`, in preference order.
+    pub fn find_resource(
+        &self,
+        type_id: &ResourceId,
+        name: &ResourceId,
+        languages: &[u16],
+    ) -> Option<(&Path, &PeFile, &Resource)> {
+        let candidates: Vec<(&Path, &PeFile, &Resource)> = self
+            .files()
+            .flat_map(|(path, pe)| {
+                pe.find_resources(type_id, name)
+                    .into_iter()
+                    .map(move |resource| (path, pe, resource))
+            })
+            .collect();
+        let index = select_language(candidates.iter().map(|(_, _, r)| r.language), languages)?;
+        Some(candidates[index])
+    }
+
+    /// An `RT_STRING` entry in the language closest to `languages`, with its LANGID.
+    pub fn string(&self, id: u16, languages: &[u16]) -> Option<(String, u16)> {
+        let (_, pe, block) =
+            self.find_resource(&RT_STRING.into(), &string_block(id).into(), languages)?;
+        let text = string_from_block(pe.resource_data(block), id)?;
+        Some((text, block.language))
+    }
+
+    /// An icon group in the language closest to `languages`, with the file it was read
+    /// from and its LANGID. Its frames come from the same file, in the same language
+    /// where there is one.
+    pub fn icon_group(
+        &self,
+        name: &ResourceId,
+        languages: &[u16],
+    ) -> Result<(IconGroup, &Path, u16), Box<dyn Error>> {
+        let (path, pe, group) = self
+            .find_resource(&RT_GROUP_ICON.into(), name, languages)
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, format!("icon group not found: {name}"))
+            })?;
+
+        let icon_group = IconGroup::from_group_bytes(pe.resource_data(group), |id| {
+            let icons = pe.find_resources(&RT_ICON.into(), &id.into());
+            let index = select_language(icons.iter().map(|r| r.language), &[group.language])?;
+            Some(pe.resource_data(icons[index]).to_vec())
+        })?;
+        Ok((icon_group, path, group.language))
+    }
+}
+
+/// The `.mui` satellites of a module: `<dir>\<lang>\<name>.mui` for every language
+/// folder next to it, sorted.
+pub fn mui_paths(module: &Path) -> Vec<PathBuf> {
+    let (Some(folder), Some(file_name)) = (module.parent(), module.file_name()) else {
+        return Vec::new();
+    };
+    let mui_name = format!("{}.mui", file_name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && path.file_name().is_some_and(is_locale_name))
+        .filter_map(|dir| find_file_ignore_case(&dir, &mui_name))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Language folders are named like `en-US`, `sr-Latn-RS` or `zh-Hans`.
+fn is_locale_name(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    let mut parts = name.split('-');
+    parts
+        .next()
+        .is_some_and(|language| (2..=3).contains(&language.len()))
+        && parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Picks among resource languages the way the resource loader falls back: an exact
+/// preferred language, then its neutral form (the primary language alone, then any
+/// sub-language of it, then `LANG_NEUTRAL`), then the user, system and English
+/// defaults, then the first one. Returns an index into `available`.
+pub fn select_language<I>(available: I, preferred: &[u16]) -> Option<usize>
+where
+    I: IntoIterator<Item = u16>,
+{
+    let available: Vec<u16> = available.into_iter().collect();
+    let position = |language: u16| available.iter().position(|&l| l == language);
+    let primary = |language: u16| language & 0x3ff;
+
+    preferred
+        .iter()
+        .find_map(|&language| position(language))
+        .or_else(|| {
+            preferred.iter().find_map(|&language| {
+                position(primary(language)).or_else(|| {
+                    available
+                        .iter()
+                        .position(|&l| primary(l) == primary(language) && l != LANG_NEUTRAL)
+                })
+            })
+        })
+        .or_else(|| {
+            [
+                LANG_NEUTRAL,
+                LANG_USER_DEFAULT,
+                LANG_SYSTEM_DEFAULT,
+                LANG_EN_US,
+            ]
+            .into_iter()
+            .find_map(position)
+        })
+        .or_else(|| (!available.is_empty()).then_some(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_languages_like_the_resource_loader() {
+        let select = |available: &[u16], preferred: &[u16]| {
+            select_language(available.iter().copied(), preferred)
+        };
+        // Every preferred language is tried exactly before any is tried loosely
+        assert_eq!(select(&[0x0407, 0x0409], &[0x0409]), Some(1));
+        assert_eq!(select(&[0x0407, 0x0409], &[0x0C07, 0x0409]), Some(1));
+        // The primary language alone, then any sub-language of it
+        assert_eq!(select(&[0x0409, 0x0007, 0x0407], &[0x0C07]), Some(1));
+        assert_eq!(select(&[0x0409, 0x0407], &[0x0C07]), Some(1));
+        // Neutral before the English default, then the first language
+        assert_eq!(select(&[0x0409, LANG_NEUTRAL], &[0x0411]), Some(1));
+        assert_eq!(select(&[0x0407, LANG_EN_US], &[0x0411]), Some(1));
+        assert_eq!(select(&[0x0407, 0x040C], &[0x0411]), Some(0));
+        assert_eq!(select(&[0x0407, 0x040C], &[]), Some(0));
+        assert_eq!(select(&[], &[0x0409]), None);
+    }
+
+    #[test]
+    fn finds_satellites_in_language_folders() {
+        let folder = std::env::temp_dir().join(format!("mui-paths-{}", std::process::id()));
+        let module = folder.join("shell32.dll");
+        for (language, file) in [
+            ("en-US", "SHELL32.dll.mui"),
+            ("de-DE", "shell32.dll.mui"),
+            ("zh-Hans", "imageres.dll.mui"),
+            ("backup", "shell32.dll.mui"),
+        ] {
+            fs::create_dir_all(folder.join(language)).unwrap();
+            fs::write(folder.join(language).join(file), b"").unwrap();
+        }
+        fs::write(&module, b"").unwrap();
+
+        assert_eq!(
+            mui_paths(&module),
+            [
+                folder.join("de-DE").join("shell32.dll.mui"),
+                folder.join("en-US").join("SHELL32.dll.mui"),
+            ]
+        );
+        assert!(is_locale_name(OsStr::new("sr-Latn-RS")));
+        assert!(!is_locale_name(OsStr::new("en-")));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::error::Error;
+
+use crate::{
+    Resource, ResourceId,
+    utils::binary_utils::{invalid_data, read_u16, truncated},
+};
+
+const RESOURCE_TABLE_OFFSET: usize = 0x24;
+const TYPE_INFO_SIZE: usize = 8;
+const NAME_INFO_SIZE: usize = 12;
+const INTEGER_ID: u16 = 0x8000;
+
+/// Reads the resource table of a 16-bit NE image (Win16 executables and `.icl` icon
+/// libraries) at `ne_offset`. NE resources carry no language; they are reported as
+/// language-neutral.
+pub(crate) fn read_ne_resources(
+    data: &[u8],
+    ne_offset: usize,
+) -> Result<Vec<Resource>, Box<dyn Error>> {
+    let table_offset =
+        read_u16(data, ne_offset + RESOURCE_TABLE_OFFSET).ok_or_else(|| truncated("NE header"))?;
+    let table = ne_offset + usize::from(table_offset);
+    let align_shift = read_u16(data, table).ok_or_else(|| truncated("NE resource table"))?;
+    if align_shift > 16 {
+        return Err(invalid_data("invalid NE resource alignment"));
+    }
+
+    let mut resources = Vec::new();
+    let mut type_info = table + 2;
+    loop {
+        let type_id = read_u16(data, type_info).ok_or_else(|| truncated("NE resource table"))?;
+        if type_id == 0 {
+            break;
+        }
+        let count = read_u16(data, type_info + 2).ok_or_else(|| truncated("NE resource table"))?;
+        let type_id = resource_id(data, table, type_id)?;
+
+        for i in 0..usize::from(count) {
+            let name_info = type_info + TYPE_INFO_SIZE + i * NAME_INFO_SIZE;
+            let field = |offset| {
+                read_u16(data, name_info + offset).ok_or_else(|| truncated("NE resource table"))
+            };
+            let offset = usize::from(field(0)?) << align_shift;
+            let size = usize::from(field(2)?) << align_shift;
+            if offset >= data.len() {
+                continue;
+            }
+            resources.push(Resource {
+                type_id: type_id.clone(),
+                name: resource_id(data, table, field(6)?)?,
+                language: 0,
+                code_page: 0,
+                offset,
+                // Sizes are rounded up to the alignment, past the end of the last one
+                size: size.min(data.len() - offset),
+            });
+        }
+        type_info += TYPE_INFO_SIZE + usize::from(count) * NAME_INFO_SIZE;
+    }
+    Ok(resources)
+}
+
+/// An ID with the high bit set is an integer; otherwise it is the offset of a
+/// length-prefixed name from the start of the resource table.
+fn resource_id(data: &[u8], table: usize, id: u16) -> Result<ResourceId, Box<dyn Error>> {
+    if id & INTEGER_ID != 0 {
+        return Ok(ResourceId::Id(id & !INTEGER_ID));
+    }
+    let start = table + usize::from(id);
+    let len = usize::from(
+        *data
+            .get(start)
+            .ok_or_else(|| truncated("NE resource name"))?,
+    );
+    let name = data
+        .get(start + 1..start + 1 + len)
+        .ok_or_else(|| truncated("NE resource name"))?;
+    Ok(ResourceId::Name(String::from_utf8_lossy(name).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PeFile, RT_GROUP_ICON};
+
+    const NE_OFFSET: usize = 0x40;
+    const TABLE: usize = 0x80;
+
+    /// An NE image with 16-byte aligned resources: group icon 1 at 0x100, and a named
+    /// `PNG` resource `LOGO` at 0x120 whose rounded-up size runs past the end.
+    fn ne_image(align_shift: u16) -> Vec<u8> {
+        let mut data = vec![0u8; TABLE];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3C..0x40].copy_from_slice(&(NE_OFFSET as u32).to_le_bytes());
+        data[NE_OFFSET..NE_OFFSET + 2].copy_from_slice(b"NE");
+        data[NE_OFFSET + RESOURCE_TABLE_OFFSET..NE_OFFSET + RESOURCE_TABLE_OFFSET + 2]
+            .copy_from_slice(&((TABLE - NE_OFFSET) as u16).to_le_bytes());
+
+        let mut table = align_shift.to_le_bytes().to_vec();
+        let type_info = |table: &mut Vec<u8>, type_id: u16, offset: u16, size: u16, id: u16| {
+            for value in [type_id, 1, 0, 0, offset, size, 0x30, id, 0, 0] {
+                table.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+        // The names follow the terminating zero type, 44 bytes into the table
+        type_info(
+            &mut table,
+            INTEGER_ID | RT_GROUP_ICON,
+            0x10,
+            1,
+            INTEGER_ID | 1,
+        );
+        type_info(&mut table, 44, 0x12, 2, 48);
+        table.extend_from_slice(&[0, 0]);
+        table.extend_from_slice(b"\x03PNG\x04LOGO\x00");
+        data.extend_from_slice(&table);
+        data.resize(0x100, 0);
+        data.extend_from_slice(&[0xAA; 16]);
+        data.extend_from_slice(&[0xBB; 20]);
+        data
+    }
+
+    #[test]
+    fn reads_the_resource_table() {
+        let pe = PeFile::from_bytes(ne_image(4)).unwrap();
+        let resources = pe.resources();
+        assert_eq!(resources.len(), 2);
+        assert_eq!(resources[0].type_id, ResourceId::Id(RT_GROUP_ICON));
+        assert_eq!(resources[0].name, ResourceId::Id(1));
+        assert_eq!(pe.resource_data(&resources[0]), [0xAA; 16]);
+
+        assert_eq!(resources[1].type_id, ResourceId::Name("PNG".to_owned()));
+        assert_eq!(resources[1].name, ResourceId::Name("LOGO".to_owned()));
+        assert_eq!(resources[1].offset, 0x120);
+        assert_eq!(pe.resource_data(&resources[1]), [0xBB; 4]);
+        assert_eq!(pe.icon_groups(), [ResourceId::Id(1)]);
+    }
+
+    #[test]
+    fn rejects_broken_tables() {
+        assert!(PeFile::from_bytes(ne_image(17)).is_err());
+        let data = ne_image(4);
+        assert!(PeFile::from_bytes(data[..TABLE + 12].to_vec()).is_err());
+    }
+}
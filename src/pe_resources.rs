@@ -0,0 +1,562 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Cursor, IconGroup,
+    mui_resources::select_language,
+    ne_resources::read_ne_resources,
+    res_file::{is_res_file, read_res_resources},
+    utils::{
+        binary_utils::{decode_utf16le, invalid_data, read_u16, read_u32, truncated},
+        path_utils::{find_dir_ignore_case, find_file_ignore_case},
+    },
+};
+
+pub const RT_CURSOR: u16 = 1;
+pub const RT_BITMAP: u16 = 2;
+pub const RT_ICON: u16 = 3;
+pub const RT_STRING: u16 = 6;
+pub const RT_RCDATA: u16 = 10;
+pub const RT_GROUP_CURSOR: u16 = 12;
+pub const RT_GROUP_ICON: u16 = 14;
+pub const RT_VERSION: u16 = 16;
+pub const RT_MANIFEST: u16 = 24;
+
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+const MAX_DIRECTORY_DEPTH: usize = 3;
+
+/// A resource type or name: a numeric ID or a string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceId {
+    Id(u16),
+    Name(String),
+}
+
+impl ResourceId {
+    /// Parses `#123` or `123` as an ID and anything else as a name.
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        match value.strip_prefix('#').unwrap_or(value).parse() {
+            Ok(id) => ResourceId::Id(id),
+            Err(_) => ResourceId::Name(value.to_owned()),
+        }
+    }
+
+    /// Resource names are case-insensitive; resource compilers store them in upper case.
+    pub fn matches(&self, other: &ResourceId) -> bool {
+        match (self, other) {
+            (ResourceId::Id(a), ResourceId::Id(b)) => a == b,
+            (ResourceId::Name(a), ResourceId::Name(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+
+impl From<u16> for ResourceId {
+    fn from(id: u16) -> Self {
+        ResourceId::Id(id)
+    }
+}
+
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceId::Id(id) => write!(f, "#{id}"),
+            ResourceId::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// IDs serialize as numbers and names as strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ResourceId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ResourceId::Id(id) => serializer.serialize_u16(*id),
+            ResourceId::Name(name) => serializer.serialize_str(name),
+        }
+    }
+}
+
+/// A leaf of the resource directory: one language of one named resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resource {
+    pub type_id: ResourceId,
+    pub name: ResourceId,
+    pub language: u16,
+    pub code_page: u32,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// The resources of a PE image (`.exe`, `.dll`, `.mun`, ...), read without loading it.
+/// 16-bit NE images, like `.icl` icon libraries, and compiled resource files (`.res`)
+/// are read the same way.
+#[derive(Clone, Debug)]
+pub struct PeFile {
+    data: Vec<u8>,
+    resources: Vec<Resource>,
+}
+
+impl PeFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(fs::read(path.as_ref())?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        if is_res_file(&data) {
+            let resources = read_res_resources(&data)?;
+            return Ok(PeFile { data, resources });
+        }
+        if data.get(..2) != Some(b"MZ".as_slice()) {
+            return Err(invalid_data("not an MZ executable"));
+        }
+        let pe_offset = read_u32(&data, 0x3C).ok_or_else(|| truncated("DOS header"))? as usize;
+        if data.get(pe_offset..pe_offset + 2) == Some(b"NE".as_slice()) {
+            let resources = read_ne_resources(&data, pe_offset)?;
+            return Ok(PeFile { data, resources });
+        }
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0".as_slice()) {
+            return Err(invalid_data("not a PE or NE image"));
+        }
+
+        let coff = pe_offset + 4;
+        let section_count =
+            usize::from(read_u16(&data, coff + 2).ok_or_else(|| truncated("COFF header"))?);
+        let optional_size =
+            usize::from(read_u16(&data, coff + 16).ok_or_else(|| truncated("COFF header"))?);
+        let optional = coff + 20;
+        let directories = match read_u16(&data, optional) {
+            Some(0x10B) => optional + 96,
+            Some(0x20B) => optional + 112,
+            _ => return Err(invalid_data("unknown optional header magic")),
+        };
+
+        let sections: Vec<Section> = (0..section_count)
+            .filter_map(|i| {
+                let header = optional + optional_size + i * 40;
+                Some(Section {
+                    virtual_address: read_u32(&data, header + 12)?,
+                    virtual_size: read_u32(&data, header + 8)?,
+                    raw_size: read_u32(&data, header + 16)?,
+                    raw_offset: read_u32(&data, header + 20)?,
+                })
+            })
+            .collect();
+
+        let directory = directories + IMAGE_DIRECTORY_ENTRY_RESOURCE * 8;
+        let resource_rva = read_u32(&data, directory).unwrap_or_default();
+        let mut pe = PeFile {
+            data,
+            resources: Vec::new(),
+        };
+        if resource_rva == 0 {
+            return Ok(pe);
+        }
+
+        let root = rva_to_offset(&sections, resource_rva)
+            .ok_or_else(|| invalid_data("resource directory is outside all sections"))?;
+        let mut resources = Vec::new();
+        let mut walker = DirectoryWalker {
+            data: &pe.data,
+            root,
+            sections: &sections,
+            path: Vec::new(),
+            resources: &mut resources,
+        };
+        walker.walk(root, 0);
+        pe.resources = resources;
+        Ok(pe)
+    }
+
+    /// Every resource, in resource directory order.
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    pub fn resource_data(&self, resource: &Resource) -> &[u8] {
+        self.data
+            .get(resource.offset..resource.offset + resource.size)
+            .unwrap_or_default()
+    }
+
+    /// All languages of one resource, in directory order.
+    pub fn find_resources(&self, type_id: &ResourceId, name: &ResourceId) -> Vec<&Resource> {
+        self.resources
+            .iter()
+            .filter(|r| r.type_id.matches(type_id) && r.name.matches(name))
+            .collect()
+    }
+
+    pub fn find_resource(&self, type_id: &ResourceId, name: &ResourceId) -> Option<&Resource> {
+        self.find_resources(type_id, name).into_iter().next()
+    }
+
+    /// Names of the resources of one type, in directory order, without duplicates
+    /// for different languages.
+    pub fn resource_names(&self, type_id: &ResourceId) -> Vec<ResourceId> {
+        let mut names: Vec<ResourceId> = Vec::new();
+        for resource in self.resources.iter().filter(|r| r.type_id.matches(type_id)) {
+            if !names.iter().any(|n| n.matches(&resource.name)) {
+                names.push(resource.name.clone());
+            }
+        }
+        names
+    }
+
+    /// The `RT_GROUP_ICON` names, in the order `ExtractIcon` indexes address them.
+    pub fn icon_groups(&self) -> Vec<ResourceId> {
+        self.resource_names(&RT_GROUP_ICON.into())
+    }
+
+    pub fn icon_group(&self, name: &ResourceId) -> Result<IconGroup, Box<dyn Error>> {
+        let group = self
+            .find_resource(&RT_GROUP_ICON.into(), name)
+            .ok_or_else(|| not_found(format!("icon group not found: {name}")))?;
+
+        IconGroup::from_group_bytes(self.resource_data(group), |id| {
+            let icons = self.find_resources(&RT_ICON.into(), &id.into());
+            let index = select_language(icons.iter().map(|r| r.language), &[group.language])?;
+            Some(self.resource_data(icons[index]).to_vec())
+        })
+    }
+
+    /// Looks an icon group up with icon location semantics: a zero-based index into
+    /// [`PeFile::icon_groups`], or a negative resource ID.
+    pub fn icon_group_at(&self, index: i32) -> Result<IconGroup, Box<dyn Error>> {
+        self.icon_group(&self.icon_group_name_at(index)?)
+    }
+
+    /// The name of the icon group an icon location refers to.
+    pub fn icon_group_name_at(&self, index: i32) -> Result<ResourceId, Box<dyn Error>> {
+        if index < 0 {
+            let id = u16::try_from(index.unsigned_abs())
+                .map_err(|_| not_found(format!("invalid icon resource ID: {index}")))?;
+            Ok(ResourceId::Id(id))
+        } else {
+            self.icon_groups()
+                .into_iter()
+                .nth(index as usize)
+                .ok_or_else(|| not_found(format!("icon index out of range: {index}")))
+        }
+    }
+
+    /// The `RT_GROUP_CURSOR` names, in directory order.
+    pub fn cursor_groups(&self) -> Vec<ResourceId> {
+        self.resource_names(&RT_GROUP_CURSOR.into())
+    }
+
+    pub fn cursor_group(&self, name: &ResourceId) -> Result<Cursor, Box<dyn Error>> {
+        let group = self
+            .find_resource(&RT_GROUP_CURSOR.into(), name)
+            .ok_or_else(|| not_found(format!("cursor group not found: {name}")))?;
+
+        Cursor::from_group_bytes(self.resource_data(group), |id| {
+            let cursors = self.find_resources(&RT_CURSOR.into(), &id.into());
+            let index = select_language(cursors.iter().map(|r| r.language), &[group.language])?;
+            Some(self.resource_data(cursors[index]).to_vec())
+        })
+    }
+
+    /// Looks a cursor group up by zero-based index or negative resource ID, like
+    /// [`PeFile::icon_group_at`].
+    pub fn cursor_group_at(&self, index: i32) -> Result<Cursor, Box<dyn Error>> {
+        let name = if index < 0 {
+            let id = u16::try_from(index.unsigned_abs())
+                .map_err(|_| not_found(format!("invalid cursor resource ID: {index}")))?;
+            ResourceId::Id(id)
+        } else {
+            self.cursor_groups()
+                .into_iter()
+                .nth(index as usize)
+                .ok_or_else(|| not_found(format!("cursor index out of range: {index}")))?
+        };
+        self.cursor_group(&name)
+    }
+
+    /// An entry of the `RT_STRING` tables, in the first language found.
+    pub fn string(&self, id: u16) -> Option<String> {
+        let block = self.find_resource(&RT_STRING.into(), &string_block(id).into())?;
+        string_from_block(self.resource_data(block), id)
+    }
+
+    /// Opens `path` for its icons. Windows 11 moved the icons of many system modules
+    /// into `%SystemRoot%\SystemResources\<name>.mun`, leaving none in the module;
+    /// the `.mun` file is read instead then. Returns the file actually read.
+    pub fn open_for_icons<P: AsRef<Path>>(path: P) -> Result<(Self, PathBuf), Box<dyn Error>> {
+        let path = path.as_ref();
+        let pe = PeFile::open(path)?;
+        if pe.icon_groups().is_empty()
+            && let Some(mun) = mun_path(path)
+        {
+            return Ok((PeFile::open(&mun)?, mun));
+        }
+        Ok((pe, path.to_path_buf()))
+    }
+}
+
+/// The `.mun` resource file of a module in `System32` or `SysWOW64`, if there is one.
+pub fn mun_path(module: &Path) -> Option<PathBuf> {
+    let file_name = module.file_name()?.to_str()?;
+    let windows = module.parent()?.parent()?;
+    let resources = find_dir_ignore_case(windows, "SystemResources")?;
+    find_file_ignore_case(&resources, &format!("{file_name}.mun"))
+}
+
+/// String tables hold 16 strings per resource, numbered from 1.
+pub(crate) fn string_block(id: u16) -> u16 {
+    id / 16 + 1
+}
+
+/// Reads string `id` from its 16-string block: each entry is a UTF-16 length followed
+/// by that many characters, with empty entries for unused IDs.
+pub(crate) fn string_from_block(data: &[u8], id: u16) -> Option<String> {
+    let mut offset = 0;
+    for _ in 0..id % 16 {
+        offset += 2 + 2 * read_u16(data, offset)? as usize;
+    }
+    let len = read_u16(data, offset)? as usize;
+    if len == 0 {
+        return None;
+    }
+    let text = data.get(offset + 2..offset + 2 + 2 * len)?;
+    // `rc -n` counts a terminating null
+    Some(decode_utf16le(text).trim_end_matches('\0').to_owned())
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+fn rva_to_offset(sections: &[Section], rva: u32) -> Option<usize> {
+    sections.iter().find_map(|s| {
+        let size = s.virtual_size.max(s.raw_size);
+        let delta = rva.checked_sub(s.virtual_address)?;
+        (delta < size).then(|| s.raw_offset as usize + delta as usize)
+    })
+}
+
+struct DirectoryWalker<'a> {
+    data: &'a [u8],
+    root: usize,
+    sections: &'a [Section],
+    path: Vec<ResourceId>,
+    resources: &'a mut Vec<Resource>,
+}
+
+impl DirectoryWalker<'_> {
+    fn walk(&mut self, directory: usize, depth: usize) {
+        let named = read_u16(self.data, directory + 12).unwrap_or_default();
+        let ids = read_u16(self.data, directory + 14).unwrap_or_default();
+
+        for i in 0..usize::from(named) + usize::from(ids) {
+            let entry = directory + 16 + i * 8;
+            let (Some(name), Some(target)) =
+                (read_u32(self.data, entry), read_u32(self.data, entry + 4))
+            else {
+                return;
+            };
+
+            let id = if name & 0x8000_0000 != 0 {
+                let offset = self.root + (name & 0x7FFF_FFFF) as usize;
+                let len = usize::from(read_u16(self.data, offset).unwrap_or_default());
+                let bytes = self
+                    .data
+                    .get(offset + 2..offset + 2 + len * 2)
+                    .unwrap_or_default();
+                ResourceId::Name(decode_utf16le(bytes))
+            } else {
+                ResourceId::Id(name as u16)
+            };
+
+            let target_offset = self.root + (target & 0x7FFF_FFFF) as usize;
+            if target & 0x8000_0000 != 0 {
+                if depth + 1 < MAX_DIRECTORY_DEPTH {
+                    self.path.push(id);
+                    self.walk(target_offset, depth + 1);
+                    self.path.pop();
+                }
+            } else if depth == MAX_DIRECTORY_DEPTH - 1 {
+                self.push_resource(id, target_offset);
+            }
+        }
+    }
+
+    fn push_resource(&mut self, language: ResourceId, entry: usize) {
+        let (Some(rva), Some(size), Some(code_page)) = (
+            read_u32(self.data, entry),
+            read_u32(self.data, entry + 4),
+            read_u32(self.data, entry + 8),
+        ) else {
+            return;
+        };
+        let (Some(offset), [type_id, name]) =
+            (rva_to_offset(self.sections, rva), self.path.as_slice())
+        else {
+            return;
+        };
+        let language = match language {
+            ResourceId::Id(id) => id,
+            ResourceId::Name(_) => 0,
+        };
+
+        self.resources.push(Resource {
+            type_id: type_id.clone(),
+            name: name.clone(),
+            language,
+            code_page,
+            offset,
+            size: size as usize,
+        });
+    }
+}
+
+fn not_found(message: String) -> Box<dyn Error> {
+    Box::new(io::Error::new(ErrorKind::NotFound, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTION_RVA: u32 = 0x1000;
+    const SECTION_OFFSET: usize = 0x200;
+
+    /// A directory table: named entries must come first, as in real images.
+    fn directory(data: &mut Vec<u8>, named: u16, entries: &[(u32, u32)]) {
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&named.to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16 - named).to_le_bytes());
+        for (name, target) in entries {
+            data.extend_from_slice(&name.to_le_bytes());
+            data.extend_from_slice(&target.to_le_bytes());
+        }
+    }
+
+    /// A `.rsrc` section with a named `PNG` resource 1 (0x409) and the first
+    /// `RT_STRING` block (0x407), laid out as type, name and language levels.
+    fn resource_section() -> Vec<u8> {
+        const SUBDIR: u32 = 0x8000_0000;
+        let mut rsrc = Vec::new();
+        directory(
+            &mut rsrc,
+            1,
+            &[(SUBDIR | 160, SUBDIR | 32), (6, SUBDIR | 56)],
+        );
+        directory(&mut rsrc, 0, &[(1, SUBDIR | 80)]);
+        directory(&mut rsrc, 0, &[(1, SUBDIR | 104)]);
+        directory(&mut rsrc, 0, &[(0x409, 128)]);
+        directory(&mut rsrc, 0, &[(0x407, 144)]);
+        for (offset, size) in [(168u32, 8u32), (176, 8)] {
+            rsrc.extend_from_slice(&(SECTION_RVA + offset).to_le_bytes());
+            rsrc.extend_from_slice(&size.to_le_bytes());
+            rsrc.extend_from_slice(&1252u32.to_le_bytes());
+            rsrc.extend_from_slice(&[0; 4]);
+        }
+        rsrc.extend_from_slice(&3u16.to_le_bytes());
+        rsrc.extend(b"PNG".iter().flat_map(|&c| [c, 0]));
+        rsrc.extend_from_slice(PNG_BYTES);
+        // Strings 0 and 1 of block 1: empty, then "Hi"
+        rsrc.extend_from_slice(&[0, 0, 2, 0, b'H', 0, b'i', 0]);
+        rsrc
+    }
+
+    const PNG_BYTES: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn pe_image(magic: u16, rsrc: &[u8], resource_rva: u32) -> Vec<u8> {
+        let mut data = vec![0u8; SECTION_OFFSET];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let coff = 0x44;
+        data[coff + 2..coff + 4].copy_from_slice(&1u16.to_le_bytes());
+        let directories = if magic == 0x10B { 96 } else { 112 };
+        let optional_size = directories + 16 * 8;
+        data[coff + 16..coff + 18].copy_from_slice(&(optional_size as u16).to_le_bytes());
+        let optional = coff + 20;
+        data[optional..optional + 2].copy_from_slice(&magic.to_le_bytes());
+        let entry = optional + directories + IMAGE_DIRECTORY_ENTRY_RESOURCE * 8;
+        data[entry..entry + 4].copy_from_slice(&resource_rva.to_le_bytes());
+        data[entry + 4..entry + 8].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+
+        let section = optional + optional_size;
+        data[section..section + 5].copy_from_slice(b".rsrc");
+        data[section + 8..section + 12].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        data[section + 12..section + 16].copy_from_slice(&SECTION_RVA.to_le_bytes());
+        data[section + 16..section + 20].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        data[section + 20..section + 24].copy_from_slice(&(SECTION_OFFSET as u32).to_le_bytes());
+        data.extend_from_slice(rsrc);
+        data
+    }
+
+    #[test]
+    fn walks_the_resource_directory() {
+        for magic in [0x10B, 0x20B] {
+            let pe = PeFile::from_bytes(pe_image(magic, &resource_section(), SECTION_RVA)).unwrap();
+            let png = ResourceId::Name("PNG".to_owned());
+
+            assert_eq!(
+                pe.resources()[0],
+                Resource {
+                    type_id: png.clone(),
+                    name: ResourceId::Id(1),
+                    language: 0x409,
+                    code_page: 1252,
+                    offset: SECTION_OFFSET + 168,
+                    size: 8,
+                }
+            );
+            let resource = pe.find_resource(&ResourceId::parse("png"), &ResourceId::parse("#1"));
+            assert_eq!(pe.resource_data(resource.unwrap()), PNG_BYTES);
+            assert_eq!(pe.resources()[1].language, 0x407);
+            assert_eq!(pe.string(1).as_deref(), Some("Hi"));
+            assert_eq!(pe.string(0), None);
+        }
+    }
+
+    #[test]
+    fn addresses_icon_groups_by_location() {
+        let pe = PeFile::from_bytes(pe_image(0x20B, &resource_section(), SECTION_RVA)).unwrap();
+        assert!(pe.icon_groups().is_empty());
+        assert_eq!(pe.icon_group_name_at(-5).unwrap(), ResourceId::Id(5));
+        assert!(pe.icon_group_name_at(0).is_err());
+        assert!(pe.icon_group_name_at(-70000).is_err());
+    }
+
+    #[test]
+    fn stops_at_directory_loops() {
+        // The root's only entry points back at the root
+        let mut rsrc = Vec::new();
+        directory(&mut rsrc, 0, &[(3, 0x8000_0000)]);
+        let pe = PeFile::from_bytes(pe_image(0x20B, &rsrc, SECTION_RVA)).unwrap();
+        assert!(pe.resources().is_empty());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let rsrc = resource_section();
+        assert!(PeFile::from_bytes(b"ZM".to_vec()).is_err());
+        assert!(PeFile::from_bytes(pe_image(0x107, &rsrc, SECTION_RVA)).is_err());
+        assert!(PeFile::from_bytes(pe_image(0x20B, &rsrc, 0x8000)).is_err());
+
+        let mut data = pe_image(0x20B, &rsrc, SECTION_RVA);
+        data[0x40..0x44].copy_from_slice(b"LE\0\0");
+        assert!(PeFile::from_bytes(data).is_err());
+    }
+
+    #[test]
+    fn parses_resource_ids() {
+        assert_eq!(ResourceId::parse("#14"), ResourceId::Id(14));
+        assert_eq!(ResourceId::parse(" 3 "), ResourceId::Id(3));
+        assert_eq!(ResourceId::parse("APP"), ResourceId::Name("APP".to_owned()));
+        assert!(ResourceId::parse("app").matches(&ResourceId::parse("APP")));
+        assert!(!ResourceId::Id(1).matches(&ResourceId::Name("1".to_owned())));
+    }
+}
@@ -0,0 +1,584 @@
+use std::{cmp::Ordering, error::Error};
+
+use crate::{
+    IconGroup, PeFile, ResourceEntry, ResourceId, ResourceTable,
+    utils::binary_utils::{invalid_data, read_u16, read_u32, truncated},
+};
+
+const SECTION_HEADER_SIZE: usize = 40;
+const DIRECTORY_SIZE: usize = 16;
+const DIRECTORY_ENTRY_SIZE: usize = 8;
+const DATA_ENTRY_SIZE: usize = 16;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+/// `IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ`, as linkers mark `.rsrc`.
+const RSRC_CHARACTERISTICS: u32 = 0x4000_0040;
+
+/// Replaces every language of the icon group `name` in a PE image with `group`, and
+/// returns the new image. See [`replace_pe_resources`].
+pub fn replace_icon_group(
+    image: &[u8],
+    name: &ResourceId,
+    group: &IconGroup,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let pe = PeFile::from_bytes(image.to_vec())?;
+    let mut resources = ResourceTable::from_pe(&pe);
+    resources.replace_icon_group(name, group)?;
+    replace_pe_resources(image, &resources)
+}
+
+/// Replaces the icon group at an `ExtractIcon`-style index: the Nth group when it is
+/// zero or more, the group with the resource ID `-index` otherwise.
+pub fn replace_icon_group_at(
+    image: &[u8],
+    index: i32,
+    group: &IconGroup,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let name = PeFile::from_bytes(image.to_vec())?.icon_group_name_at(index)?;
+    replace_icon_group(image, &name, group)
+}
+
+/// Rebuilds the resource section of a PE image from `resources`, leaving the other
+/// sections as they are. When the old resource directory fills a section of its
+/// own, the new one is written over it if it fits there or the section is last;
+/// otherwise it is added as a new last section and the old data is left in place.
+/// Data appended past the sections is kept after them. An Authenticode signature no
+/// longer matches the image, so it is removed, and the header checksum is recomputed.
+pub fn replace_pe_resources(
+    image: &[u8],
+    resources: &ResourceTable,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let headers = PeHeaders::parse(image)?;
+    let sections = headers.sections(image);
+    let sections_end = sections
+        .iter()
+        .map(|s| (s.raw_offset + s.raw_size) as usize)
+        .max()
+        .unwrap_or(headers.size_of_headers as usize)
+        .min(image.len());
+
+    // The certificate table sits past the sections, addressed by file offset
+    let (cert_offset, cert_size) = headers.data_directory(image, IMAGE_DIRECTORY_ENTRY_SECURITY);
+    let mut overlay = image[sections_end..].to_vec();
+    if cert_size != 0 && cert_offset as usize >= sections_end {
+        let start = cert_offset as usize - sections_end;
+        let end = (start + cert_size as usize).min(overlay.len());
+        if start < end {
+            overlay.drain(start..end);
+        }
+    }
+
+    // Only a section of nothing but resources may be overwritten; linkers can merge
+    // `.rsrc` into a section that holds other data
+    let (resource_rva, resource_size) =
+        headers.data_directory(image, IMAGE_DIRECTORY_ENTRY_RESOURCE);
+    let current = (resource_rva != 0)
+        .then(|| {
+            sections.iter().position(|s| {
+                let used = if s.virtual_size != 0 {
+                    s.virtual_size
+                } else {
+                    s.raw_size
+                };
+                resource_rva == s.virtual_address && resource_size >= used
+            })
+        })
+        .flatten();
+    let last_by_address = sections
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, s)| s.virtual_address)
+        .map(|(i, _)| i);
+    let last_by_offset = sections
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, s)| s.raw_offset + s.raw_size)
+        .map(|(i, _)| i);
+
+    let mut output = image[..sections_end].to_vec();
+    let (index, rva) = match current {
+        // The old section is last: rewrite it at whatever size
+        Some(i) if Some(i) == last_by_address && Some(i) == last_by_offset => {
+            let section = &sections[i];
+            let data = build_rsrc(&resources.entries, section.virtual_address);
+            output.truncate(section.raw_offset as usize);
+            write_section_data(&mut output, &data, headers.file_alignment);
+            headers.set_section(&mut output, i, section.virtual_address, &data);
+            (i, section.virtual_address)
+        }
+        // The new data fits in the old section's raw data and address range
+        Some(i) if fits_in_place(&sections, i, resources) => {
+            let section = &sections[i];
+            let data = build_rsrc(&resources.entries, section.virtual_address);
+            let start = section.raw_offset as usize;
+            let end = start + section.raw_size as usize;
+            output[start..end].fill(0);
+            output[start..start + data.len()].copy_from_slice(&data);
+            let virtual_size = (data.len() as u32).max(1);
+            write_u32(&mut output, headers.section_header(i) + 8, virtual_size);
+            (i, section.virtual_address)
+        }
+        _ => {
+            let index = sections.len();
+            let header = headers.section_header(index);
+            let first_raw = sections
+                .iter()
+                .filter(|s| s.raw_size != 0)
+                .map(|s| s.raw_offset as usize)
+                .min()
+                .unwrap_or(headers.size_of_headers as usize);
+            if header + SECTION_HEADER_SIZE > (headers.size_of_headers as usize).min(first_raw) {
+                return Err(invalid_data(
+                    "no room in the headers for a resource section",
+                ));
+            }
+            let rva = align(
+                sections
+                    .iter()
+                    .map(|s| s.virtual_address + s.virtual_size.max(s.raw_size))
+                    .max()
+                    .unwrap_or(headers.size_of_headers),
+                headers.section_alignment,
+            );
+            let data = build_rsrc(&resources.entries, rva);
+            let raw_offset = align(output.len() as u32, headers.file_alignment);
+            output.resize(raw_offset as usize, 0);
+            output[header..header + SECTION_HEADER_SIZE].fill(0);
+            output[header..header + 8].copy_from_slice(b".rsrc\0\0\0");
+            write_u32(&mut output, header + 12, rva);
+            write_u32(&mut output, header + 20, raw_offset);
+            write_u32(&mut output, header + 36, RSRC_CHARACTERISTICS);
+            write_section_data(&mut output, &data, headers.file_alignment);
+            headers.set_section(&mut output, index, rva, &data);
+            write_u16(&mut output, headers.coff + 2, (sections.len() + 1) as u16);
+            (index, rva)
+        }
+    };
+    output.extend_from_slice(&overlay);
+
+    let size = read_u32(&output, headers.section_header(index) + 8).unwrap_or_default();
+    headers.set_data_directory(&mut output, IMAGE_DIRECTORY_ENTRY_RESOURCE, rva, size);
+    headers.set_data_directory(&mut output, IMAGE_DIRECTORY_ENTRY_SECURITY, 0, 0);
+    let sections = headers.sections(&output);
+    let image_end = sections
+        .iter()
+        .map(|s| s.virtual_address + s.virtual_size.max(s.raw_size))
+        .max()
+        .unwrap_or(headers.size_of_headers);
+    write_u32(
+        &mut output,
+        headers.optional + 56,
+        align(image_end, headers.section_alignment),
+    );
+    let checksum = pe_checksum(&output, headers.optional + 64);
+    write_u32(&mut output, headers.optional + 64, checksum);
+    Ok(output)
+}
+
+/// A resource name and its languages.
+type NameNode<'a> = (&'a ResourceId, Vec<&'a ResourceEntry>);
+/// A resource type and its names.
+type TypeNode<'a> = (&'a ResourceId, Vec<NameNode<'a>>);
+
+/// Lays out a resource section for the address `rva`: the type, name and language
+/// directories, the data entries, the names, then the data, 8-byte aligned. Named
+/// entries come first, sorted case-insensitively, then IDs in ascending order, as
+/// the loader's binary search expects.
+pub(crate) fn build_rsrc(entries: &[ResourceEntry], rva: u32) -> Vec<u8> {
+    let mut sorted: Vec<&ResourceEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| {
+        compare_ids(&a.type_id, &b.type_id)
+            .then_with(|| compare_ids(&a.name, &b.name))
+            .then(a.language.cmp(&b.language))
+    });
+    sorted.dedup_by(|a, b| {
+        a.type_id.matches(&b.type_id) && a.name.matches(&b.name) && a.language == b.language
+    });
+
+    // Group into types, then names, keeping each one's entries
+    let mut types: Vec<TypeNode> = Vec::new();
+    for entry in sorted {
+        match types.last_mut() {
+            Some((type_id, names)) if type_id.matches(&entry.type_id) => match names.last_mut() {
+                Some((name, languages)) if name.matches(&entry.name) => languages.push(entry),
+                _ => names.push((&entry.name, vec![entry])),
+            },
+            _ => types.push((&entry.type_id, vec![(&entry.name, vec![entry])])),
+        }
+    }
+
+    let directory_size = |count: usize| DIRECTORY_SIZE + count * DIRECTORY_ENTRY_SIZE;
+    let mut offset = directory_size(types.len());
+    let mut name_directories = Vec::new();
+    for (_, names) in &types {
+        name_directories.push(offset);
+        offset += directory_size(names.len());
+    }
+    let mut language_directories = Vec::new();
+    for (_, names) in &types {
+        for (_, languages) in names {
+            language_directories.push(offset);
+            offset += directory_size(languages.len());
+        }
+    }
+    let data_entries = offset;
+    let leaf_count: usize = types
+        .iter()
+        .flat_map(|(_, names)| names.iter().map(|(_, languages)| languages.len()))
+        .sum();
+    offset += leaf_count * DATA_ENTRY_SIZE;
+
+    // Names are stored once, however many directories use them
+    let mut strings = Vec::new();
+    let mut string_offsets: Vec<(&str, usize)> = Vec::new();
+    let ids = types.iter().flat_map(|(type_id, names)| {
+        std::iter::once(*type_id).chain(names.iter().map(|(name, _)| *name))
+    });
+    for id in ids {
+        if let ResourceId::Name(name) = id
+            && !string_offsets.iter().any(|(known, _)| known == name)
+        {
+            string_offsets.push((name, offset + strings.len()));
+            let units: Vec<u16> = name.encode_utf16().collect();
+            strings.extend_from_slice(&(units.len() as u16).to_le_bytes());
+            for unit in units {
+                strings.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+    }
+    let data_start = align((offset + strings.len()) as u32, 8) as usize;
+
+    let mut section = vec![0u8; data_start];
+    section[offset..offset + strings.len()].copy_from_slice(&strings);
+    let id_field = |id: &ResourceId| match id {
+        ResourceId::Id(id) => u32::from(*id),
+        ResourceId::Name(name) => {
+            let (_, offset) = string_offsets
+                .iter()
+                .find(|(known, _)| known == name)
+                .copied()
+                .unwrap_or_default();
+            0x8000_0000 | offset as u32
+        }
+    };
+
+    write_directory(&mut section, 0, &types);
+    let mut leaf = 0;
+    let mut language_directory = language_directories.iter();
+    for ((type_id, names), &name_directory) in types.iter().zip(&name_directories) {
+        let type_entry = type_entry_offset(&types, type_id);
+        write_u32(&mut section, type_entry, id_field(type_id));
+        write_u32(
+            &mut section,
+            type_entry + 4,
+            0x8000_0000 | name_directory as u32,
+        );
+        write_directory(&mut section, name_directory, names);
+
+        for (i, (name, languages)) in names.iter().enumerate() {
+            let &language_directory = language_directory.next().unwrap_or(&0);
+            let name_entry = name_directory + DIRECTORY_SIZE + i * DIRECTORY_ENTRY_SIZE;
+            write_u32(&mut section, name_entry, id_field(name));
+            write_u32(
+                &mut section,
+                name_entry + 4,
+                0x8000_0000 | language_directory as u32,
+            );
+            write_u16(
+                &mut section,
+                language_directory + 14,
+                languages.len() as u16,
+            );
+
+            for (j, entry) in languages.iter().enumerate() {
+                let language_entry = language_directory + DIRECTORY_SIZE + j * DIRECTORY_ENTRY_SIZE;
+                let data_entry = data_entries + leaf * DATA_ENTRY_SIZE;
+                write_u32(&mut section, language_entry, u32::from(entry.language));
+                write_u32(&mut section, language_entry + 4, data_entry as u32);
+
+                let data_offset = section.len();
+                section.extend_from_slice(&entry.data);
+                section.resize(align(section.len() as u32, 8) as usize, 0);
+                write_u32(&mut section, data_entry, rva + data_offset as u32);
+                write_u32(&mut section, data_entry + 4, entry.data.len() as u32);
+                leaf += 1;
+            }
+        }
+    }
+    section
+}
+
+/// The standard PE checksum: a 16-bit one's complement sum of the file, skipping
+/// the checksum field, plus the file length.
+pub(crate) fn pe_checksum(data: &[u8], checksum_offset: usize) -> u32 {
+    let mut sum: u64 = 0;
+    for (i, chunk) in data.chunks(2).enumerate() {
+        if i * 2 == checksum_offset || i * 2 == checksum_offset + 2 {
+            continue;
+        }
+        let word = u16::from_le_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]);
+        sum += u64::from(word);
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum = (sum & 0xFFFF) + (sum >> 16);
+    (sum as u32).wrapping_add(data.len() as u32)
+}
+
+/// The header fields of a PE image that rewriting a section touches.
+struct PeHeaders {
+    coff: usize,
+    optional: usize,
+    data_directories: usize,
+    directory_count: u32,
+    section_table: usize,
+    section_alignment: u32,
+    file_alignment: u32,
+    size_of_headers: u32,
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+impl PeHeaders {
+    fn parse(image: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if image.get(..2) != Some(b"MZ".as_slice()) {
+            return Err(invalid_data("not an MZ executable"));
+        }
+        let pe_offset = read_u32(image, 0x3C).ok_or_else(|| truncated("DOS header"))? as usize;
+        if image.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0".as_slice()) {
+            return Err(invalid_data("only PE images can be rewritten"));
+        }
+        let coff = pe_offset + 4;
+        let optional_size =
+            usize::from(read_u16(image, coff + 16).ok_or_else(|| truncated("COFF header"))?);
+        let optional = coff + 20;
+        let (data_directories, directory_count) = match read_u16(image, optional) {
+            Some(0x10B) => (optional + 96, read_u32(image, optional + 92)),
+            Some(0x20B) => (optional + 112, read_u32(image, optional + 108)),
+            _ => return Err(invalid_data("unknown optional header magic")),
+        };
+        let field =
+            |offset| read_u32(image, optional + offset).ok_or_else(|| truncated("optional header"));
+        let headers = PeHeaders {
+            coff,
+            optional,
+            data_directories,
+            directory_count: directory_count.ok_or_else(|| truncated("optional header"))?,
+            section_table: optional + optional_size,
+            section_alignment: field(32)?.max(1),
+            file_alignment: field(36)?.max(1),
+            size_of_headers: field(60)?,
+        };
+        if headers.directory_count as usize <= IMAGE_DIRECTORY_ENTRY_SECURITY {
+            return Err(invalid_data("PE image has too few data directories"));
+        }
+        let section_count = usize::from(read_u16(image, coff + 2).unwrap_or_default());
+        if headers.section_header(section_count) > image.len() {
+            return Err(truncated("section table"));
+        }
+        Ok(headers)
+    }
+
+    fn section_header(&self, index: usize) -> usize {
+        self.section_table + index * SECTION_HEADER_SIZE
+    }
+
+    fn sections(&self, image: &[u8]) -> Vec<Section> {
+        let count = usize::from(read_u16(image, self.coff + 2).unwrap_or_default());
+        (0..count)
+            .filter_map(|i| {
+                let header = self.section_header(i);
+                Some(Section {
+                    virtual_size: read_u32(image, header + 8)?,
+                    virtual_address: read_u32(image, header + 12)?,
+                    raw_size: read_u32(image, header + 16)?,
+                    raw_offset: read_u32(image, header + 20)?,
+                })
+            })
+            .collect()
+    }
+
+    fn data_directory(&self, image: &[u8], index: usize) -> (u32, u32) {
+        let entry = self.data_directories + index * 8;
+        (
+            read_u32(image, entry).unwrap_or_default(),
+            read_u32(image, entry + 4).unwrap_or_default(),
+        )
+    }
+
+    fn set_data_directory(&self, image: &mut [u8], index: usize, rva: u32, size: u32) {
+        let entry = self.data_directories + index * 8;
+        write_u32(image, entry, rva);
+        write_u32(image, entry + 4, size);
+    }
+
+    /// Points section `index` at `data`, written at its raw offset and padded to the
+    /// file alignment.
+    fn set_section(&self, image: &mut [u8], index: usize, rva: u32, data: &[u8]) {
+        let header = self.section_header(index);
+        write_u32(image, header + 8, data.len() as u32);
+        write_u32(image, header + 12, rva);
+        write_u32(
+            image,
+            header + 16,
+            align(data.len() as u32, self.file_alignment),
+        );
+    }
+}
+
+fn fits_in_place(sections: &[Section], index: usize, resources: &ResourceTable) -> bool {
+    let section = &sections[index];
+    // The layout does not depend on the address, so any address gives the size
+    let size = build_rsrc(&resources.entries, section.virtual_address).len() as u32;
+    let next_address = sections
+        .iter()
+        .map(|s| s.virtual_address)
+        .filter(|&address| address > section.virtual_address)
+        .min()
+        .unwrap_or(u32::MAX);
+    size <= section.raw_size && section.virtual_address + size <= next_address
+}
+
+fn write_section_data(image: &mut Vec<u8>, data: &[u8], file_alignment: u32) {
+    image.extend_from_slice(data);
+    image.resize(align(image.len() as u32, file_alignment) as usize, 0);
+}
+
+fn write_directory<T>(section: &mut [u8], offset: usize, children: &[(&ResourceId, T)]) {
+    let named = children
+        .iter()
+        .filter(|(id, _)| matches!(id, ResourceId::Name(_)))
+        .count();
+    write_u16(section, offset + 12, named as u16);
+    write_u16(section, offset + 14, (children.len() - named) as u16);
+}
+
+fn type_entry_offset<T>(types: &[(&ResourceId, T)], type_id: &ResourceId) -> usize {
+    let index = types
+        .iter()
+        .position(|(id, _)| id.matches(type_id))
+        .unwrap_or_default();
+    DIRECTORY_SIZE + index * DIRECTORY_ENTRY_SIZE
+}
+
+/// Names sort before IDs; names compare by their upper-case UTF-16 units.
+pub(crate) fn compare_ids(a: &ResourceId, b: &ResourceId) -> Ordering {
+    match (a, b) {
+        (ResourceId::Name(a), ResourceId::Name(b)) => a
+            .to_uppercase()
+            .encode_utf16()
+            .cmp(b.to_uppercase().encode_utf16()),
+        (ResourceId::Name(_), ResourceId::Id(_)) => Ordering::Less,
+        (ResourceId::Id(_), ResourceId::Name(_)) => Ordering::Greater,
+        (ResourceId::Id(a), ResourceId::Id(b)) => a.cmp(b),
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::IconLibrary;
+
+    fn group(size: u32) -> IconGroup {
+        IconGroup::from_images(&[RgbaImage::from_pixel(
+            size,
+            size,
+            image::Rgba([200, 0, 0, 255]),
+        )])
+        .unwrap()
+    }
+
+    fn library() -> Vec<u8> {
+        IconLibrary::new()
+            .with_icon(1, group(16))
+            .with_icon(2, group(32))
+            .to_dll_bytes()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_replaced_icon_groups() {
+        let image = library();
+        let output = replace_icon_group(&image, &ResourceId::Id(1), &group(48)).unwrap();
+
+        let pe = PeFile::from_bytes(output.clone()).unwrap();
+        assert_eq!(pe.icon_group(&ResourceId::Id(1)).unwrap(), group(48));
+        assert_eq!(pe.icon_group(&ResourceId::Id(2)).unwrap(), group(32));
+
+        // The resource section was last, so it is rewritten rather than added to
+        let headers = PeHeaders::parse(&output).unwrap();
+        assert_eq!(headers.sections(&output).len(), 1);
+        let checksum = headers.optional + 64;
+        assert_eq!(
+            read_u32(&output, checksum),
+            Some(pe_checksum(&output, checksum))
+        );
+
+        let again = replace_icon_group_at(&output, 0, &group(16)).unwrap();
+        let pe = PeFile::from_bytes(again).unwrap();
+        assert_eq!(pe.icon_group_at(0).unwrap(), group(16));
+    }
+
+    #[test]
+    fn keeps_sections_shared_with_other_data() {
+        let image = library();
+        let headers = PeHeaders::parse(&image).unwrap();
+        let section = headers.section_header(0);
+        let resources = {
+            let mut table = ResourceTable::from_pe(&PeFile::from_bytes(image.clone()).unwrap());
+            table
+                .replace_icon_group(&ResourceId::Id(1), &group(48))
+                .unwrap();
+            table
+        };
+
+        // Data of its own after the resources, in a grown section
+        let mut trailing = image.clone();
+        trailing.extend_from_slice(&[0xAB; 0x200]);
+        let raw_size = read_u32(&trailing, section + 16).unwrap() + 0x200;
+        write_u32(&mut trailing, section + 8, raw_size);
+        write_u32(&mut trailing, section + 16, raw_size);
+
+        // Resources that start past the beginning of the section
+        let mut inner = image.clone();
+        let directory = headers.data_directories + IMAGE_DIRECTORY_ENTRY_RESOURCE * 8;
+        let rva = read_u32(&inner, directory).unwrap();
+        write_u32(&mut inner, directory, rva + 0x10);
+
+        for image in [trailing, inner] {
+            let output = replace_pe_resources(&image, &resources).unwrap();
+            let sections = headers.sections(&output);
+            assert_eq!(sections.len(), 2);
+
+            let old = &sections[0];
+            let raw = old.raw_offset as usize..(old.raw_offset + old.raw_size) as usize;
+            assert_eq!(output[raw.clone()], image[raw]);
+            assert_eq!(
+                headers
+                    .data_directory(&output, IMAGE_DIRECTORY_ENTRY_RESOURCE)
+                    .0,
+                sections[1].virtual_address
+            );
+            let pe = PeFile::from_bytes(output).unwrap();
+            assert_eq!(pe.icon_group(&ResourceId::Id(1)).unwrap(), group(48));
+        }
+    }
+}
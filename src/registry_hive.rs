@@ -0,0 +1,447 @@
+use std::{error::Error, fmt, fs, path::Path};
+
+use crate::utils::binary_utils::{
+    decode_utf16le, invalid_data, read_i32, read_u16, read_u32, truncated,
+};
+
+const BASE_BLOCK_SIZE: usize = 0x1000;
+const KEY_COMP_NAME: u16 = 0x0020;
+const VALUE_COMP_NAME: u16 = 0x0001;
+const DATA_IS_RESIDENT: u32 = 0x8000_0000;
+const BIG_DATA_SEGMENT_SIZE: usize = 16344;
+const MAX_LIST_DEPTH: usize = 8;
+
+pub const REG_SZ: u32 = 1;
+pub const REG_EXPAND_SZ: u32 = 2;
+pub const REG_BINARY: u32 = 3;
+pub const REG_DWORD: u32 = 4;
+pub const REG_MULTI_SZ: u32 = 7;
+
+/// Read access to a registry tree, shared by `regf` hives and Wine `.reg` files.
+/// Key paths are backslash-separated and relative to the tree root; lookups ignore case.
+pub trait RegistrySource: fmt::Debug + Send + Sync {
+    fn values(&self, key_path: &str) -> Option<Vec<RegistryValue>>;
+
+    fn value(&self, key_path: &str, name: &str) -> Option<RegistryValue> {
+        self.values(key_path)?
+            .into_iter()
+            .find(|value| value.name.eq_ignore_ascii_case(name))
+    }
+
+    fn string_value(&self, key_path: &str, name: &str) -> Option<String> {
+        self.value(key_path, name)?.as_string()
+    }
+}
+
+/// An offline registry hive file in the `regf` format, such as `SOFTWARE`,
+/// `NTUSER.DAT` or `UsrClass.dat`.
+#[derive(Clone, Debug)]
+pub struct RegistryHive {
+    data: Vec<u8>,
+    root_offset: u32,
+    minor_version: u32,
+}
+
+impl RegistryHive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(fs::read(path.as_ref())?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        if data.get(..4) != Some(b"regf".as_slice()) {
+            return Err(invalid_data("not a registry hive file"));
+        }
+        let minor_version = read_u32(&data, 24).ok_or_else(|| truncated("hive base block"))?;
+        let root_offset = read_u32(&data, 36).ok_or_else(|| truncated("hive base block"))?;
+
+        let hive = RegistryHive {
+            data,
+            root_offset,
+            minor_version,
+        };
+        if hive.cell_signature(root_offset) != Some(*b"nk") {
+            return Err(invalid_data("hive root cell is not a key node"));
+        }
+        Ok(hive)
+    }
+
+    pub fn root(&self) -> RegistryKey<'_> {
+        RegistryKey {
+            hive: self,
+            offset: self.root_offset,
+        }
+    }
+
+    /// Opens a key by its backslash-separated path below the hive root, ignoring case.
+    pub fn open_key(&self, path: &str) -> Option<RegistryKey<'_>> {
+        path.split('\\')
+            .filter(|part| !part.is_empty())
+            .try_fold(self.root(), |key, name| key.subkey(name))
+    }
+
+    fn cell(&self, offset: u32) -> Option<&[u8]> {
+        let start = BASE_BLOCK_SIZE.checked_add(offset as usize)?;
+        let size = read_i32(&self.data, start)?.unsigned_abs() as usize;
+        self.data.get(start + 4..start.checked_add(size)?)
+    }
+
+    fn cell_signature(&self, offset: u32) -> Option<[u8; 2]> {
+        self.cell(offset)?.get(..2)?.try_into().ok()
+    }
+
+    fn collect_subkeys(&self, list_offset: u32, depth: usize, keys: &mut Vec<u32>) {
+        let Some(list) = self.cell(list_offset) else {
+            return;
+        };
+        let count = usize::from(read_u16(list, 2).unwrap_or_default());
+
+        match list.get(..2).unwrap_or_default() {
+            b"lf" | b"lh" => keys.extend((0..count).filter_map(|i| read_u32(list, 4 + i * 8))),
+            b"li" => keys.extend((0..count).filter_map(|i| read_u32(list, 4 + i * 4))),
+            b"ri" if depth < MAX_LIST_DEPTH => {
+                for offset in (0..count).filter_map(|i| read_u32(list, 4 + i * 4)) {
+                    self.collect_subkeys(offset, depth + 1, keys);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn value_data(&self, size: u32, offset: u32) -> Option<Vec<u8>> {
+        if size & DATA_IS_RESIDENT != 0 {
+            // Up to four bytes are stored in the offset field itself
+            let len = (size & !DATA_IS_RESIDENT) as usize;
+            return Some(offset.to_le_bytes().get(..len.min(4))?.to_vec());
+        }
+
+        let size = size as usize;
+        let cell = self.cell(offset)?;
+        if size > BIG_DATA_SEGMENT_SIZE && self.minor_version >= 4 && cell.get(..2) == Some(b"db") {
+            let count = usize::from(read_u16(cell, 2)?);
+            let segments = self.cell(read_u32(cell, 4)?)?;
+            let mut data = Vec::with_capacity(size);
+            for i in 0..count {
+                let segment = self.cell(read_u32(segments, i * 4)?)?;
+                data.extend_from_slice(&segment[..segment.len().min(BIG_DATA_SEGMENT_SIZE)]);
+            }
+            data.truncate(size);
+            return Some(data);
+        }
+
+        cell.get(..size).map(<[u8]>::to_vec)
+    }
+}
+
+impl RegistrySource for RegistryHive {
+    fn values(&self, key_path: &str) -> Option<Vec<RegistryValue>> {
+        Some(self.open_key(key_path)?.values())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RegistryKey<'a> {
+    hive: &'a RegistryHive,
+    offset: u32,
+}
+
+impl<'a> RegistryKey<'a> {
+    fn node(&self) -> &'a [u8] {
+        self.hive.cell(self.offset).unwrap_or_default()
+    }
+
+    pub fn name(&self) -> String {
+        let node = self.node();
+        let flags = read_u16(node, 2).unwrap_or_default();
+        let len = usize::from(read_u16(node, 72).unwrap_or_default());
+        let name = node.get(76..76 + len).unwrap_or_default();
+        decode_name(name, flags & KEY_COMP_NAME != 0)
+    }
+
+    pub fn subkeys(&self) -> Vec<RegistryKey<'a>> {
+        let node = self.node();
+        let mut offsets = Vec::new();
+        if read_u32(node, 20).unwrap_or_default() > 0
+            && let Some(list_offset) = read_u32(node, 28)
+        {
+            self.hive.collect_subkeys(list_offset, 0, &mut offsets);
+        }
+
+        offsets
+            .into_iter()
+            .filter(|&offset| self.hive.cell_signature(offset) == Some(*b"nk"))
+            .map(|offset| RegistryKey {
+                hive: self.hive,
+                offset,
+            })
+            .collect()
+    }
+
+    pub fn subkey(&self, name: &str) -> Option<RegistryKey<'a>> {
+        self.subkeys()
+            .into_iter()
+            .find(|key| key.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn values(&self) -> Vec<RegistryValue> {
+        let node = self.node();
+        let count = read_u32(node, 36).unwrap_or_default() as usize;
+        let list = read_u32(node, 40)
+            .and_then(|offset| self.hive.cell(offset))
+            .unwrap_or_default();
+
+        list.chunks_exact(4)
+            .take(count)
+            .filter_map(|offset| self.read_value(read_u32(offset, 0)?))
+            .collect()
+    }
+
+    /// Looks a value up by name, ignoring case. An empty name is the default value.
+    pub fn value(&self, name: &str) -> Option<RegistryValue> {
+        self.values()
+            .into_iter()
+            .find(|value| value.name.eq_ignore_ascii_case(name))
+    }
+
+    fn read_value(&self, offset: u32) -> Option<RegistryValue> {
+        let vk = self
+            .hive
+            .cell(offset)
+            .filter(|c| c.get(..2) == Some(b"vk"))?;
+        let name_len = usize::from(read_u16(vk, 2)?);
+        let flags = read_u16(vk, 16)?;
+        let name = decode_name(vk.get(20..20 + name_len)?, flags & VALUE_COMP_NAME != 0);
+
+        Some(RegistryValue {
+            name,
+            value_type: read_u32(vk, 12)?,
+            data: self.hive.value_data(read_u32(vk, 4)?, read_u32(vk, 8)?)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryValue {
+    pub name: String,
+    pub value_type: u32,
+    pub data: Vec<u8>,
+}
+
+impl RegistryValue {
+    /// Decodes `REG_SZ`, `REG_EXPAND_SZ` and the first string of `REG_MULTI_SZ`.
+    /// Expandable strings are returned unexpanded.
+    pub fn as_string(&self) -> Option<String> {
+        match self.value_type {
+            REG_SZ | REG_EXPAND_SZ | REG_MULTI_SZ => {
+                let text = decode_utf16le(&self.data);
+                Some(text.split('\0').next().unwrap_or_default().to_owned())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.value_type {
+            REG_DWORD => read_u32(&self.data, 0),
+            _ => None,
+        }
+    }
+}
+
+fn decode_name(name: &[u8], is_compressed: bool) -> String {
+    if is_compressed {
+        name.iter().map(|&b| char::from(b)).collect()
+    } else {
+        decode_utf16le(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a hive by appending cells after an `hbin` header; offsets are relative to
+    /// the first bin, as in real hives.
+    struct HiveBuilder {
+        bins: Vec<u8>,
+    }
+
+    impl HiveBuilder {
+        fn new() -> Self {
+            let mut bins = b"hbin".to_vec();
+            bins.resize(32, 0);
+            HiveBuilder { bins }
+        }
+
+        fn cell(&mut self, payload: &[u8]) -> u32 {
+            let offset = self.bins.len() as u32;
+            let size = (4 + payload.len()).next_multiple_of(8);
+            self.bins.extend_from_slice(&(-(size as i32)).to_le_bytes());
+            self.bins.extend_from_slice(payload);
+            self.bins.resize(offset as usize + size, 0);
+            offset
+        }
+
+        fn key(&mut self, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+            let list = (!subkeys.is_empty()).then(|| (self.list(b"lf", subkeys), subkeys.len()));
+            self.key_with_list(name, list, values)
+        }
+
+        fn key_with_list(&mut self, name: &str, list: Option<(u32, usize)>, values: &[u32]) -> u32 {
+            let mut nk = vec![0u8; 76];
+            nk[..2].copy_from_slice(b"nk");
+            nk[2..4].copy_from_slice(&KEY_COMP_NAME.to_le_bytes());
+            if let Some((list, count)) = list {
+                nk[20..24].copy_from_slice(&(count as u32).to_le_bytes());
+                nk[28..32].copy_from_slice(&list.to_le_bytes());
+            }
+            if !values.is_empty() {
+                let list: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                nk[36..40].copy_from_slice(&(values.len() as u32).to_le_bytes());
+                nk[40..44].copy_from_slice(&self.cell(&list).to_le_bytes());
+            }
+            nk[72..74].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            nk.extend_from_slice(name.as_bytes());
+            self.cell(&nk)
+        }
+
+        fn list(&mut self, signature: &[u8; 2], offsets: &[u32]) -> u32 {
+            let mut list = signature.to_vec();
+            list.extend_from_slice(&(offsets.len() as u16).to_le_bytes());
+            for offset in offsets {
+                list.extend_from_slice(&offset.to_le_bytes());
+                if signature != b"li" && signature != b"ri" {
+                    list.extend_from_slice(&[0; 4]);
+                }
+            }
+            self.cell(&list)
+        }
+
+        /// A value with a UTF-16 name; data of up to four bytes is stored resident.
+        fn value(&mut self, name: &str, value_type: u32, data: &[u8]) -> u32 {
+            let name: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            let (size, offset) = if data.len() <= 4 {
+                let mut resident = [0u8; 4];
+                resident[..data.len()].copy_from_slice(data);
+                (
+                    data.len() as u32 | DATA_IS_RESIDENT,
+                    u32::from_le_bytes(resident),
+                )
+            } else {
+                (data.len() as u32, self.cell(data))
+            };
+            let mut vk = b"vk".to_vec();
+            vk.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            vk.extend_from_slice(&size.to_le_bytes());
+            vk.extend_from_slice(&offset.to_le_bytes());
+            vk.extend_from_slice(&value_type.to_le_bytes());
+            vk.extend_from_slice(&[0; 4]);
+            vk.extend_from_slice(&name);
+            self.cell(&vk)
+        }
+
+        fn finish(self, root: u32) -> Vec<u8> {
+            let mut data = b"regf".to_vec();
+            data.resize(BASE_BLOCK_SIZE, 0);
+            data[24..28].copy_from_slice(&5u32.to_le_bytes());
+            data[36..40].copy_from_slice(&root.to_le_bytes());
+            data.extend_from_slice(&self.bins);
+            data
+        }
+    }
+
+    fn utf16z(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain(Some(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn reads_keys_and_values_ignoring_case() {
+        let mut hive = HiveBuilder::new();
+        let default = hive.value("", REG_SZ, &utf16z("txtfile"));
+        let flags = hive.value("EditFlags", REG_DWORD, &0x20u32.to_le_bytes());
+        let txt = hive.key(".txt", &[], &[default, flags]);
+        let classes = hive.key("Classes", &[txt], &[]);
+        let root = hive.key("ROOT", &[classes], &[]);
+        let hive = RegistryHive::from_bytes(hive.finish(root)).unwrap();
+
+        let key = hive.open_key("classes\\.TXT").unwrap();
+        assert_eq!(key.name(), ".txt");
+        assert_eq!(
+            key.value("").and_then(|v| v.as_string()).as_deref(),
+            Some("txtfile")
+        );
+        assert_eq!(key.value("editflags").and_then(|v| v.as_u32()), Some(0x20));
+        assert_eq!(
+            hive.string_value("Classes\\.txt", "").as_deref(),
+            Some("txtfile")
+        );
+        assert!(hive.open_key("Classes\\.doc").is_none());
+    }
+
+    #[test]
+    fn stops_at_the_end_of_value_lists() {
+        let mut hive = HiveBuilder::new();
+        let value = hive.value("Name", REG_DWORD, &[1, 0, 0, 0]);
+        let root = hive.key("ROOT", &[], &[value]);
+        // A value count far past the list's one entry
+        let count = root as usize + 4 + 36;
+        hive.bins[count..count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let hive = RegistryHive::from_bytes(hive.finish(root)).unwrap();
+
+        assert_eq!(hive.root().values().len(), 1);
+    }
+
+    #[test]
+    fn follows_index_roots() {
+        let mut hive = HiveBuilder::new();
+        let a = hive.key("a", &[], &[]);
+        let b = hive.key("b", &[], &[]);
+        let first = hive.list(b"li", &[a]);
+        let second = hive.list(b"lh", &[b]);
+        let index = hive.list(b"ri", &[first, second]);
+        let root = hive.key_with_list("ROOT", Some((index, 2)), &[]);
+        let hive = RegistryHive::from_bytes(hive.finish(root)).unwrap();
+
+        let names: Vec<String> = hive.root().subkeys().iter().map(|k| k.name()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn joins_big_data_segments() {
+        let data: Vec<u8> = (0..BIG_DATA_SEGMENT_SIZE + 100).map(|i| i as u8).collect();
+        let mut hive = HiveBuilder::new();
+        let first = hive.cell(&data[..BIG_DATA_SEGMENT_SIZE]);
+        let second = hive.cell(&data[BIG_DATA_SEGMENT_SIZE..]);
+        let segments: Vec<u8> = [first, second]
+            .iter()
+            .flat_map(|o| o.to_le_bytes())
+            .collect();
+        let segments = hive.cell(&segments);
+        let mut db = b"db".to_vec();
+        db.extend_from_slice(&2u16.to_le_bytes());
+        db.extend_from_slice(&segments.to_le_bytes());
+        let db = hive.cell(&db);
+
+        let value = hive.value("Blob", REG_BINARY, &[0; 8]);
+        // Repoint the value at the big data cell
+        let vk = value as usize + 4;
+        hive.bins[vk + 4..vk + 8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        hive.bins[vk + 8..vk + 12].copy_from_slice(&db.to_le_bytes());
+        let root = hive.key("ROOT", &[], &[value]);
+        let hive = RegistryHive::from_bytes(hive.finish(root)).unwrap();
+
+        assert_eq!(hive.root().value("blob").unwrap().data, data);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(RegistryHive::from_bytes(b"REGEDIT4\r\n".to_vec()).is_err());
+        assert!(RegistryHive::from_bytes(b"regf".to_vec()).is_err());
+
+        let mut hive = HiveBuilder::new();
+        let value = hive.value("x", REG_SZ, &utf16z("not a key"));
+        assert!(RegistryHive::from_bytes(hive.finish(value)).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+
+use crate::{
+    Resource, ResourceEntry, ResourceId,
+    utils::binary_utils::{invalid_data, read_u16, read_u32, read_utf16z, truncated},
+};
+
+/// The empty entry `.res` files start with, which tells them from 16-bit ones.
+const NULL_ENTRY: [u8; 32] = [
+    0, 0, 0, 0, 0x20, 0, 0, 0, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+];
+/// `MOVEABLE | PURE | DISCARDABLE`, which resource compilers write for most types.
+const MEMORY_FLAGS: u16 = 0x1030;
+/// The fields after the type and name: data version, memory flags, language, version
+/// and characteristics.
+const HEADER_FIELDS_SIZE: usize = 16;
+
+pub(crate) fn is_res_file(data: &[u8]) -> bool {
+    data.get(..16) == Some(&NULL_ENTRY[..16])
+}
+
+/// Reads the entries of a 32-bit compiled resource file: each a `RESOURCEHEADER`
+/// (sizes, type, name, flags and language) followed by the data, aligned to 32 bits.
+pub(crate) fn read_res_resources(data: &[u8]) -> Result<Vec<Resource>, Box<dyn Error>> {
+    let mut resources = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let data_size = read_u32(data, offset).ok_or_else(|| truncated("resource header"))?;
+        let header_size = read_u32(data, offset + 4).ok_or_else(|| truncated("resource header"))?;
+        let (type_id, name_offset) = read_id(data, offset + 8)?;
+        let (name, fields) = read_id(data, name_offset)?;
+        let fields = align4(fields);
+        let language = read_u16(data, fields + 6).ok_or_else(|| truncated("resource header"))?;
+
+        let start = offset + header_size as usize;
+        if start < fields + HEADER_FIELDS_SIZE {
+            return Err(invalid_data(format!(
+                "resource header at {offset:#x} is shorter than its fields"
+            )));
+        }
+        let end = start + data_size as usize;
+        if end > data.len() {
+            return Err(truncated("resource data"));
+        }
+        // Null entries pad the start of the file and carry nothing
+        if data_size != 0 || type_id != ResourceId::Id(0) {
+            resources.push(Resource {
+                type_id,
+                name,
+                language,
+                code_page: 0,
+                offset: start,
+                size: data_size as usize,
+            });
+        }
+        let next = align4(end);
+        if next <= offset {
+            return Err(invalid_data(format!(
+                "resource entry at {offset:#x} does not advance"
+            )));
+        }
+        offset = next;
+    }
+    Ok(resources)
+}
+
+/// Writes entries as a compiled resource file, after the null entry.
+pub(crate) fn write_res(entries: &[ResourceEntry]) -> Vec<u8> {
+    let mut data = NULL_ENTRY.to_vec();
+    for entry in entries {
+        let mut header = Vec::new();
+        write_id(&mut header, &entry.type_id);
+        write_id(&mut header, &entry.name);
+        // The header starts 8 bytes in, so padding it to 32 bits pads the fields
+        header.resize(align4(header.len()), 0);
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&MEMORY_FLAGS.to_le_bytes());
+        header.extend_from_slice(&entry.language.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+
+        data.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(8 + header.len() as u32).to_le_bytes());
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&entry.data);
+        data.resize(align4(data.len()), 0);
+    }
+    data
+}
+
+/// An ID is `0xFFFF` and the number; a name is a null-terminated UTF-16 string.
+fn read_id(data: &[u8], offset: usize) -> Result<(ResourceId, usize), Box<dyn Error>> {
+    let first = read_u16(data, offset).ok_or_else(|| truncated("resource header"))?;
+    if first == 0xFFFF {
+        let id = read_u16(data, offset + 2).ok_or_else(|| truncated("resource header"))?;
+        return Ok((ResourceId::Id(id), offset + 4));
+    }
+    let name = read_utf16z(data, offset).ok_or_else(|| truncated("resource name"))?;
+    let units = name.encode_utf16().count();
+    Ok((ResourceId::Name(name), offset + (units + 1) * 2))
+}
+
+fn write_id(header: &mut Vec<u8>, id: &ResourceId) {
+    match id {
+        ResourceId::Id(id) => {
+            header.extend_from_slice(&0xFFFFu16.to_le_bytes());
+            header.extend_from_slice(&id.to_le_bytes());
+        }
+        ResourceId::Name(name) => {
+            for unit in name.encode_utf16().chain(Some(0)) {
+                header.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(type_id: ResourceId, name: ResourceId, language: u16, data: &[u8]) -> ResourceEntry {
+        ResourceEntry {
+            type_id,
+            name,
+            language,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let entries = [
+            entry(
+                ResourceId::Id(14),
+                ResourceId::Id(1),
+                0x0409,
+                &[1, 2, 3, 4, 5],
+            ),
+            entry(
+                ResourceId::Name("PNG".to_owned()),
+                ResourceId::Name("LOGO".to_owned()),
+                0x0407,
+                b"png",
+            ),
+            entry(ResourceId::Id(3), ResourceId::Name("A".to_owned()), 0, &[]),
+        ];
+        let data = write_res(&entries);
+        assert!(is_res_file(&data));
+
+        let resources = read_res_resources(&data).unwrap();
+        assert_eq!(resources.len(), entries.len());
+        for (resource, entry) in resources.iter().zip(&entries) {
+            assert_eq!(resource.type_id, entry.type_id);
+            assert_eq!(resource.name, entry.name);
+            assert_eq!(resource.language, entry.language);
+            assert_eq!(resource.offset % 4, 0);
+            assert_eq!(
+                &data[resource.offset..resource.offset + resource.size],
+                entry.data
+            );
+        }
+        assert!(!is_res_file(b"MZ"));
+    }
+
+    #[test]
+    fn rejects_broken_headers() {
+        let data = write_res(&[entry(ResourceId::Id(3), ResourceId::Id(1), 0, &[9; 8])]);
+        let sizes = NULL_ENTRY.len();
+        let with_sizes = |data_size: u32, header_size: u32| {
+            let mut data = data.clone();
+            data[sizes..sizes + 4].copy_from_slice(&data_size.to_le_bytes());
+            data[sizes + 4..sizes + 8].copy_from_slice(&header_size.to_le_bytes());
+            read_res_resources(&data)
+        };
+
+        // Sizes of zero once left the reader on the same entry forever
+        assert!(with_sizes(0, 0).is_err());
+        assert!(with_sizes(8, 8).is_err());
+        assert!(with_sizes(64, 32).is_err());
+        assert!(with_sizes(8, 32).is_ok());
+    }
+}
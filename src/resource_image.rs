@@ -0,0 +1,370 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use image::{ImageFormat, RgbaImage, imageops};
+
+use crate::{
+    PeFile, RT_BITMAP, RT_RCDATA, Resource, ResourceId,
+    utils::binary_utils::{invalid_data, read_i32, read_u16, read_u32, truncated},
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const BITMAPFILEHEADER_SIZE: usize = 14;
+const BITMAPCOREHEADER_SIZE: u32 = 12;
+/// `BITMAPINFOHEADER`, the Adobe extensions of it, `BITMAPV4HEADER` and `BITMAPV5HEADER`.
+const BITMAPINFOHEADER_SIZES: [u32; 5] = [40, 52, 56, 108, 124];
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// An image stored in a resource, as a file in its own format. `RT_BITMAP` resources
+/// hold a DIB without its file header, which is added back to make a `.bmp` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceImage {
+    pub format: ImageFormat,
+    pub data: Vec<u8>,
+}
+
+impl ResourceImage {
+    /// Reads resource `name` of `type_id` in its first language, e.g. an `RT_BITMAP`,
+    /// an `RT_RCDATA` or a custom `PNG` type.
+    pub fn from_pe(
+        pe: &PeFile,
+        type_id: &ResourceId,
+        name: &ResourceId,
+    ) -> Result<Self, Box<dyn Error>> {
+        let resource = pe.find_resource(type_id, name).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("resource not found: {type_id} {name}"),
+            )
+        })?;
+        Self::from_bytes(pe.resource_data(resource))
+    }
+
+    /// Recognizes PNG, JPEG, GIF and BMP files, and bare DIBs.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if let Some(format) = sniff_format(data) {
+            return Ok(ResourceImage {
+                format,
+                data: data.to_vec(),
+            });
+        }
+        Ok(ResourceImage {
+            format: ImageFormat::Bmp,
+            data: dib_to_bmp(data)?,
+        })
+    }
+
+    /// The resources that hold images: `RT_BITMAP`, `RT_RCDATA` and named types, in
+    /// directory order.
+    pub fn find_all(pe: &PeFile) -> Vec<&Resource> {
+        pe.resources()
+            .iter()
+            .filter(|r| match r.type_id {
+                ResourceId::Id(id) => id == RT_BITMAP || id == RT_RCDATA,
+                ResourceId::Name(_) => true,
+            })
+            .filter(|r| Self::from_bytes(pe.resource_data(r)).is_ok())
+            .collect()
+    }
+
+    /// The usual file extension of the format, without the dot.
+    pub fn extension(&self) -> &'static str {
+        self.format
+            .extensions_str()
+            .first()
+            .copied()
+            .unwrap_or("bin")
+    }
+
+    /// Decodes the image. The fourth byte of 32-bit `BI_RGB` bitmaps is used as
+    /// alpha, as image lists do, unless it is zero throughout.
+    pub fn to_image(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let mut image = image::load_from_memory_with_format(&self.data, self.format)?.to_rgba8();
+        if self.format == ImageFormat::Bmp
+            && let Some(alpha) = bmp_alpha(&self.data)
+            && alpha.len() == image.pixels().len()
+        {
+            for (pixel, alpha) in image.pixels_mut().zip(alpha) {
+                pixel.0[3] = alpha;
+            }
+        }
+        Ok(image)
+    }
+
+    /// Slices the image into tiles of `tile_width` by `tile_height`, left to right and
+    /// then top to bottom, like the buttons of a toolbar strip.
+    pub fn to_tiles(
+        &self,
+        tile_width: u32,
+        tile_height: u32,
+    ) -> Result<Vec<RgbaImage>, Box<dyn Error>> {
+        let image = self.to_image()?;
+        let (width, height) = image.dimensions();
+        if tile_width == 0
+            || tile_height == 0
+            || !width.is_multiple_of(tile_width)
+            || !height.is_multiple_of(tile_height)
+        {
+            return Err(invalid_data(format!(
+                "a {width}x{height} image cannot be split into {tile_width}x{tile_height} tiles"
+            )));
+        }
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(tile_height as usize) {
+            for x in (0..width).step_by(tile_width as usize) {
+                tiles.push(imageops::crop_imm(&image, x, y, tile_width, tile_height).to_image());
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// Slices a horizontal or vertical strip into square tiles as wide as the strip
+    /// is thin.
+    pub fn to_square_tiles(&self) -> Result<Vec<RgbaImage>, Box<dyn Error>> {
+        let image = self.to_image()?;
+        let size = image.width().min(image.height());
+        self.to_tiles(size, size)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path.as_ref(), &self.data)?;
+        Ok(())
+    }
+}
+
+fn sniff_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(PNG_SIGNATURE) {
+        Some(ImageFormat::Png)
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if data.starts_with(b"BM")
+        && read_u32(data, 2).is_some_and(|size| size as usize <= data.len())
+    {
+        Some(ImageFormat::Bmp)
+    } else {
+        None
+    }
+}
+
+/// The alpha bytes of a 32-bit `BI_RGB` bitmap file, top row first, if any is set.
+fn bmp_alpha(data: &[u8]) -> Option<Vec<u8>> {
+    let pixels = read_u32(data, 10)? as usize;
+    let dib = data.get(BITMAPFILEHEADER_SIZE..)?;
+    if !BITMAPINFOHEADER_SIZES.contains(&read_u32(dib, 0)?)
+        || read_u16(dib, 14)? != 32
+        || read_u32(dib, 16)? != BI_RGB
+    {
+        return None;
+    }
+    let width = read_i32(dib, 4)?.unsigned_abs() as usize;
+    let height = read_i32(dib, 8)?;
+    let rows = height.unsigned_abs() as usize;
+    let bytes = data.get(pixels..pixels.checked_add(width * rows * 4)?)?;
+
+    let mut alpha: Vec<u8> = bytes.iter().skip(3).step_by(4).copied().collect();
+    if alpha.iter().all(|&a| a == 0) {
+        return None;
+    }
+    // Positive heights are stored bottom-up
+    if height > 0 && width > 0 {
+        alpha = alpha.chunks(width).rev().flatten().copied().collect();
+    }
+    Some(alpha)
+}
+
+/// Prepends a `BITMAPFILEHEADER` to a DIB. Its pixels start after the header, the
+/// color masks of `BI_BITFIELDS` and the color table.
+fn dib_to_bmp(dib: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let header_size = read_u32(dib, 0).ok_or_else(|| truncated("bitmap header"))?;
+    let palette_size = if header_size == BITMAPCOREHEADER_SIZE {
+        let bit_count = read_u16(dib, 10).ok_or_else(|| truncated("bitmap header"))?;
+        if bit_count <= 8 {
+            3usize << bit_count
+        } else {
+            0
+        }
+    } else if BITMAPINFOHEADER_SIZES.contains(&header_size) {
+        let bit_count = read_u16(dib, 14).ok_or_else(|| truncated("bitmap header"))?;
+        let compression = read_u32(dib, 16).ok_or_else(|| truncated("bitmap header"))?;
+        let colors_used = read_u32(dib, 32).ok_or_else(|| truncated("bitmap header"))?;
+        let colors = match colors_used {
+            0 if bit_count <= 8 => 1usize << bit_count,
+            colors => colors as usize,
+        };
+        // Masks follow a plain BITMAPINFOHEADER; the later headers include them
+        let masks = match compression {
+            BI_BITFIELDS if header_size == 40 => 12,
+            BI_ALPHABITFIELDS if header_size == 40 => 16,
+            _ => 0,
+        };
+        masks + colors * 4
+    } else {
+        return Err(invalid_data("not an image or a device-independent bitmap"));
+    };
+
+    let pixels = BITMAPFILEHEADER_SIZE + header_size as usize + palette_size;
+    let file_size = BITMAPFILEHEADER_SIZE + dib.len();
+    if pixels > file_size {
+        return Err(truncated("bitmap color table"));
+    }
+    let mut data = Vec::with_capacity(file_size);
+    data.extend_from_slice(b"BM");
+    data.extend_from_slice(&(file_size as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&(pixels as u32).to_le_bytes());
+    data.extend_from_slice(dib);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::image_utils::encode_png;
+
+    /// A `BITMAPINFOHEADER` DIB followed by `tables` (masks and colors) and `pixels`.
+    fn dib(
+        width: i32,
+        height: i32,
+        bit_count: u16,
+        compression: u32,
+        tables: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let mut dib = Vec::new();
+        dib.extend_from_slice(&40u32.to_le_bytes());
+        dib.extend_from_slice(&width.to_le_bytes());
+        dib.extend_from_slice(&height.to_le_bytes());
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&bit_count.to_le_bytes());
+        dib.extend_from_slice(&compression.to_le_bytes());
+        dib.resize(40, 0);
+        dib.extend_from_slice(tables);
+        dib.extend_from_slice(pixels);
+        dib
+    }
+
+    fn pixel_offset(bmp: &[u8]) -> u32 {
+        read_u32(bmp, 10).unwrap()
+    }
+
+    #[test]
+    fn sniffs_image_files() {
+        assert_eq!(
+            sniff_format(b"\x89PNG\r\n\x1a\n...."),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            sniff_format(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(sniff_format(b"GIF89a...."), Some(ImageFormat::Gif));
+        assert_eq!(sniff_format(b"BM\x06\0\0\0"), Some(ImageFormat::Bmp));
+        // A file size past the end is not a bitmap file
+        assert_eq!(sniff_format(b"BM\xFF\0\0\0"), None);
+        assert_eq!(sniff_format(&40u32.to_le_bytes()), None);
+    }
+
+    #[test]
+    fn places_pixels_after_the_color_table() {
+        // Two colors in use, each row padded to 32 bits
+        let palette = [0, 0, 255, 0, 255, 0, 0, 0];
+        let mut indexed = dib(2, 1, 8, BI_RGB, &palette, &[0, 1, 0, 0]);
+        indexed[32..36].copy_from_slice(&2u32.to_le_bytes());
+        let image = ResourceImage::from_bytes(&indexed).unwrap();
+        assert_eq!(image.format, ImageFormat::Bmp);
+        assert_eq!(pixel_offset(&image.data), 14 + 40 + 8);
+        let decoded = image.to_image().unwrap();
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 0, 255, 255]);
+
+        // Without a count, the table holds every color of the bit depth
+        let full = dib(8, 1, 4, BI_RGB, &[0; 16 * 4], &[0; 4]);
+        assert_eq!(pixel_offset(&dib_to_bmp(&full).unwrap()), 14 + 40 + 64);
+        assert!(dib_to_bmp(&full[..40 + 32]).is_err());
+
+        let masks: Vec<u8> = [0xF800u32, 0x07E0, 0x001F]
+            .iter()
+            .flat_map(|mask| mask.to_le_bytes())
+            .collect();
+        let bitfields = dib(2, 1, 16, BI_BITFIELDS, &masks, &[0x00, 0xF8, 0x1F, 0x00]);
+        let image = ResourceImage::from_bytes(&bitfields).unwrap();
+        assert_eq!(pixel_offset(&image.data), 14 + 40 + 12);
+        let decoded = image.to_image().unwrap();
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn reads_core_headers() {
+        // BITMAPCOREHEADER: 16-bit sizes and 3-byte colors
+        let mut core = 12u32.to_le_bytes().to_vec();
+        for value in [8u16, 1, 1, 1] {
+            core.extend_from_slice(&value.to_le_bytes());
+        }
+        core.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        core.extend_from_slice(&[0b1010_1010, 0, 0, 0]);
+        let image = ResourceImage::from_bytes(&core).unwrap();
+        assert_eq!(pixel_offset(&image.data), 14 + 12 + 6);
+
+        let decoded = image.to_image().unwrap();
+        assert_eq!(decoded.dimensions(), (8, 1));
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 0, 0, 255]);
+
+        assert!(ResourceImage::from_bytes(&core[..16]).is_err());
+        assert!(ResourceImage::from_bytes(&[64, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn uses_the_fourth_byte_as_alpha() {
+        // Bottom-up: the first row in the file is the bottom one
+        let pixels = [9, 9, 9, 10, 9, 9, 9, 20, 9, 9, 9, 30, 9, 9, 9, 40];
+        let bottom_up = ResourceImage::from_bytes(&dib(2, 2, 32, BI_RGB, &[], &pixels)).unwrap();
+        let alpha: Vec<u8> = bottom_up
+            .to_image()
+            .unwrap()
+            .pixels()
+            .map(|p| p.0[3])
+            .collect();
+        assert_eq!(alpha, [30, 40, 10, 20]);
+
+        let top_down = ResourceImage::from_bytes(&dib(2, -2, 32, BI_RGB, &[], &pixels)).unwrap();
+        let alpha: Vec<u8> = top_down
+            .to_image()
+            .unwrap()
+            .pixels()
+            .map(|p| p.0[3])
+            .collect();
+        assert_eq!(alpha, [10, 20, 30, 40]);
+
+        // No alpha set anywhere means an opaque bitmap
+        let pixels = [9, 9, 9, 0].repeat(4);
+        let opaque = ResourceImage::from_bytes(&dib(2, 2, 32, BI_RGB, &[], &pixels)).unwrap();
+        assert!(opaque.to_image().unwrap().pixels().all(|p| p.0[3] == 255));
+    }
+
+    #[test]
+    fn slices_strips_into_tiles() {
+        let strip = RgbaImage::from_fn(6, 2, |x, _| image::Rgba([x as u8 / 2, 0, 0, 255]));
+        let image = ResourceImage::from_bytes(&encode_png(&strip).unwrap()).unwrap();
+        assert_eq!(image.format, ImageFormat::Png);
+
+        let tiles = image.to_square_tiles().unwrap();
+        assert_eq!(tiles.len(), 3);
+        for (i, tile) in tiles.iter().enumerate() {
+            assert_eq!(tile.dimensions(), (2, 2));
+            assert!(tile.pixels().all(|p| p.0[0] == i as u8));
+        }
+        assert_eq!(image.to_tiles(3, 1).unwrap().len(), 4);
+        assert!(image.to_tiles(4, 2).is_err());
+        assert!(image.to_tiles(0, 2).is_err());
+    }
+}
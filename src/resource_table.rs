@@ -0,0 +1,236 @@
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    IconGroup, PeFile, RT_GROUP_ICON, RT_ICON, ResourceId,
+    icon_group::ICONDIR_SIZE,
+    res_file::write_res,
+    utils::binary_utils::{invalid_data, read_u16},
+};
+
+const GRPICONDIRENTRY_SIZE: usize = 14;
+
+/// One resource to write: its type, name, language and data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceEntry {
+    pub type_id: ResourceId,
+    pub name: ResourceId,
+    pub language: u16,
+    pub data: Vec<u8>,
+}
+
+/// A set of resources to write out, as a compiled resource file (`.res`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceTable {
+    pub entries: Vec<ResourceEntry>,
+}
+
+impl ResourceTable {
+    pub fn new() -> Self {
+        ResourceTable::default()
+    }
+
+    /// Copies every resource of a PE, NE or `.res` file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_pe(&PeFile::open(path)?))
+    }
+
+    pub fn from_pe(pe: &PeFile) -> Self {
+        ResourceTable {
+            entries: pe
+                .resources()
+                .iter()
+                .map(|resource| ResourceEntry {
+                    type_id: resource.type_id.clone(),
+                    name: resource.name.clone(),
+                    language: resource.language,
+                    data: pe.resource_data(resource).to_vec(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds a resource, replacing the one with the same type, name and language.
+    /// Names are stored in upper case, as resource compilers do, because
+    /// `FindResource` looks them up that way.
+    pub fn insert(&mut self, type_id: ResourceId, name: ResourceId, language: u16, data: Vec<u8>) {
+        let entry = ResourceEntry {
+            type_id: upper_case(type_id),
+            name: upper_case(name),
+            language,
+            data,
+        };
+        match self.entries.iter_mut().find(|e| {
+            e.type_id.matches(&entry.type_id)
+                && e.name.matches(&entry.name)
+                && e.language == entry.language
+        }) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Removes every language of a resource and returns how many were removed.
+    pub fn remove(&mut self, type_id: &ResourceId, name: &ResourceId) -> usize {
+        let count = self.entries.len();
+        self.entries
+            .retain(|e| !(e.type_id.matches(type_id) && e.name.matches(name)));
+        count - self.entries.len()
+    }
+
+    /// The names of the `RT_GROUP_ICON` resources, in order and without duplicates.
+    pub fn icon_groups(&self) -> Vec<ResourceId> {
+        let group = ResourceId::Id(RT_GROUP_ICON);
+        let mut names: Vec<ResourceId> = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.type_id.matches(&group)) {
+            if !names.iter().any(|name| name.matches(&entry.name)) {
+                names.push(entry.name.clone());
+            }
+        }
+        names
+    }
+
+    /// Adds an icon group and its frames as `RT_ICON` resources, numbered from the
+    /// lowest unused IDs. A group of the same name and language is replaced, and the
+    /// frames only it used are removed.
+    pub fn add_icon_group(
+        &mut self,
+        name: ResourceId,
+        language: u16,
+        group: &IconGroup,
+    ) -> Result<(), Box<dyn Error>> {
+        if group.frames.is_empty() {
+            return Err(invalid_data("icon group has no frames"));
+        }
+        let name = upper_case(name);
+        self.remove_icon_groups(|entry| entry.name.matches(&name) && entry.language == language);
+
+        let mut used = self.icon_ids();
+        let mut ids = Vec::with_capacity(group.frames.len());
+        let mut next = 1u16;
+        for frame in &group.frames {
+            while used.contains(&next) {
+                next = next
+                    .checked_add(1)
+                    .ok_or_else(|| invalid_data("no free RT_ICON IDs"))?;
+            }
+            used.insert(next);
+            ids.push(next);
+            self.entries.push(ResourceEntry {
+                type_id: ResourceId::Id(RT_ICON),
+                name: ResourceId::Id(next),
+                language,
+                data: frame.data.clone(),
+            });
+        }
+        self.entries.push(ResourceEntry {
+            type_id: ResourceId::Id(RT_GROUP_ICON),
+            name,
+            language,
+            data: group.to_group_bytes(&ids),
+        });
+        Ok(())
+    }
+
+    /// Replaces every language of an existing icon group with `group`, keeping its
+    /// name and languages. Frame IDs the old group used are reused first.
+    pub fn replace_icon_group(
+        &mut self,
+        name: &ResourceId,
+        group: &IconGroup,
+    ) -> Result<(), Box<dyn Error>> {
+        let group_type = ResourceId::Id(RT_GROUP_ICON);
+        let mut languages: Vec<(ResourceId, u16)> = self
+            .entries
+            .iter()
+            .filter(|e| e.type_id.matches(&group_type) && e.name.matches(name))
+            .map(|e| (e.name.clone(), e.language))
+            .collect();
+        if languages.is_empty() {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                format!("icon group not found: {name}"),
+            )));
+        }
+        languages.sort_by_key(|(_, language)| *language);
+
+        self.remove_icon_group(name);
+        for (name, language) in languages {
+            self.add_icon_group(name, language, group)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every language of an icon group, and the frames no other group uses.
+    /// Returns whether there was one.
+    pub fn remove_icon_group(&mut self, name: &ResourceId) -> bool {
+        self.remove_icon_groups(|entry| entry.name.matches(name))
+    }
+
+    pub fn to_res_bytes(&self) -> Vec<u8> {
+        write_res(&self.entries)
+    }
+
+    pub fn save_res<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path.as_ref(), self.to_res_bytes())?;
+        Ok(())
+    }
+
+    fn remove_icon_groups<F>(&mut self, matches: F) -> bool
+    where
+        F: Fn(&ResourceEntry) -> bool,
+    {
+        let group = ResourceId::Id(RT_GROUP_ICON);
+        let before = self.entries.len();
+        self.entries
+            .retain(|entry| !(entry.type_id.matches(&group) && matches(entry)));
+        if self.entries.len() == before {
+            return false;
+        }
+
+        let referenced = self.referenced_icon_ids();
+        self.entries.retain(|entry| {
+            !entry.type_id.matches(&ResourceId::Id(RT_ICON))
+                || matches!(entry.name, ResourceId::Id(id) if referenced.contains(&id))
+        });
+        true
+    }
+
+    fn icon_ids(&self) -> BTreeSet<u16> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.type_id.matches(&ResourceId::Id(RT_ICON)))
+            .filter_map(|entry| match entry.name {
+                ResourceId::Id(id) => Some(id),
+                ResourceId::Name(_) => None,
+            })
+            .collect()
+    }
+
+    /// The `RT_ICON` IDs the remaining groups point at.
+    fn referenced_icon_ids(&self) -> BTreeSet<u16> {
+        let group = ResourceId::Id(RT_GROUP_ICON);
+        self.entries
+            .iter()
+            .filter(|entry| entry.type_id.matches(&group))
+            .flat_map(|entry| {
+                let count = usize::from(read_u16(&entry.data, 4).unwrap_or_default());
+                (0..count).filter_map(|i| {
+                    read_u16(&entry.data, ICONDIR_SIZE + i * GRPICONDIRENTRY_SIZE + 12)
+                })
+            })
+            .collect()
+    }
+}
+
+fn upper_case(id: ResourceId) -> ResourceId {
+    match id {
+        ResourceId::Name(name) => ResourceId::Name(name.to_uppercase()),
+        id => id,
+    }
+}
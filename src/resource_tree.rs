@@ -0,0 +1,256 @@
+use std::{error::Error, path::Path};
+
+use crate::{PeFile, RT_GROUP_ICON, ResourceId};
+
+/// The whole resource directory of a module, for inspecting what it contains: every
+/// type, name and language, and a summary of its icon groups. With the `serde`
+/// feature it serializes as a nested structure, e.g. to JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResourceTree {
+    pub types: Vec<ResourceTypeNode>,
+    pub icon_groups: Vec<IconGroupSummary>,
+}
+
+/// The resources of one type, in directory order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResourceTypeNode {
+    pub id: ResourceId,
+    /// The `RT_*` name of a predefined type, such as `RT_GROUP_ICON`.
+    pub known_name: Option<&'static str>,
+    pub names: Vec<ResourceNameNode>,
+}
+
+/// The languages of one resource, in directory order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResourceNameNode {
+    pub id: ResourceId,
+    pub languages: Vec<ResourceLeaf>,
+}
+
+/// The data of one language of a resource; `offset` is a file offset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResourceLeaf {
+    pub language: u16,
+    pub code_page: u32,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// An `RT_GROUP_ICON` resource and the frames it resolves to. `index` is the icon
+/// index that addresses it in an icon location such as `app.exe,2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IconGroupSummary {
+    pub index: usize,
+    pub name: ResourceId,
+    pub languages: Vec<u16>,
+    /// The frames of the first language; empty when the group cannot be read.
+    pub frames: Vec<IconFrameSummary>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IconFrameSummary {
+    pub width: u32,
+    pub height: u32,
+    pub bit_count: u16,
+    pub png: bool,
+    pub size: usize,
+}
+
+impl ResourceTree {
+    /// Reads the resources of a PE, NE or `.res` file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_pe(&PeFile::open(path)?))
+    }
+
+    pub fn from_pe(pe: &PeFile) -> Self {
+        let mut types: Vec<ResourceTypeNode> = Vec::new();
+        for resource in pe.resources() {
+            let type_index = match types.iter().position(|t| t.id.matches(&resource.type_id)) {
+                Some(index) => index,
+                None => {
+                    types.push(ResourceTypeNode {
+                        id: resource.type_id.clone(),
+                        known_name: known_type_name(&resource.type_id),
+                        names: Vec::new(),
+                    });
+                    types.len() - 1
+                }
+            };
+            let names = &mut types[type_index].names;
+            let name_index = match names.iter().position(|n| n.id.matches(&resource.name)) {
+                Some(index) => index,
+                None => {
+                    names.push(ResourceNameNode {
+                        id: resource.name.clone(),
+                        languages: Vec::new(),
+                    });
+                    names.len() - 1
+                }
+            };
+            names[name_index].languages.push(ResourceLeaf {
+                language: resource.language,
+                code_page: resource.code_page,
+                offset: resource.offset,
+                size: resource.size,
+            });
+        }
+
+        let icon_groups = pe
+            .icon_groups()
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| IconGroupSummary {
+                index,
+                languages: pe
+                    .find_resources(&RT_GROUP_ICON.into(), &name)
+                    .iter()
+                    .map(|r| r.language)
+                    .collect(),
+                frames: pe
+                    .icon_group(&name)
+                    .map(|group| {
+                        group
+                            .frames
+                            .iter()
+                            .map(|frame| IconFrameSummary {
+                                width: frame.width,
+                                height: frame.height,
+                                bit_count: frame.bit_count,
+                                png: frame.is_png(),
+                                size: frame.data.len(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                name,
+            })
+            .collect();
+
+        ResourceTree { types, icon_groups }
+    }
+
+    pub fn resource_count(&self) -> usize {
+        self.types
+            .iter()
+            .flat_map(|t| &t.names)
+            .map(|n| n.languages.len())
+            .sum()
+    }
+}
+
+/// The `RT_*` constant names of the predefined resource types.
+fn known_type_name(type_id: &ResourceId) -> Option<&'static str> {
+    let ResourceId::Id(id) = type_id else {
+        return None;
+    };
+    Some(match id {
+        1 => "RT_CURSOR",
+        2 => "RT_BITMAP",
+        3 => "RT_ICON",
+        4 => "RT_MENU",
+        5 => "RT_DIALOG",
+        6 => "RT_STRING",
+        7 => "RT_FONTDIR",
+        8 => "RT_FONT",
+        9 => "RT_ACCELERATOR",
+        10 => "RT_RCDATA",
+        11 => "RT_MESSAGETABLE",
+        12 => "RT_GROUP_CURSOR",
+        14 => "RT_GROUP_ICON",
+        16 => "RT_VERSION",
+        17 => "RT_DLGINCLUDE",
+        19 => "RT_PLUGPLAY",
+        20 => "RT_VXD",
+        21 => "RT_ANICURSOR",
+        22 => "RT_ANIICON",
+        23 => "RT_HTML",
+        24 => "RT_MANIFEST",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::{IconGroup, IconLibrary, RT_ICON, replace_pe_resources};
+
+    #[test]
+    fn walks_types_names_and_languages() {
+        let group = |sizes: &[u32]| {
+            let images: Vec<RgbaImage> = sizes.iter().map(|&s| RgbaImage::new(s, s)).collect();
+            IconGroup::from_images(&images).unwrap()
+        };
+        let library = IconLibrary::new()
+            .with_named_icon("logo", group(&[16]))
+            .with_icon(1, group(&[32, 16]))
+            .with_icon(3, group(&[48]))
+            .with_language(0x0409);
+        let mut table = library.to_resource_table().unwrap();
+        let group_icon = ResourceId::Id(RT_GROUP_ICON);
+        let german = table
+            .entries
+            .iter()
+            .find(|e| e.type_id == group_icon && e.name == ResourceId::Id(3))
+            .unwrap()
+            .data
+            .clone();
+        table.insert(group_icon.clone(), ResourceId::Id(3), 0x0407, german);
+        let dll = replace_pe_resources(&library.to_dll_bytes().unwrap(), &table).unwrap();
+        let pe = PeFile::from_bytes(dll).unwrap();
+        let tree = ResourceTree::from_pe(&pe);
+
+        let types: Vec<_> = tree.types.iter().map(|t| (&t.id, t.known_name)).collect();
+        assert_eq!(
+            types,
+            [
+                (&ResourceId::Id(RT_ICON), Some("RT_ICON")),
+                (&group_icon, Some("RT_GROUP_ICON")),
+            ]
+        );
+        assert_eq!(tree.types[0].names.len(), 4);
+        let groups = &tree.types[1].names;
+        let languages: Vec<Vec<u16>> = groups
+            .iter()
+            .map(|n| n.languages.iter().map(|l| l.language).collect())
+            .collect();
+        assert_eq!(
+            languages,
+            [vec![0x0409], vec![0x0409], vec![0x0407, 0x0409]]
+        );
+        assert_eq!(tree.resource_count(), 8);
+        let logo = pe.find_resource(&group_icon, &groups[0].id).unwrap();
+        assert_eq!(
+            (groups[0].languages[0].offset, groups[0].languages[0].size),
+            (logo.offset, logo.size)
+        );
+
+        // Summaries follow `ExtractIcon` indexes: names first, then IDs
+        let summaries: Vec<_> = tree
+            .icon_groups
+            .iter()
+            .map(|g| (g.index, &g.name, g.languages.clone()))
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                (0, &ResourceId::Name("LOGO".to_owned()), vec![0x0409]),
+                (1, &ResourceId::Id(1), vec![0x0409]),
+                (2, &ResourceId::Id(3), vec![0x0407, 0x0409]),
+            ]
+        );
+        let frames: Vec<(u32, u16, bool)> = tree.icon_groups[1]
+            .frames
+            .iter()
+            .map(|f| (f.width, f.bit_count, f.png))
+            .collect();
+        assert_eq!(frames, [(32, 32, false), (16, 32, false)]);
+    }
+}
@@ -0,0 +1,665 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    DllIcon, IconSource,
+    utils::{
+        binary_utils::{
+            decode_utf16le, format_guid, invalid_data, read_ansiz, read_guid, read_i32, read_u8,
+            read_u16, read_u32, read_utf16z, truncated,
+        },
+        path_utils::{expand_env_vars, resolve_icon_path},
+    },
+};
+
+const HEADER_SIZE: u32 = 0x4C;
+const LINK_CLSID: &str = "{00021401-0000-0000-C000-000000000046}";
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x0000_0001;
+const HAS_LINK_INFO: u32 = 0x0000_0002;
+const HAS_NAME: u32 = 0x0000_0004;
+const HAS_RELATIVE_PATH: u32 = 0x0000_0008;
+const HAS_WORKING_DIR: u32 = 0x0000_0010;
+const HAS_ARGUMENTS: u32 = 0x0000_0020;
+const HAS_ICON_LOCATION: u32 = 0x0000_0040;
+const IS_UNICODE: u32 = 0x0000_0080;
+const FORCE_NO_LINK_INFO: u32 = 0x0000_0100;
+const HAS_EXP_STRING: u32 = 0x0000_0200;
+const HAS_EXP_ICON: u32 = 0x0000_4000;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+const ENVIRONMENT_VARIABLE_DATA_BLOCK: u32 = 0xA000_0001;
+const SPECIAL_FOLDER_DATA_BLOCK: u32 = 0xA000_0005;
+const ICON_ENVIRONMENT_DATA_BLOCK: u32 = 0xA000_0007;
+const PROPERTY_STORE_DATA_BLOCK: u32 = 0xA000_0009;
+const KNOWN_FOLDER_DATA_BLOCK: u32 = 0xA000_000B;
+
+// PKEY_AppUserModel_ID = {9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}, 5
+const APP_USER_MODEL_FMTID: &str = "{9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}";
+const APP_USER_MODEL_ID_PID: u32 = 5;
+const VT_LPWSTR: u16 = 0x001F;
+
+const MY_COMPUTER_CLSID: &str = "{20D04FE0-3AEA-1069-A2D8-08002B30309D}";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KnownFolder {
+    pub id: String,
+    pub offset: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellLink {
+    pub link_flags: u32,
+    pub file_attributes: u32,
+    pub icon_index: i32,
+    pub show_command: u32,
+    pub id_list: Vec<Vec<u8>>,
+    pub local_base_path: Option<String>,
+    pub network_path: Option<String>,
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub environment_target: Option<String>,
+    pub icon_environment_location: Option<String>,
+    pub known_folder: Option<KnownFolder>,
+    pub special_folder: Option<(u32, u32)>,
+    pub app_user_model_id: Option<String>,
+}
+
+impl ShellLink {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path.as_ref())?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let header_size = read_u32(data, 0).ok_or_else(|| truncated("shell link header"))?;
+        let clsid = read_guid(data, 4).ok_or_else(|| truncated("shell link header"))?;
+        if header_size != HEADER_SIZE || format_guid(&clsid) != LINK_CLSID {
+            return Err(invalid_data("not a shell link file"));
+        }
+
+        let header = |offset| read_u32(data, offset).ok_or_else(|| truncated("shell link header"));
+        let mut link = ShellLink {
+            link_flags: header(20)?,
+            file_attributes: header(24)?,
+            icon_index: read_i32(data, 56).ok_or_else(|| truncated("shell link header"))?,
+            show_command: header(60)?,
+            ..Default::default()
+        };
+        let flags = link.link_flags;
+        let mut offset = HEADER_SIZE as usize;
+
+        if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            let size = read_u16(data, offset).ok_or_else(|| truncated("LinkTargetIDList"))?;
+            let id_list = data
+                .get(offset + 2..offset + 2 + usize::from(size))
+                .ok_or_else(|| truncated("LinkTargetIDList"))?;
+            link.id_list = parse_id_list(id_list);
+            offset += 2 + usize::from(size);
+        }
+
+        if flags & HAS_LINK_INFO != 0 {
+            let size = read_u32(data, offset).ok_or_else(|| truncated("LinkInfo"))? as usize;
+            let link_info = data
+                .get(offset..offset + size)
+                .ok_or_else(|| truncated("LinkInfo"))?;
+            if flags & FORCE_NO_LINK_INFO == 0 {
+                link.parse_link_info(link_info);
+            }
+            offset += size;
+        }
+
+        let is_unicode = flags & IS_UNICODE != 0;
+        let string_flags = [
+            HAS_NAME,
+            HAS_RELATIVE_PATH,
+            HAS_WORKING_DIR,
+            HAS_ARGUMENTS,
+            HAS_ICON_LOCATION,
+        ];
+        for flag in string_flags {
+            if flags & flag == 0 {
+                continue;
+            }
+            let (value, size) = read_counted_string(data, offset, is_unicode)?;
+            offset += size;
+            let field = match flag {
+                HAS_NAME => &mut link.name,
+                HAS_RELATIVE_PATH => &mut link.relative_path,
+                HAS_WORKING_DIR => &mut link.working_dir,
+                HAS_ARGUMENTS => &mut link.arguments,
+                _ => &mut link.icon_location,
+            };
+            *field = Some(value);
+        }
+
+        link.parse_extra_data(data.get(offset..).unwrap_or_default());
+        Ok(link)
+    }
+
+    fn parse_link_info(&mut self, info: &[u8]) {
+        let header_size = read_u32(info, 4).unwrap_or_default();
+        let flags = read_u32(info, 8).unwrap_or_default();
+        let field = |offset| read_u32(info, offset).map(|v| v as usize);
+
+        let suffix = if header_size >= 0x24 {
+            field(32).and_then(|o| read_utf16z(info, o))
+        } else {
+            field(24).and_then(|o| read_ansiz(info, o))
+        }
+        .unwrap_or_default();
+
+        if flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+            let base_path = if header_size >= 0x24 {
+                field(28).and_then(|o| read_utf16z(info, o))
+            } else {
+                field(16).and_then(|o| read_ansiz(info, o))
+            };
+            self.local_base_path = base_path.map(|base| join_windows_path(&base, &suffix));
+        }
+
+        if flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+            let network_link = field(20).and_then(|o| info.get(o..)).unwrap_or_default();
+            let net_name_offset = read_u32(network_link, 8).unwrap_or_default() as usize;
+            let net_name = if net_name_offset > 0x14 {
+                read_u32(network_link, 20).and_then(|o| read_utf16z(network_link, o as usize))
+            } else {
+                read_ansiz(network_link, net_name_offset)
+            };
+            self.network_path = net_name.map(|name| join_windows_path(&name, &suffix));
+        }
+    }
+
+    fn parse_extra_data(&mut self, mut data: &[u8]) {
+        while let Some(size) = read_u32(data, 0).map(|s| s as usize) {
+            let Some(block) = data.get(..size).filter(|_| size >= 8) else {
+                break;
+            };
+            let signature = read_u32(block, 4).unwrap_or_default();
+
+            match signature {
+                ENVIRONMENT_VARIABLE_DATA_BLOCK => {
+                    self.environment_target = read_environment_block(block);
+                }
+                ICON_ENVIRONMENT_DATA_BLOCK => {
+                    self.icon_environment_location = read_environment_block(block);
+                }
+                SPECIAL_FOLDER_DATA_BLOCK => {
+                    self.special_folder = read_u32(block, 8).zip(read_u32(block, 12));
+                }
+                KNOWN_FOLDER_DATA_BLOCK => {
+                    self.known_folder =
+                        read_guid(block, 8)
+                            .zip(read_u32(block, 24))
+                            .map(|(id, offset)| KnownFolder {
+                                id: format_guid(&id),
+                                offset,
+                            });
+                }
+                PROPERTY_STORE_DATA_BLOCK => {
+                    self.app_user_model_id = find_app_user_model_id(&block[8..]);
+                }
+                _ => {}
+            }
+
+            data = &data[size..];
+        }
+    }
+
+    /// Best-effort path of the link target: the expanded environment target, the
+    /// `LinkInfo` path, the `LinkTargetIDList` path, then the relative path.
+    pub fn target_path(&self, link_path: Option<&Path>) -> Option<PathBuf> {
+        self.target_path_with(link_path, expand_env_vars)
+    }
+
+    fn target_path_with(
+        &self,
+        link_path: Option<&Path>,
+        expand: fn(&str) -> String,
+    ) -> Option<PathBuf> {
+        if self.link_flags & HAS_EXP_STRING != 0
+            && let Some(target) = self.environment_target.as_deref()
+        {
+            return Some(PathBuf::from(expand(target)));
+        }
+
+        if let Some(path) = self.local_base_path.as_ref().or(self.network_path.as_ref()) {
+            return Some(PathBuf::from(path));
+        }
+
+        if let Some(path) = self
+            .known_folder_path(expand)
+            .or_else(|| id_list_path(&self.id_list))
+        {
+            return Some(PathBuf::from(path));
+        }
+
+        let relative_path = self.relative_path.as_deref()?;
+        let link_folder = link_path.and_then(Path::parent)?;
+        Some(link_folder.join(relative_path.replace('\\', "/")))
+    }
+
+    fn known_folder_path(&self, expand: fn(&str) -> String) -> Option<String> {
+        let known_folder = self.known_folder.as_ref()?;
+        let folder = known_folder_path(&known_folder.id)?;
+
+        // The block offset points at the first ID list item below the known folder
+        let mut position = 0;
+        let mut rest = Vec::new();
+        for item in &self.id_list {
+            if position >= known_folder.offset as usize {
+                rest.push(item_name(item)?);
+            }
+            position += item.len() + 2;
+        }
+
+        Some(
+            rest.iter()
+                .fold(expand(folder), |path, name| join_windows_path(&path, name)),
+        )
+    }
+
+    /// Resolves which icon Explorer would show for this link, without the shortcut overlay.
+    /// `%NAME%` references stay in the paths, for whoever loads the icon to expand.
+    pub fn icon_source(&self, link_path: Option<&Path>) -> Option<IconSource> {
+        let icon_location = if self.link_flags & HAS_EXP_ICON != 0 {
+            self.icon_environment_location
+                .as_deref()
+                .or(self.icon_location.as_deref())
+        } else {
+            self.icon_location.as_deref()
+        };
+
+        if let Some(location) = icon_location.filter(|l| !l.is_empty()) {
+            let path = resolve_icon_path(location, link_path.and_then(Path::parent));
+            let icon = DllIcon::new().with_icon_location(path, self.icon_index);
+            return Some(IconSource::Resource(icon));
+        }
+
+        if let Some(app_id) = self
+            .app_user_model_id
+            .as_ref()
+            .filter(|id| id.contains('!'))
+        {
+            return Some(IconSource::AppUserModelId(app_id.to_owned()));
+        }
+
+        self.target_path_with(link_path, str::to_owned)
+            .map(IconSource::File)
+    }
+}
+
+fn read_counted_string(
+    data: &[u8],
+    offset: usize,
+    is_unicode: bool,
+) -> Result<(String, usize), Box<dyn Error>> {
+    let count = usize::from(read_u16(data, offset).ok_or_else(|| truncated("StringData"))?);
+    let len = if is_unicode { count * 2 } else { count };
+    let bytes = data
+        .get(offset + 2..offset + 2 + len)
+        .ok_or_else(|| truncated("StringData"))?;
+
+    let value = if is_unicode {
+        decode_utf16le(bytes)
+    } else {
+        bytes.iter().map(|&b| char::from(b)).collect()
+    };
+    Ok((value, 2 + len))
+}
+
+fn read_environment_block(block: &[u8]) -> Option<String> {
+    // TargetAnsi is 260 bytes at offset 8, TargetUnicode is 520 bytes after it
+    let unicode = block.get(268..788).and_then(|b| read_utf16z(b, 0));
+    unicode
+        .filter(|s| !s.is_empty())
+        .or_else(|| block.get(8..268).and_then(|b| read_ansiz(b, 0)))
+        .filter(|s| !s.is_empty())
+}
+
+fn find_app_user_model_id(mut data: &[u8]) -> Option<String> {
+    // A sequence of serialized property storages, each with a list of typed values
+    while let Some(storage_size) = read_u32(data, 0).map(|s| s as usize) {
+        let storage = data.get(..storage_size).filter(|_| storage_size >= 24)?;
+        let format_id = read_guid(storage, 8).map(|id| format_guid(&id))?;
+
+        if format_id == APP_USER_MODEL_FMTID {
+            let mut values = &storage[24..];
+            while let Some(value_size) = read_u32(values, 0).map(|s| s as usize) {
+                let value = values.get(..value_size).filter(|_| value_size >= 13)?;
+                let id = read_u32(value, 4)?;
+                let value_type = read_u16(value, 9)?;
+                if id == APP_USER_MODEL_ID_PID && value_type == VT_LPWSTR {
+                    return read_utf16z(value, 17);
+                }
+                values = &values[value_size..];
+            }
+        }
+
+        data = &data[storage_size..];
+    }
+    None
+}
+
+fn parse_id_list(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+    while let Some(size) = read_u16(data, offset).map(usize::from) {
+        if size < 2 {
+            break;
+        }
+        match data.get(offset + 2..offset + size) {
+            Some(item) => items.push(item.to_vec()),
+            None => break,
+        }
+        offset += size;
+    }
+    items
+}
+
+fn id_list_path(items: &[Vec<u8>]) -> Option<String> {
+    let mut items = items.iter();
+    let first = items.next()?;
+
+    // Only paths below "This PC" can be turned into file system paths
+    let mut path = match read_u8(first, 0)? {
+        0x1F if read_guid(first, 2).map(|g| format_guid(&g)).as_deref()
+            == Some(MY_COMPUTER_CLSID) =>
+        {
+            String::new()
+        }
+        0x23..=0x2F => read_ansiz(first, 1)?,
+        _ => return None,
+    };
+
+    for item in items {
+        let name = match read_u8(item, 0)? {
+            0x23..=0x2F => read_ansiz(item, 1)?,
+            _ => item_name(item)?,
+        };
+        path = if path.is_empty() {
+            name
+        } else {
+            join_windows_path(&path, &name)
+        };
+    }
+
+    (!path.is_empty()).then_some(path)
+}
+
+fn item_name(item: &[u8]) -> Option<String> {
+    // File entry items: 0x30-0x3F, with an optional 0xBEEF0004 extension holding the long name
+    if !(0x30..=0x3F).contains(&read_u8(item, 0)?) {
+        return None;
+    }
+
+    let long_name = read_u16(item, item.len().checked_sub(2)?)
+        .map(usize::from)
+        .and_then(|extension_offset| item.get(extension_offset.checked_sub(2)?..))
+        .filter(|ext| read_u32(ext, 4) == Some(0xBEEF_0004))
+        .and_then(|ext| {
+            let version = read_u16(ext, 2)?;
+            let mut name_offset = 18;
+            if version >= 7 {
+                name_offset += 18;
+            }
+            if version >= 3 {
+                name_offset += 2;
+            }
+            if version >= 9 {
+                name_offset += 4;
+            }
+            if version >= 8 {
+                name_offset += 4;
+            }
+            read_utf16z(ext, name_offset)
+        })
+        .filter(|name| !name.is_empty());
+
+    long_name.or_else(|| read_ansiz(item, 12))
+}
+
+fn known_folder_path(id: &str) -> Option<&'static str> {
+    let path = match id {
+        "{905E63B6-C1BF-494E-B29C-65B732D3D21A}" => "%ProgramFiles%",
+        "{7C5A40EF-A0FB-4BFC-874A-C0F2E0B9FA8E}" => "%ProgramFiles(x86)%",
+        "{F7F1ED05-9F6D-47A2-AAAE-29D317C6F066}" => "%CommonProgramFiles%",
+        "{F38BF404-1D43-42F2-9305-67DE0B28FC23}" => "%SystemRoot%",
+        "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}" => "%SystemRoot%\\System32",
+        "{D65231B0-B2F1-4857-A4CE-A8E7C6EA7D27}" => "%SystemRoot%\\SysWOW64",
+        "{62AB5D82-FDC1-4DC3-A9DD-070D1D495D97}" => "%ProgramData%",
+        "{5E6C858F-0E22-4760-9AFE-EA3317B67173}" => "%USERPROFILE%",
+        "{F1B32785-6FBA-4FCF-9D55-7B8E7F157091}" => "%LOCALAPPDATA%",
+        "{3EB685DB-65F9-4CF6-A03A-E3EF65729F3D}" => "%APPDATA%",
+        "{5CD7AEE2-2219-4A67-B85D-6C9CE15660CB}" => "%LOCALAPPDATA%\\Programs",
+        "{B4BFCC3A-DB2C-424C-B029-7FE99A87C641}" => "%USERPROFILE%\\Desktop",
+        "{FDD39AD0-238F-46AF-ADB4-6C85480369C7}" => "%USERPROFILE%\\Documents",
+        "{374DE290-123F-4565-9164-39C4925E467B}" => "%USERPROFILE%\\Downloads",
+        "{A77F5D77-2E2B-44C3-A6A2-ABA601054A51}" => {
+            "%APPDATA%\\Microsoft\\Windows\\Start Menu\\Programs"
+        }
+        "{0139D44E-6AFE-49F2-8690-3DAFCAE6FFB8}" => {
+            "%ProgramData%\\Microsoft\\Windows\\Start Menu\\Programs"
+        }
+        _ => return None,
+    };
+    Some(path)
+}
+
+fn join_windows_path(base: &str, name: &str) -> String {
+    if name.is_empty() {
+        base.to_owned()
+    } else if base.ends_with('\\') {
+        format!("{base}{name}")
+    } else {
+        format!("{base}\\{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK_CLSID_BYTES: [u8; 16] = [
+        0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x46,
+    ];
+
+    fn header(flags: u32, icon_index: i32) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE as usize];
+        data[..4].copy_from_slice(&HEADER_SIZE.to_le_bytes());
+        data[4..20].copy_from_slice(&LINK_CLSID_BYTES);
+        data[20..24].copy_from_slice(&flags.to_le_bytes());
+        data[56..60].copy_from_slice(&icon_index.to_le_bytes());
+        data[60..64].copy_from_slice(&1u32.to_le_bytes());
+        data
+    }
+
+    fn push_string(data: &mut Vec<u8>, value: &str) {
+        let units: Vec<u16> = value.encode_utf16().collect();
+        data.extend_from_slice(&(units.len() as u16).to_le_bytes());
+        for unit in units {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    /// A `LinkInfo` with an ANSI local base path and an empty suffix.
+    fn link_info(base_path: &str) -> Vec<u8> {
+        let base_offset = 0x1C + 4;
+        let suffix_offset = base_offset + base_path.len() + 1;
+        let size = suffix_offset + 1;
+        let mut info = Vec::new();
+        for value in [
+            size,
+            0x1C,
+            VOLUME_ID_AND_LOCAL_BASE_PATH as usize,
+            0x1C,
+            base_offset,
+            0,
+            suffix_offset,
+        ] {
+            info.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        // An empty VolumeID
+        info.extend_from_slice(&[0; 4]);
+        info.extend_from_slice(base_path.as_bytes());
+        info.extend_from_slice(&[0, 0]);
+        info
+    }
+
+    fn environment_block(signature: u32, value: &str) -> Vec<u8> {
+        let mut block = vec![0u8; 788];
+        block[..4].copy_from_slice(&788u32.to_le_bytes());
+        block[4..8].copy_from_slice(&signature.to_le_bytes());
+        block[8..8 + value.len()].copy_from_slice(value.as_bytes());
+        for (i, unit) in value.encode_utf16().enumerate() {
+            block[268 + i * 2..270 + i * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        block
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(ShellLink::from_bytes(b"MZ").is_err());
+        let mut data = header(0, 0);
+        data[4] = 0;
+        assert!(ShellLink::from_bytes(&data).is_err());
+    }
+
+    #[test]
+    fn reads_link_info_and_strings() {
+        let mut data = header(
+            HAS_LINK_INFO | HAS_RELATIVE_PATH | HAS_ICON_LOCATION | IS_UNICODE,
+            3,
+        );
+        data.extend_from_slice(&link_info(r"C:\Tools\app.exe"));
+        push_string(&mut data, r".\app.exe");
+        push_string(&mut data, r"C:\Tools\app.dll");
+        data.extend_from_slice(&[0; 4]);
+
+        let link = ShellLink::from_bytes(&data).unwrap();
+        assert_eq!(link.local_base_path.as_deref(), Some(r"C:\Tools\app.exe"));
+        assert_eq!(link.relative_path.as_deref(), Some(r".\app.exe"));
+        assert_eq!(link.icon_location.as_deref(), Some(r"C:\Tools\app.dll"));
+        assert_eq!(link.icon_index, 3);
+        assert_eq!(
+            link.target_path(None),
+            Some(PathBuf::from(r"C:\Tools\app.exe"))
+        );
+        match link.icon_source(None) {
+            Some(IconSource::Resource(icon)) => assert_eq!(
+                icon,
+                DllIcon::new().with_icon_location(r"C:\Tools\app.dll", 3)
+            ),
+            other => panic!("unexpected icon source: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn prefers_the_environment_icon_location() {
+        let mut data = header(HAS_ICON_LOCATION | IS_UNICODE | HAS_EXP_ICON, -2);
+        push_string(&mut data, r"C:\Windows\old.dll");
+        data.extend_from_slice(&environment_block(
+            ICON_ENVIRONMENT_DATA_BLOCK,
+            r"C:\Windows\new.dll",
+        ));
+        data.extend_from_slice(&[0; 4]);
+
+        let link = ShellLink::from_bytes(&data).unwrap();
+        assert_eq!(
+            link.icon_environment_location.as_deref(),
+            Some(r"C:\Windows\new.dll")
+        );
+        match link.icon_source(None) {
+            Some(IconSource::Resource(icon)) => assert_eq!(
+                icon,
+                DllIcon::new().with_icon_location(r"C:\Windows\new.dll", -2)
+            ),
+            other => panic!("unexpected icon source: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn leaves_environment_targets_for_the_icon_loader() {
+        let mut data = header(HAS_EXP_STRING, 0);
+        data.extend_from_slice(&environment_block(
+            ENVIRONMENT_VARIABLE_DATA_BLOCK,
+            r"%ProgramFiles%\App\app.exe",
+        ));
+        data.extend_from_slice(&[0; 4]);
+
+        let link = ShellLink::from_bytes(&data).unwrap();
+        assert_eq!(
+            link.icon_source(None),
+            Some(IconSource::File(PathBuf::from(
+                r"%ProgramFiles%\App\app.exe"
+            )))
+        );
+    }
+
+    #[test]
+    fn reads_the_app_user_model_id() {
+        let app_id = "Microsoft.WindowsCalculator_8wekyb3d8bbwe!App";
+        let mut value = Vec::new();
+        value.extend_from_slice(&APP_USER_MODEL_ID_PID.to_le_bytes());
+        value.push(0);
+        value.extend_from_slice(&VT_LPWSTR.to_le_bytes());
+        value.extend_from_slice(&[0, 0]);
+        value.extend_from_slice(&(app_id.len() as u32 + 1).to_le_bytes());
+        for unit in app_id.encode_utf16().chain(Some(0)) {
+            value.extend_from_slice(&unit.to_le_bytes());
+        }
+        let mut value = [(value.len() as u32 + 4).to_le_bytes().to_vec(), value].concat();
+        value.extend_from_slice(&[0; 4]);
+
+        // {9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3}
+        let format_id = [
+            0x55, 0x28, 0x4C, 0x9F, 0x79, 0x9F, 0x39, 0x4B, 0xA8, 0xD0, 0xE1, 0xD4, 0x2D, 0xE1,
+            0xD5, 0xF3,
+        ];
+        let mut storage = Vec::new();
+        storage.extend_from_slice(&(24 + value.len() as u32).to_le_bytes());
+        storage.extend_from_slice(b"1SPS");
+        storage.extend_from_slice(&format_id);
+        storage.extend_from_slice(&value);
+
+        let mut data = header(0, 0);
+        data.extend_from_slice(&(8 + storage.len() as u32 + 4).to_le_bytes());
+        data.extend_from_slice(&PROPERTY_STORE_DATA_BLOCK.to_le_bytes());
+        data.extend_from_slice(&storage);
+        data.extend_from_slice(&[0; 8]);
+
+        let link = ShellLink::from_bytes(&data).unwrap();
+        assert_eq!(link.app_user_model_id.as_deref(), Some(app_id));
+        assert_eq!(
+            link.icon_source(None),
+            Some(IconSource::AppUserModelId(app_id.to_owned()))
+        );
+    }
+
+    #[test]
+    fn resolves_relative_paths_next_to_the_link() {
+        let mut data = header(HAS_RELATIVE_PATH | IS_UNICODE, 0);
+        push_string(&mut data, r"..\bin\app.exe");
+        let link = ShellLink::from_bytes(&data).unwrap();
+        assert_eq!(
+            link.target_path(Some(Path::new("/links/app.lnk"))),
+            Some(PathBuf::from("/links/../bin/app.exe"))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_strings() {
+        let mut data = header(HAS_NAME | IS_UNICODE, 0);
+        data.extend_from_slice(&[10, 0, b'a', 0]);
+        assert!(ShellLink::from_bytes(&data).is_err());
+    }
+}
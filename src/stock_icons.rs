@@ -0,0 +1,178 @@
+use crate::{CatalogEntry, IconCatalog};
+
+/// The shell's stock icons, mirroring `SHSTOCKICONID` (`SIID_*`). The discriminants
+/// are the `SIID_*` values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StockIcon {
+    DocNoAssoc = 0,
+    DocAssoc = 1,
+    Application = 2,
+    Folder = 3,
+    FolderOpen = 4,
+    Drive525 = 5,
+    Drive35 = 6,
+    DriveRemove = 7,
+    DriveFixed = 8,
+    DriveNet = 9,
+    DriveNetDisabled = 10,
+    DriveCd = 11,
+    DriveRam = 12,
+    World = 13,
+    Server = 15,
+    Printer = 16,
+    MyNetwork = 17,
+    Find = 22,
+    Help = 23,
+    Share = 28,
+    Link = 29,
+    SlowFile = 30,
+    Recycler = 31,
+    RecyclerFull = 32,
+    MediaCdAudio = 40,
+    Lock = 47,
+    AutoList = 49,
+    PrinterNet = 50,
+    ServerShare = 51,
+    PrinterFax = 52,
+    PrinterFaxNet = 53,
+    PrinterFile = 54,
+    Stack = 55,
+    MediaSvcd = 56,
+    StuffedFolder = 57,
+    DriveUnknown = 58,
+    DriveDvd = 59,
+    MediaDvd = 60,
+    MediaDvdRam = 61,
+    MediaDvdRw = 62,
+    MediaDvdR = 63,
+    MediaDvdRom = 64,
+    MediaCdAudioPlus = 65,
+    MediaCdRw = 66,
+    MediaCdR = 67,
+    MediaCdBurn = 68,
+    MediaBlankCd = 69,
+    MediaCdRom = 70,
+    AudioFiles = 71,
+    ImageFiles = 72,
+    VideoFiles = 73,
+    MixedFiles = 74,
+    FolderBack = 75,
+    FolderFront = 76,
+    Shield = 77,
+    Warning = 78,
+    Info = 79,
+    Error = 80,
+    Key = 81,
+    Software = 82,
+    Rename = 83,
+    Delete = 84,
+    MediaAudioDvd = 85,
+    MediaMovieDvd = 86,
+    MediaEnhancedCd = 87,
+    MediaEnhancedDvd = 88,
+    MediaHdDvd = 89,
+    MediaBluRay = 90,
+    MediaVcd = 91,
+    MediaDvdPlusR = 92,
+    MediaDvdPlusRw = 93,
+    DesktopPc = 94,
+    MobilePc = 95,
+    Users = 96,
+    MediaSmartMedia = 97,
+    MediaCompactFlash = 98,
+    DeviceCellPhone = 99,
+    DeviceCamera = 100,
+    DeviceVideoCamera = 101,
+    DeviceAudioPlayer = 102,
+    NetworkConnect = 103,
+    Internet = 104,
+    ZipFile = 105,
+    Settings = 106,
+    DriveHdDvd = 132,
+    DriveBd = 133,
+    MediaHdDvdRom = 134,
+    MediaHdDvdR = 135,
+    MediaHdDvdRam = 136,
+    MediaBdRom = 137,
+    MediaBdR = 138,
+    MediaBdRe = 139,
+    ClusteredDrive = 140,
+}
+
+/// The icon catalog names of the stock icons known to live in a fixed place, so that
+/// offline lookups follow the catalog's rows per build.
+const CATALOG_NAMES: &[(StockIcon, &str)] = &[
+    (StockIcon::DocNoAssoc, "document"),
+    (StockIcon::DocAssoc, "document-associated"),
+    (StockIcon::Application, "application"),
+    (StockIcon::Folder, "folder"),
+    (StockIcon::FolderOpen, "folder-open"),
+    (StockIcon::Drive525, "floppy-525"),
+    (StockIcon::Drive35, "floppy"),
+    (StockIcon::DriveRemove, "drive-removable"),
+    (StockIcon::DriveFixed, "drive-fixed"),
+    (StockIcon::DriveNet, "drive-network"),
+    (StockIcon::DriveNetDisabled, "drive-network-disabled"),
+    (StockIcon::DriveCd, "drive-cd"),
+    (StockIcon::DriveRam, "drive-ram"),
+    (StockIcon::World, "world"),
+    (StockIcon::Server, "server"),
+    (StockIcon::Printer, "printer"),
+    (StockIcon::MyNetwork, "network"),
+    (StockIcon::Find, "search"),
+    (StockIcon::Help, "help"),
+    (StockIcon::Share, "share-overlay"),
+    (StockIcon::Link, "link-overlay"),
+    (StockIcon::SlowFile, "slow-file"),
+    (StockIcon::Recycler, "recycle-bin-empty"),
+    (StockIcon::RecyclerFull, "recycle-bin-full"),
+    (StockIcon::MediaCdAudio, "cd-audio"),
+    (StockIcon::Lock, "lock"),
+    (StockIcon::AutoList, "autolist"),
+    (StockIcon::Shield, "shield"),
+    (StockIcon::Warning, "warning"),
+    (StockIcon::Info, "information"),
+    (StockIcon::Error, "error"),
+    (StockIcon::DesktopPc, "computer"),
+    (StockIcon::ZipFile, "zip-file"),
+];
+
+impl StockIcon {
+    /// The `SIID_*` value.
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    /// The name of this icon in [`IconCatalog::builtin`], if it has a known location.
+    pub fn catalog_name(self) -> Option<&'static str> {
+        CATALOG_NAMES
+            .iter()
+            .find(|(icon, _)| *icon == self)
+            .map(|(_, name)| *name)
+    }
+
+    /// The catalog row for this icon on the given Windows build, for reading it without
+    /// the shell. Only icons with a known location are listed; live lookups ask the
+    /// shell and cover all of them.
+    pub fn catalog_entry(self, build: u32) -> Option<&'static CatalogEntry> {
+        IconCatalog::builtin().lookup(self.catalog_name()?, build)
+    }
+
+    /// The module and icon location of this icon on the given Windows build.
+    pub fn location(self, build: u32) -> Option<(&'static str, i32)> {
+        self.catalog_entry(build)
+            .map(|entry| (entry.module.as_str(), entry.location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_catalog_name_is_in_the_catalog() {
+        for (icon, name) in CATALOG_NAMES {
+            assert!(IconCatalog::builtin().contains(name), "{icon:?}: {name}");
+        }
+    }
+}
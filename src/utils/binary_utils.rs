@@ -0,0 +1,79 @@
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+};
+
+pub fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    read_u32(data, offset).map(|v| v as i32)
+}
+
+pub fn read_guid(data: &[u8], offset: usize) -> Option<[u8; 16]> {
+    data.get(offset..offset.checked_add(16)?)?.try_into().ok()
+}
+
+/// Formats a little-endian GUID as `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}`.
+pub fn format_guid(guid: &[u8; 16]) -> String {
+    let d1 = u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]);
+    let d2 = u16::from_le_bytes([guid[4], guid[5]]);
+    let d3 = u16::from_le_bytes([guid[6], guid[7]]);
+    let d4: String = guid[8..10].iter().map(|b| format!("{b:02X}")).collect();
+    let d5: String = guid[10..16].iter().map(|b| format!("{b:02X}")).collect();
+    format!("{{{d1:08X}-{d2:04X}-{d3:04X}-{d4}-{d5}}}")
+}
+
+/// Reads a NUL-terminated UTF-16LE string, or the whole slice if it has no terminator.
+pub fn read_utf16z(data: &[u8], offset: usize) -> Option<String> {
+    let wide: Vec<u16> = data
+        .get(offset..)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&wide))
+}
+
+/// Reads a NUL-terminated ANSI string. Code pages are not available offline, so bytes
+/// are mapped as Latin-1, which is exact for ASCII paths.
+pub fn read_ansiz(data: &[u8], offset: usize) -> Option<String> {
+    let bytes = data.get(offset..)?;
+    Some(
+        bytes
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| char::from(b))
+            .collect(),
+    )
+}
+
+pub fn decode_utf16le(data: &[u8]) -> String {
+    let wide: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&wide)
+}
+
+pub fn truncated(what: &str) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        ErrorKind::UnexpectedEof,
+        format!("{what} is truncated"),
+    ))
+}
+
+pub fn invalid_data<M: Into<String>>(message: M) -> Box<dyn Error> {
+    Box::new(io::Error::new(ErrorKind::InvalidData, message.into()))
+}
@@ -1,15 +1,24 @@
 use std::{
     error::Error,
-    ffi::OsStr,
     fs::File,
-    io::{self, ErrorKind, Read},
+    io::{self, Read},
+    path::Path,
+    time::Duration,
+};
+#[cfg(windows)]
+use std::{
+    ffi::OsStr,
     mem::{self, MaybeUninit},
     os::windows::ffi::OsStrExt,
-    path::Path,
 };
 
 use base64::{Engine, engine::general_purpose};
-use image::RgbaImage;
+use image::{
+    Delay, Frame, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::FilterType,
+};
+#[cfg(windows)]
 use windows::{
     Win32::{
         Graphics::Gdi::{
@@ -25,8 +34,10 @@ use windows::{
     core::PCWSTR,
 };
 
+#[cfg(windows)]
 struct AutoDc(HDC);
 
+#[cfg(windows)]
 impl Drop for AutoDc {
     fn drop(&mut self) {
         if !self.0.0.is_null() {
@@ -35,8 +46,10 @@ impl Drop for AutoDc {
     }
 }
 
+#[cfg(windows)]
 struct AutoBitmap(HBITMAP);
 
+#[cfg(windows)]
 impl Drop for AutoBitmap {
     fn drop(&mut self) {
         if !self.0.0.is_null() {
@@ -45,8 +58,10 @@ impl Drop for AutoBitmap {
     }
 }
 
+#[cfg(windows)]
 struct AutoIcon(HICON);
 
+#[cfg(windows)]
 impl Drop for AutoIcon {
     fn drop(&mut self) {
         if !self.0.0.is_null() {
@@ -55,11 +70,13 @@ impl Drop for AutoIcon {
     }
 }
 
+#[cfg(windows)]
 pub fn get_hicon_to_image(file_path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
     let hicon = unsafe { get_hicon(file_path) }?;
     unsafe { hicon_to_image(hicon) }
 }
 
+#[cfg(windows)]
 unsafe fn get_hicon(file_path: &Path) -> Result<HICON, Box<dyn Error>> {
     let wide_path: Vec<u16> = OsStr::new(file_path).encode_wide().chain(Some(0)).collect();
     let mut shfileinfo = MaybeUninit::<SHFILEINFOW>::uninit();
@@ -76,10 +93,9 @@ unsafe fn get_hicon(file_path: &Path) -> Result<HICON, Box<dyn Error>> {
 
     if result == 0 {
         let last_error = windows::core::Error::from_win32();
-        return Err(Box::new(io::Error::new(
-            ErrorKind::Other,
-            format!("failed to get hIcon for the file: {file_path:?}: {last_error}."),
-        )));
+        return Err(Box::new(io::Error::other(format!(
+            "failed to get hIcon for the file: {file_path:?}: {last_error}."
+        ))));
     }
 
     let shfileinfo = unsafe { shfileinfo.assume_init() };
@@ -87,6 +103,7 @@ unsafe fn get_hicon(file_path: &Path) -> Result<HICON, Box<dyn Error>> {
     Ok(shfileinfo.hIcon)
 }
 
+#[cfg(windows)]
 pub unsafe fn hicon_to_image(icon: HICON) -> Result<RgbaImage, Box<dyn Error>> {
     let bitmap_size_i32 = i32::try_from(mem::size_of::<BITMAP>())?;
     let biheader_size_u32 = u32::try_from(mem::size_of::<BITMAPINFOHEADER>())?;
@@ -94,7 +111,7 @@ pub unsafe fn hicon_to_image(icon: HICON) -> Result<RgbaImage, Box<dyn Error>> {
     let mut info = MaybeUninit::uninit();
     unsafe {
         GetIconInfo(icon, info.as_mut_ptr())
-            .map_err(|e| io::Error::new(ErrorKind::Other, format!("GetIconInfo failed: {e}")))
+            .map_err(|e| io::Error::other(format!("GetIconInfo failed: {e}")))
     }?;
     let info = unsafe { info.assume_init() };
 
@@ -111,10 +128,9 @@ pub unsafe fn hicon_to_image(icon: HICON) -> Result<RgbaImage, Box<dyn Error>> {
         )
     };
     if result != bitmap_size_i32 {
-        return Err(Box::new(io::Error::new(
-            ErrorKind::Other,
-            format!("GetObjectW failed, expected {bitmap_size_i32}, got {result}"),
-        )));
+        return Err(Box::new(io::Error::other(format!(
+            "GetObjectW failed, expected {bitmap_size_i32}, got {result}"
+        ))));
     }
     let bitmap = unsafe { bitmap.assume_init() };
 
@@ -126,16 +142,13 @@ pub unsafe fn hicon_to_image(icon: HICON) -> Result<RgbaImage, Box<dyn Error>> {
 
     let buf_size = width_usize
         .checked_mul(height_usize)
-        .ok_or_else(|| io::Error::new(ErrorKind::Other, "Buffer size overflow"))?;
+        .ok_or_else(|| io::Error::other("Buffer size overflow"))?;
 
     let mut buf = vec![0u32; buf_size];
 
     let dc = unsafe { GetDC(None) };
     if dc.0.is_null() {
-        return Err(Box::new(io::Error::new(
-            ErrorKind::Other,
-            "GetDC returned null",
-        )));
+        return Err(Box::new(io::Error::other("GetDC returned null")));
     }
     let _dc_guard = AutoDc(dc);
 
@@ -168,15 +181,13 @@ pub unsafe fn hicon_to_image(icon: HICON) -> Result<RgbaImage, Box<dyn Error>> {
     };
     if result == 0 {
         let last_error = windows::core::Error::from_win32();
-        return Err(Box::new(io::Error::new(
-            ErrorKind::Other,
-            format!("GetDIBits failed: {last_error}."),
-        )));
+        return Err(Box::new(io::Error::other(format!(
+            "GetDIBits failed: {last_error}."
+        ))));
     } else if result != expected_lines {
-        return Err(Box::new(io::Error::new(
-            ErrorKind::Other,
-            format!("GetDIBits failed, expected lines: `{expected_lines}`, got: `{result}`"),
-        )));
+        return Err(Box::new(io::Error::other(format!(
+            "GetDIBits failed, expected lines: `{expected_lines}`, got: `{result}`"
+        ))));
     }
 
     let pixel_data = unsafe {
@@ -203,7 +214,7 @@ fn read_icon_file(icon_path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
 pub fn icon_file_to_image(icon_path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
     let buffer = read_icon_file(icon_path)?;
     let image = image::load_from_memory(&buffer)
-        .map_err(|e| io::Error::new(ErrorKind::Other, format!("Image decode failed: {e}")))?;
+        .map_err(|e| io::Error::other(format!("Image decode failed: {e}")))?;
     Ok(image.to_rgba8())
 }
 
@@ -220,3 +231,95 @@ pub fn image_to_base64(img: RgbaImage) -> Result<String, Box<dyn Error>> {
     )?;
     Ok(general_purpose::STANDARD.encode(buffer))
 }
+
+/// Encodes frames of one size as an endlessly looping animated PNG. Delays are kept
+/// as fractions of a second, so `.ani` jiffies (1/60 s) survive exactly.
+pub fn encode_apng(frames: &[(RgbaImage, u16, u16)]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (first, _, _) = frames
+        .first()
+        .ok_or_else(|| io::Error::other("no frames to encode"))?;
+    let mut buffer = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buffer, first.width(), first.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(u32::try_from(frames.len())?, 0)?;
+
+    let mut writer = encoder.write_header()?;
+    for (image, numerator, denominator) in frames {
+        if image.dimensions() != first.dimensions() {
+            return Err(Box::new(io::Error::other(
+                "animation frames differ in size",
+            )));
+        }
+        writer.set_frame_delay(*numerator, *denominator)?;
+        writer.write_image_data(image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(buffer)
+}
+
+/// Encodes frames as an endlessly looping GIF. GIF delays are in hundredths of a
+/// second, and transparency is on or off per pixel.
+pub fn encode_gif(frames: &[(RgbaImage, Duration)]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut buffer, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.iter().map(|(image, duration)| {
+            Frame::from_parts(
+                image.clone(),
+                0,
+                0,
+                Delay::from_saturating_duration(*duration),
+            )
+        }))?;
+    }
+    Ok(buffer)
+}
+
+/// Encodes an image as a PNG stream.
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    image.write_to(
+        &mut std::io::Cursor::new(&mut buffer),
+        image::ImageFormat::Png,
+    )?;
+    Ok(buffer)
+}
+
+/// Centers an image on a transparent square, unless it is square already.
+pub fn square_image(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width == height {
+        return image.clone();
+    }
+    let size = width.max(height);
+    let mut canvas = RgbaImage::new(size, size);
+    image::imageops::overlay(
+        &mut canvas,
+        image,
+        i64::from((size - width) / 2),
+        i64::from((size - height) / 2),
+    );
+    canvas
+}
+
+/// The image of `sources` (square, of distinct sizes) for `size` pixels: the one of
+/// exactly that size, or else the smallest larger one, or else the largest,
+/// resampled.
+pub fn image_for_size(sources: &[RgbaImage], size: u32) -> Option<RgbaImage> {
+    let source = sources
+        .iter()
+        .filter(|image| image.width() >= size)
+        .min_by_key(|image| image.width())
+        .or_else(|| sources.iter().max_by_key(|image| image.width()))?;
+    if source.width() == size {
+        return Some(source.clone());
+    }
+    Some(image::imageops::resize(
+        source,
+        size,
+        size,
+        FilterType::Lanczos3,
+    ))
+}
@@ -0,0 +1,107 @@
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ini {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Ini {
+    /// Parses Windows INI text. Section and key lookups are case-insensitive, and
+    /// keys before the first section header land in an unnamed section.
+    pub fn parse(text: &str) -> Self {
+        let mut sections: Vec<(String, Vec<(String, String)>)> = vec![(String::new(), Vec::new())];
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.push((name.trim().to_owned(), Vec::new()));
+            } else if let Some((key, value)) = line.split_once('=')
+                && let Some((_, entries)) = sections.last_mut()
+            {
+                entries.push((key.trim().to_owned(), value.trim().to_owned()));
+            }
+        }
+
+        Ini { sections }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::parse(&decode_text(bytes))
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(section))
+            .flat_map(|(_, entries)| entries)
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Decodes a text file written by Windows: UTF-16 with a BOM, UTF-8, or ANSI.
+/// ANSI code pages are not available offline, so non-UTF-8 bytes are mapped as Latin-1.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let wide: Vec<u16> = bytes.chunks_exact(2).map(|c| from([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&wide)
+    };
+
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        // UTF-16LE without a BOM still has a NUL high byte for ASCII characters
+        [_, 0, ..] if bytes.len().is_multiple_of(2) => utf16(bytes, u16::from_le_bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_owned(),
+            Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le_with_bom(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn looks_sections_and_keys_up_ignoring_case() {
+        let ini = Ini::parse("top=1\n; comment\n[Shell]\r\n Command = 2 \n\n[Other]\nCommand=3\n");
+        assert_eq!(ini.get("", "TOP"), Some("1"));
+        assert_eq!(ini.get("shell", "command"), Some("2"));
+        assert_eq!(ini.get("OTHER", "Command"), Some("3"));
+        assert_eq!(ini.get("Shell", "missing"), None);
+    }
+
+    #[test]
+    fn treats_empty_values_as_missing() {
+        let ini = Ini::parse("[Section]\nKey=\n");
+        assert_eq!(ini.get("Section", "Key"), None);
+    }
+
+    #[test]
+    fn decodes_windows_encodings() {
+        assert_eq!(decode_text(&utf16le_with_bom("[A]\r\nB=ü")), "[A]\r\nB=ü");
+        assert_eq!(decode_text(&[0xFE, 0xFF, 0, b'x']), "x");
+        assert_eq!(decode_text(b"\xEF\xBB\xBFx=\xC3\xBC"), "x=ü");
+        assert_eq!(decode_text(&utf16le_with_bom("[A]")[2..]), "[A]");
+        assert_eq!(decode_text(b"x=\xFC"), "x=ü");
+    }
+
+    #[test]
+    fn parses_utf16_files() {
+        let ini = Ini::from_bytes(&utf16le_with_bom("[.ShellClassInfo]\r\nIconIndex=-3\r\n"));
+        assert_eq!(ini.get(".ShellClassInfo", "IconIndex"), Some("-3"));
+    }
+}
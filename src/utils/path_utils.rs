@@ -0,0 +1,114 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Expands `%NAME%` references, leaving unknown variables untouched like
+/// `ExpandEnvironmentStringsW` does.
+pub fn expand_env_vars(value: &str) -> String {
+    expand_env_vars_with(value, |name| env::var(name).ok())
+}
+
+pub fn expand_env_vars_with<F>(value: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) if end > 0 => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(v) => expanded.push_str(&v),
+                    None => {
+                        expanded.push('%');
+                        expanded.push_str(name);
+                        expanded.push('%');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                expanded.push('%');
+                rest = after;
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Splits an icon location such as `%SystemRoot%\system32\shell32.dll,-16770` into its
+/// path and index. A missing index means the first icon.
+pub fn split_icon_location(location: &str) -> (String, i32) {
+    let location = unquote(location);
+    if let Some((path, index)) = location.rsplit_once(',')
+        && let Ok(index) = index.trim().parse()
+    {
+        return (unquote(path).to_owned(), index);
+    }
+    (unquote(location).to_owned(), 0)
+}
+
+pub fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+pub fn is_relative_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[1] == b':';
+    !has_drive && !path.starts_with('\\') && !path.starts_with('/')
+}
+
+/// Resolves an icon path against `base_dir` when it is relative and exists there.
+/// Other relative names, like `shell32.dll`, are left for the system search path, and
+/// `%NAME%` references are left unexpanded: an offline Windows root expands them with
+/// its own environment rather than the host's.
+pub fn resolve_icon_path(path: &str, base_dir: Option<&Path>) -> PathBuf {
+    if let Some(base_dir) = base_dir
+        && is_relative_windows_path(path)
+        && !path.starts_with('%')
+    {
+        let candidate = base_dir.join(path.replace('\\', "/"));
+        if candidate.exists() {
+            return candidate;
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// Finds `name` in `folder` ignoring case, as Windows file systems do.
+pub fn find_file_ignore_case(folder: &Path, name: &str) -> Option<PathBuf> {
+    find_entry_ignore_case(folder, name).filter(|path| path.is_file())
+}
+
+pub fn find_dir_ignore_case(folder: &Path, name: &str) -> Option<PathBuf> {
+    find_entry_ignore_case(folder, name).filter(|path| path.is_dir())
+}
+
+fn find_entry_ignore_case(folder: &Path, name: &str) -> Option<PathBuf> {
+    let exact = folder.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+
+    fs::read_dir(folder)
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
@@ -1,4 +1,5 @@
 use std::{
+    env,
     error::Error,
     ffi::OsStr,
     fs,
@@ -6,7 +7,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use glob::glob;
+use glob::{Pattern, glob};
 use image::RgbaImage;
 
 use crate::utils::image_utils::{icon_file_to_base64, icon_file_to_image};
@@ -14,10 +15,9 @@ use crate::utils::image_utils::{icon_file_to_base64, icon_file_to_image};
 pub fn get_uwp_icon(file_path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
     let icon_path = get_icon_file_path(file_path)?;
     let rgba_image = icon_file_to_image(&icon_path).map_err(|e| {
-        io::Error::new(
-            ErrorKind::Other,
-            format!("Failed to get icon image for path: '{file_path:?}'\n{e}"),
-        )
+        io::Error::other(format!(
+            "Failed to get icon image for path: '{file_path:?}'\n{e}"
+        ))
     })?;
     Ok(rgba_image)
 }
@@ -25,12 +25,99 @@ pub fn get_uwp_icon(file_path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
 pub fn get_uwp_icon_base64(file_path: &Path) -> Result<String, Box<dyn Error>> {
     let icon_path = get_icon_file_path(file_path)?;
     let base64 = icon_file_to_base64(&icon_path).map_err(|e| {
+        io::Error::other(format!(
+            "Failed to get icon base64 for path: '{file_path:?}'\n{e}"
+        ))
+    })?;
+    Ok(base64)
+}
+
+pub fn get_uwp_icon_by_app_id(app_user_model_id: &str) -> Result<RgbaImage, Box<dyn Error>> {
+    let package_folder = find_package_folder(app_user_model_id)?;
+    let icon_path = get_package_icon_file_path(&package_folder)?;
+    icon_file_to_image(&icon_path)
+}
+
+pub fn get_uwp_icon_base64_by_app_id(app_user_model_id: &str) -> Result<String, Box<dyn Error>> {
+    let package_folder = find_package_folder(app_user_model_id)?;
+    let icon_path = get_package_icon_file_path(&package_folder)?;
+    icon_file_to_base64(&icon_path)
+}
+
+fn find_package_folder(app_user_model_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let program_files = env::var("ProgramFiles").unwrap_or_else(|_| "C:\\Program Files".into());
+    let system_root = env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".into());
+    find_package_folder_in(
+        app_user_model_id,
+        Path::new(&program_files),
+        Path::new(&system_root),
+    )
+}
+
+pub fn find_package_folder_in(
+    app_user_model_id: &str,
+    program_files: &Path,
+    system_root: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    // An AUMID is "<PackageFamilyName>!<AppId>", and a family name is "<Name>_<PublisherId>"
+    let package_family_name = app_user_model_id
+        .split('!')
+        .next()
+        .unwrap_or(app_user_model_id);
+    let (name, publisher_id) = package_family_name.rsplit_once('_').ok_or_else(|| {
         io::Error::new(
-            ErrorKind::Other,
-            format!("Failed to get icon base64 for path: '{file_path:?}'\n{e}"),
+            ErrorKind::InvalidInput,
+            format!("not a packaged app id: '{app_user_model_id}'"),
         )
     })?;
-    Ok(base64)
+
+    let patterns = [
+        format!(
+            "{}/WindowsApps/{name}_*_{publisher_id}",
+            Pattern::escape(&program_files.to_string_lossy())
+        ),
+        format!(
+            "{}/SystemApps/{name}_{publisher_id}",
+            Pattern::escape(&system_root.to_string_lossy())
+        ),
+    ];
+
+    let mut package_folders = Vec::new();
+    for pattern in patterns {
+        for folder in glob(&pattern)?.filter_map(Result::ok) {
+            let folder_name = folder
+                .file_name()
+                .and_then(OsStr::to_str)
+                .unwrap_or_default();
+            // Skip resource packages, which carry no applications
+            let is_resource_package =
+                folder_name.contains("_split.") || folder_name.contains("_~_");
+            if !is_resource_package && folder.join("AppxManifest.xml").is_file() {
+                let version = package_version(folder_name, name);
+                package_folders.push((folder, version));
+            }
+        }
+    }
+
+    package_folders
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(folder, _)| folder)
+        .ok_or_else(|| {
+            Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                format!("no installed package found for: '{app_user_model_id}'"),
+            )) as Box<dyn Error>
+        })
+}
+
+fn package_version(package_full_name: &str, name: &str) -> Vec<u32> {
+    package_full_name
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .and_then(|rest| rest.split('_').next())
+        .map(|version| version.split('.').filter_map(|v| v.parse().ok()).collect())
+        .unwrap_or_default()
 }
 
 fn get_icon_file_path(app_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
@@ -47,13 +134,17 @@ fn get_icon_file_path(app_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
             format!("failed to get parent directory: '{app_path:?}'"),
         )
     })?;
+    get_package_icon_file_path(package_folder)
+}
+
+pub fn get_package_icon_file_path(package_folder: &Path) -> Result<PathBuf, Box<dyn Error>> {
     let manifest_path = package_folder.join("AppxManifest.xml");
     if manifest_path.exists() {
-        let manifest_content = fs::read_to_string(&manifest_path).map_err(|_| {
-            io::Error::new(ErrorKind::Other, "could not to read the AppxManifest.xml.")
-        })?;
+        let manifest_content = fs::read_to_string(&manifest_path)
+            .map_err(|_| io::Error::other("could not to read the AppxManifest.xml."))?;
 
-        let icon_path = extract_icon_path(&manifest_content)?;
+        // Manifests use Windows separators, which only Windows accepts in paths
+        let icon_path = extract_icon_path(&manifest_content)?.replace('\\', "/");
         let icon_full_path = package_folder.join(icon_path);
         if icon_full_path.exists() {
             Ok(icon_full_path)
@@ -62,10 +153,9 @@ fn get_icon_file_path(app_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
         }
     } else {
         fuzzy_get_icon_file_path(package_folder).map_err(|e| {
-            Box::new(io::Error::new(
-                ErrorKind::Other,
-                format!("AppxManifest.xml does not exist and {e}"),
-            )) as Box<dyn Error>
+            Box::new(io::Error::other(format!(
+                "AppxManifest.xml does not exist and {e}"
+            ))) as Box<dyn Error>
         })
     }
 }
@@ -75,12 +165,12 @@ fn extract_icon_path(manifest_content: &str) -> Result<String, Box<dyn Error>> {
     let start_tag = "<Logo>";
     let end_tag = "</Logo>";
 
-    if let Some(start) = manifest_content.find(start_tag) {
-        if let Some(end) = manifest_content.find(end_tag) {
-            let start_pos = start + start_tag.len();
-            let icon_path = &manifest_content[start_pos..end];
-            return Ok(icon_path.trim().to_string());
-        }
+    if let Some(start) = manifest_content.find(start_tag)
+        && let Some(end) = manifest_content.find(end_tag)
+    {
+        let start_pos = start + start_tag.len();
+        let icon_path = &manifest_content[start_pos..end];
+        return Ok(icon_path.trim().to_string());
     }
 
     Err(Box::new(io::Error::new(
@@ -0,0 +1,421 @@
+use std::{error::Error, path::Path};
+
+use image::RgbaImage;
+
+use crate::{
+    PeFile, RT_VERSION, ResourceId,
+    utils::binary_utils::{decode_utf16le, invalid_data, read_u16, read_u32, truncated},
+};
+
+const VS_FFI_SIGNATURE: u32 = 0xFEEF_04BD;
+const FIXED_FILE_INFO_SIZE: usize = 52;
+const CP_UNICODE: u16 = 1200;
+const CP_WINDOWS_1252: u16 = 1252;
+
+/// An icon together with the version resource of the file it stands for.
+#[derive(Clone, Debug)]
+pub struct IconWithMetadata {
+    pub image: RgbaImage,
+    /// `None` for files without a version resource.
+    pub version_info: Option<VersionInfo>,
+}
+
+/// `VS_FIXEDFILEINFO`: the binary part of a version resource.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FixedFileInfo {
+    pub file_version: [u16; 4],
+    pub product_version: [u16; 4],
+    pub file_flags_mask: u32,
+    pub file_flags: u32,
+    pub file_os: u32,
+    pub file_type: u32,
+    pub file_subtype: u32,
+    pub file_date: u64,
+}
+
+impl FixedFileInfo {
+    /// `major.minor.build.revision`.
+    pub fn file_version_string(&self) -> String {
+        format_version(self.file_version)
+    }
+
+    pub fn product_version_string(&self) -> String {
+        format_version(self.product_version)
+    }
+}
+
+/// One `StringFileInfo` table, for one language and code page.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringTable {
+    pub language: u16,
+    pub code_page: u16,
+    pub strings: Vec<(String, String)>,
+}
+
+impl StringTable {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A `VS_VERSIONINFO` resource (`RT_VERSION`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub fixed: Option<FixedFileInfo>,
+    pub string_tables: Vec<StringTable>,
+    /// `VarFileInfo\Translation`: the language and code page pairs the file declares.
+    pub translations: Vec<(u16, u16)>,
+}
+
+impl VersionInfo {
+    /// Reads the version resource of a PE file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_pe(&PeFile::open(path)?)
+    }
+
+    pub fn from_pe(pe: &PeFile) -> Result<Self, Box<dyn Error>> {
+        let version = ResourceId::Id(RT_VERSION);
+        let resource = pe
+            .resources()
+            .iter()
+            .find(|resource| resource.type_id.matches(&version))
+            .ok_or_else(|| invalid_data("no version resource"))?;
+        Self::from_bytes(pe.resource_data(resource))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let root = Block::parse(data, 0)?.ok_or_else(|| truncated("VS_VERSIONINFO"))?;
+        if root.key != "VS_VERSION_INFO" {
+            return Err(invalid_data("not a VS_VERSIONINFO resource"));
+        }
+
+        let mut info = VersionInfo {
+            fixed: parse_fixed_file_info(root.value),
+            ..VersionInfo::default()
+        };
+        for child in root.children(data) {
+            match child.key.as_str() {
+                "StringFileInfo" => {
+                    for table in child.children(data) {
+                        info.string_tables.push(parse_string_table(data, &table));
+                    }
+                }
+                "VarFileInfo" => {
+                    for var in child.children(data) {
+                        if var.key == "Translation" {
+                            info.translations
+                                .extend(var.value.chunks_exact(4).map(|pair| {
+                                    (
+                                        u16::from_le_bytes([pair[0], pair[1]]),
+                                        u16::from_le_bytes([pair[2], pair[3]]),
+                                    )
+                                }));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    /// Looks `key` up the way `VerQueryValue` callers conventionally fall back: the
+    /// declared translations in order, then US English and language-neutral tables,
+    /// then any table that has it. Empty values are skipped.
+    pub fn string(&self, key: &str) -> Option<&str> {
+        let fallbacks = [
+            (0x0409, CP_UNICODE),
+            (0x0409, CP_WINDOWS_1252),
+            (0x0409, 0),
+            (0x0000, CP_UNICODE),
+            (0x0000, CP_WINDOWS_1252),
+            (0x0000, 0),
+        ];
+        self.translations
+            .iter()
+            .chain(fallbacks.iter())
+            .filter_map(|&(language, code_page)| self.string_for(key, language, code_page))
+            .chain(self.string_tables.iter().filter_map(|table| table.get(key)))
+            .find(|value| !value.is_empty())
+    }
+
+    /// `key` from the table of exactly one language and code page.
+    pub fn string_for(&self, key: &str, language: u16, code_page: u16) -> Option<&str> {
+        self.string_tables
+            .iter()
+            .find(|table| table.language == language && table.code_page == code_page)
+            .and_then(|table| table.get(key))
+    }
+
+    pub fn file_description(&self) -> Option<&str> {
+        self.string("FileDescription")
+    }
+
+    pub fn product_name(&self) -> Option<&str> {
+        self.string("ProductName")
+    }
+
+    pub fn company_name(&self) -> Option<&str> {
+        self.string("CompanyName")
+    }
+
+    /// The `FileVersion` string, or the fixed file version when there is none.
+    pub fn file_version(&self) -> Option<String> {
+        self.string("FileVersion")
+            .map(str::to_owned)
+            .or_else(|| self.fixed.map(|fixed| fixed.file_version_string()))
+    }
+}
+
+/// A version resource node: `wLength`, `wValueLength`, `wType`, a null-terminated
+/// UTF-16 key, the value and the child nodes, each aligned to 32 bits.
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    is_text: bool,
+    children_start: usize,
+    end: usize,
+}
+
+impl<'a> Block<'a> {
+    fn parse(data: &'a [u8], offset: usize) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(length) = read_u16(data, offset) else {
+            return Ok(None);
+        };
+        let length = length as usize;
+        if length < 6 {
+            return Ok(None);
+        }
+        let end = (offset + length).min(data.len());
+        if offset + 6 > end {
+            return Err(truncated("version block"));
+        }
+        let value_length = read_u16(data, offset + 2).ok_or_else(|| truncated("version block"))?;
+        let is_text = read_u16(data, offset + 4).ok_or_else(|| truncated("version block"))? == 1;
+
+        let key_start = offset + 6;
+        let mut key_end = key_start;
+        while key_end + 1 < end && (data[key_end] != 0 || data[key_end + 1] != 0) {
+            key_end += 2;
+        }
+        let key = decode_utf16le(&data[key_start..key_end]);
+
+        let value_start = align4(key_end + 2).min(end);
+        // Text lengths are in characters, though some compilers write bytes
+        let value_size = if is_text {
+            value_length as usize * 2
+        } else {
+            value_length as usize
+        };
+        let value_end = (value_start + value_size).min(end);
+        Ok(Some(Block {
+            key,
+            value: &data[value_start..value_end],
+            is_text,
+            children_start: align4(value_end),
+            end,
+        }))
+    }
+
+    fn children(&self, data: &'a [u8]) -> Vec<Block<'a>> {
+        let mut children = Vec::new();
+        let mut offset = self.children_start;
+        while offset < self.end {
+            match Block::parse(&data[..self.end], offset) {
+                Ok(Some(child)) => {
+                    offset = align4(child.end);
+                    children.push(child);
+                }
+                _ => break,
+            }
+        }
+        children
+    }
+
+    fn text(&self) -> String {
+        if !self.is_text {
+            return String::new();
+        }
+        let text = decode_utf16le(self.value);
+        match text.find('\0') {
+            Some(end) => text[..end].to_owned(),
+            None => text,
+        }
+    }
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn parse_fixed_file_info(value: &[u8]) -> Option<FixedFileInfo> {
+    if value.len() < FIXED_FILE_INFO_SIZE || read_u32(value, 0)? != VS_FFI_SIGNATURE {
+        return None;
+    }
+    let dword = |index: usize| read_u32(value, index * 4).unwrap_or_default();
+    let version = |ms: u32, ls: u32| [(ms >> 16) as u16, ms as u16, (ls >> 16) as u16, ls as u16];
+    Some(FixedFileInfo {
+        file_version: version(dword(2), dword(3)),
+        product_version: version(dword(4), dword(5)),
+        file_flags_mask: dword(6),
+        file_flags: dword(7),
+        file_os: dword(8),
+        file_type: dword(9),
+        file_subtype: dword(10),
+        file_date: (u64::from(dword(11)) << 32) | u64::from(dword(12)),
+    })
+}
+
+/// Tables are keyed by eight hex digits: the language, then the code page.
+fn parse_string_table(data: &[u8], table: &Block) -> StringTable {
+    let code = u32::from_str_radix(&table.key, 16).unwrap_or_default();
+    StringTable {
+        language: (code >> 16) as u16,
+        code_page: code as u16,
+        strings: table
+            .children(data)
+            .iter()
+            .map(|string| (string.key.clone(), string.text()))
+            .collect(),
+    }
+}
+
+fn format_version(version: [u16; 4]) -> String {
+    let [major, minor, build, revision] = version;
+    format!("{major}.{minor}.{build}.{revision}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16z(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain(Some(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    /// A version block; text values are counted in characters, binary ones in bytes.
+    fn block(key: &str, value: &[u8], is_text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0; 6];
+        data.extend(utf16z(key));
+        data.resize(align4(data.len()), 0);
+        data.extend_from_slice(value);
+        for child in children {
+            data.resize(align4(data.len()), 0);
+            data.extend_from_slice(child);
+        }
+        let value_length = if is_text {
+            value.len() / 2
+        } else {
+            value.len()
+        };
+        let length = data.len() as u16;
+        data[..2].copy_from_slice(&length.to_le_bytes());
+        data[2..4].copy_from_slice(&(value_length as u16).to_le_bytes());
+        data[4..6].copy_from_slice(&u16::from(is_text).to_le_bytes());
+        data
+    }
+
+    fn string(key: &str, value: &str) -> Vec<u8> {
+        block(key, &utf16z(value), true, &[])
+    }
+
+    fn version_info() -> Vec<u8> {
+        let fixed: Vec<u8> = [
+            VS_FFI_SIGNATURE,
+            0x1_0000,
+            10 << 16,
+            (19041 << 16) | 1,
+            10 << 16,
+            19041 << 16,
+            0x3F,
+            0,
+            0x4_0004,
+            1,
+            0,
+            0,
+            0,
+        ]
+        .iter()
+        .flat_map(|dword| dword.to_le_bytes())
+        .collect();
+        let us_english = block(
+            "040904B0",
+            &[],
+            true,
+            &[
+                string("CompanyName", "Contoso"),
+                string("FileDescription", "Notepad"),
+                string("Comments", ""),
+            ],
+        );
+        let neutral = block(
+            "000004B0",
+            &[],
+            true,
+            &[string("ProductName", "Editor"), string("Comments", "Plain")],
+        );
+        let translation = block("Translation", &[0x07, 0x04, 0xB0, 0x04], false, &[]);
+        block(
+            "VS_VERSION_INFO",
+            &fixed,
+            false,
+            &[
+                block("StringFileInfo", &[], true, &[us_english, neutral]),
+                block("VarFileInfo", &[], true, &[translation]),
+            ],
+        )
+    }
+
+    #[test]
+    fn reads_the_fixed_file_info() {
+        let info = VersionInfo::from_bytes(&version_info()).unwrap();
+        let fixed = info.fixed.unwrap();
+        assert_eq!(fixed.file_version, [10, 0, 19041, 1]);
+        assert_eq!(fixed.file_version_string(), "10.0.19041.1");
+        assert_eq!(fixed.product_version_string(), "10.0.19041.0");
+        assert_eq!((fixed.file_os, fixed.file_type), (0x4_0004, 1));
+        // No FileVersion string, so the fixed version stands in
+        assert_eq!(info.file_version().as_deref(), Some("10.0.19041.1"));
+    }
+
+    #[test]
+    fn reads_string_and_var_file_info() {
+        let info = VersionInfo::from_bytes(&version_info()).unwrap();
+        let tables: Vec<(u16, u16, usize)> = info
+            .string_tables
+            .iter()
+            .map(|t| (t.language, t.code_page, t.strings.len()))
+            .collect();
+        assert_eq!(tables, [(0x0409, 1200, 3), (0x0000, 1200, 2)]);
+        assert_eq!(info.string_tables[0].get("companyname"), Some("Contoso"));
+        assert_eq!(info.translations, [(0x0407, 1200)]);
+
+        // German is declared but missing, so US English comes first
+        assert_eq!(info.file_description(), Some("Notepad"));
+        assert_eq!(info.product_name(), Some("Editor"));
+        assert_eq!(info.string("Comments"), Some("Plain"));
+        assert_eq!(info.string_for("Comments", 0x0409, 1200), Some(""));
+    }
+
+    #[test]
+    fn rejects_truncated_resources() {
+        let data = version_info();
+        for length in 0..data.len() {
+            // Cut short anywhere, the parser keeps what it can read
+            let _ = VersionInfo::from_bytes(&data[..length]);
+        }
+        assert!(VersionInfo::from_bytes(&[]).is_err());
+        assert!(VersionInfo::from_bytes(&data[..4]).is_err());
+        assert!(VersionInfo::from_bytes(&block("VS_VERSION", &[], false, &[])).is_err());
+
+        let cut = VersionInfo::from_bytes(&data[..data.len() - 24]).unwrap();
+        assert!(cut.translations.is_empty());
+        assert!(cut.fixed.is_some());
+    }
+}
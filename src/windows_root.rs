@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsStr,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::RgbaImage;
+
+use crate::{
+    AutorunInf, DllIcon, DriveType, ExtractedIcon, FileAssociations, IconSource, IndirectString,
+    MAX_SHELL_FILE_HOPS, RegistryHive, RegistrySource, StockIcon, has_own_icon,
+    shell_file_icon_source,
+    utils::{
+        image_utils::icon_file_to_image,
+        path_utils::{expand_env_vars_with, find_file_ignore_case, is_relative_windows_path},
+    },
+    uwp_apps::{find_package_folder_in, get_package_icon_file_path, get_uwp_icon},
+};
+
+const DEFAULT_ENVIRONMENT: [(&str, &str); 11] = [
+    ("SystemDrive", "C:"),
+    ("SystemRoot", "%SystemDrive%\\Windows"),
+    ("windir", "%SystemRoot%"),
+    ("ProgramFiles", "%SystemDrive%\\Program Files"),
+    ("ProgramFiles(x86)", "%SystemDrive%\\Program Files (x86)"),
+    ("ProgramW6432", "%ProgramFiles%"),
+    ("CommonProgramFiles", "%ProgramFiles%\\Common Files"),
+    (
+        "CommonProgramFiles(x86)",
+        "%ProgramFiles(x86)%\\Common Files",
+    ),
+    ("ProgramData", "%SystemDrive%\\ProgramData"),
+    ("ALLUSERSPROFILE", "%ProgramData%"),
+    ("PUBLIC", "%SystemDrive%\\Users\\Public"),
+];
+
+/// A Windows installation that is not the running system, such as a disk image
+/// mounted at `/mnt/win`. Drive letters, environment variables, the module search
+/// path and package folders all resolve below it instead of on the live machine.
+#[derive(Clone, Debug)]
+pub struct WindowsRoot {
+    drives: Vec<(char, PathBuf)>,
+    environment: HashMap<String, String>,
+    build: Option<u32>,
+    file_associations: Option<Arc<FileAssociations>>,
+}
+
+impl WindowsRoot {
+    /// `root` is the system volume, the folder holding `Windows`; it becomes `C:`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        let mut windows_root = WindowsRoot {
+            drives: vec![('c', root.as_ref().to_path_buf())],
+            environment: HashMap::new(),
+            build: None,
+            file_associations: None,
+        };
+        for (name, value) in DEFAULT_ENVIRONMENT {
+            windows_root.set_env_var(name, value);
+        }
+        windows_root
+    }
+
+    /// Maps another drive letter to a host folder.
+    pub fn with_drive<P: AsRef<Path>>(mut self, letter: char, path: P) -> Self {
+        let letter = letter.to_ascii_lowercase();
+        self.drives.retain(|(drive, _)| *drive != letter);
+        self.drives.push((letter, path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Sets an environment variable; `%NAME%` references in `value` are expanded
+    /// against the variables already set.
+    pub fn with_env_var(mut self, name: &str, value: &str) -> Self {
+        self.set_env_var(name, value);
+        self
+    }
+
+    /// Sets the profile variables (`USERPROFILE`, `APPDATA`, ...) for `user_name`.
+    pub fn with_user(self, user_name: &str) -> Self {
+        self.with_env_var("USERNAME", user_name)
+            .with_env_var("USERPROFILE", &format!("%SystemDrive%\\Users\\{user_name}"))
+            .with_env_var("APPDATA", "%USERPROFILE%\\AppData\\Roaming")
+            .with_env_var("LOCALAPPDATA", "%USERPROFILE%\\AppData\\Local")
+    }
+
+    /// The Windows build number of the installation, e.g. `22631`, which decides
+    /// where stock icons are looked up.
+    pub fn with_build(mut self, build: u32) -> Self {
+        self.build = Some(build);
+        self
+    }
+
+    pub fn with_file_associations(mut self, file_associations: FileAssociations) -> Self {
+        self.file_associations = Some(Arc::new(file_associations));
+        self
+    }
+
+    /// Reads file associations from the hives under the root: `SOFTWARE`, and with a
+    /// user set, their `NTUSER.DAT` and `UsrClass.dat`. The build number is taken
+    /// from `SOFTWARE` unless already set.
+    pub fn with_registry_hives(mut self) -> Result<Self, Box<dyn Error>> {
+        let hive = |path: &str| {
+            self.to_host_path(path)
+                .filter(|path| path.is_file())
+                .map(RegistryHive::open)
+                .transpose()
+        };
+
+        let mut file_associations = FileAssociations::new();
+        let mut build = self.build;
+        if let Some(software) = hive("%SystemRoot%\\System32\\config\\SOFTWARE")? {
+            build =
+                build.or_else(|| read_build(&software, "Microsoft\\Windows NT\\CurrentVersion"));
+            file_associations = file_associations.with_software(software);
+        }
+        if self.env_var("USERPROFILE").is_some() {
+            if let Some(ntuser) = hive("%USERPROFILE%\\NTUSER.DAT")? {
+                file_associations = file_associations.with_ntuser(ntuser);
+            }
+            if let Some(usrclass) = hive("%LOCALAPPDATA%\\Microsoft\\Windows\\UsrClass.dat")? {
+                file_associations = file_associations.with_usrclass(usrclass);
+            }
+        }
+        self.build = build;
+        Ok(self.with_file_associations(file_associations))
+    }
+
+    /// The host folder of the system volume.
+    pub fn system_drive(&self) -> &Path {
+        self.drive('c').unwrap_or(Path::new(""))
+    }
+
+    pub fn build(&self) -> Option<u32> {
+        self.build
+    }
+
+    pub fn file_associations(&self) -> Option<&FileAssociations> {
+        self.file_associations.as_deref()
+    }
+
+    pub fn env_var(&self, name: &str) -> Option<&str> {
+        self.environment
+            .get(&name.to_ascii_uppercase())
+            .map(String::as_str)
+    }
+
+    /// Expands `%NAME%` references with the root's environment, not the host's.
+    pub fn expand_env_vars(&self, value: &str) -> String {
+        expand_env_vars_with(value, |name| self.env_var(name).map(str::to_owned))
+    }
+
+    /// Maps a Windows path to the host path below its drive, matching components
+    /// case-insensitively. `Sysnative` is `System32`, as seen by 64-bit processes.
+    /// Host paths already below a mapped drive are accepted too; other paths are not
+    /// in the root.
+    pub fn to_host_path(&self, windows_path: &str) -> Option<PathBuf> {
+        let path = self.expand_env_vars(windows_path.trim());
+        let path = path.strip_prefix("\\\\?\\").unwrap_or(&path);
+
+        let host_path = Path::new(path);
+        if let Some((root, rest)) = self
+            .drives
+            .iter()
+            .find_map(|(_, root)| Some((root, host_path.strip_prefix(root).ok()?)))
+        {
+            return Some(join_ignore_case(root, &rest.to_string_lossy()));
+        }
+
+        match path.as_bytes() {
+            [letter, b':', ..] if letter.is_ascii_alphabetic() => {
+                let drive = self.drive(char::from(*letter))?;
+                Some(join_ignore_case(drive, &path[2..]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds a module the way the loader would for a bare name: in `System32`, then
+    /// `SysWOW64` for 32-bit only modules, then the Windows folder.
+    pub fn find_module(&self, name: &str) -> Option<PathBuf> {
+        let name = self.expand_env_vars(name.trim());
+        if !is_relative_windows_path(&name) {
+            return self.to_host_path(&name).filter(|path| path.is_file());
+        }
+        [
+            "%SystemRoot%\\System32",
+            "%SystemRoot%\\SysWOW64",
+            "%SystemRoot%",
+        ]
+        .into_iter()
+        .filter_map(|folder| self.to_host_path(folder))
+        .find_map(|folder| find_file_ignore_case(&folder, &name))
+    }
+
+    /// The installed package folder for an AUMID or package family name, under
+    /// `Program Files\WindowsApps` or `Windows\SystemApps`.
+    pub fn package_folder(&self, app_user_model_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let folder = |path| self.to_host_path(path).unwrap_or_default();
+        find_package_folder_in(
+            app_user_model_id,
+            &folder("%ProgramFiles%"),
+            &folder("%SystemRoot%"),
+        )
+    }
+
+    /// Newest build when unknown, so stock icons resolve as on current Windows.
+    pub(crate) fn extract(&self, dll_icon: &DllIcon) -> Result<ExtractedIcon, Box<dyn Error>> {
+        dll_icon.extract_with(self.build.unwrap_or(u32::MAX), |path| self.locate(path))
+    }
+
+    pub(crate) fn load_string(
+        &self,
+        indirect: &IndirectString,
+        languages: &[u16],
+    ) -> Result<String, Box<dyn Error>> {
+        indirect.load_with(languages, |path| self.locate(path))
+    }
+
+    fn locate(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.to_string_lossy();
+        if is_relative_windows_path(&path) && !path.starts_with('%') {
+            return self.find_module(&path);
+        }
+        self.to_host_path(&path).filter(|path| path.is_file())
+    }
+
+    fn drive(&self, letter: char) -> Option<&Path> {
+        let letter = letter.to_ascii_lowercase();
+        self.drives
+            .iter()
+            .find(|(drive, _)| *drive == letter)
+            .map(|(_, path)| path.as_path())
+    }
+
+    fn set_env_var(&mut self, name: &str, value: &str) {
+        let value = self.expand_env_vars(value);
+        self.environment.insert(name.to_ascii_uppercase(), value);
+    }
+}
+
+/// Reads `CurrentBuildNumber` from a `Windows NT\CurrentVersion` key.
+pub(crate) fn read_build(registry: &dyn RegistrySource, key_path: &str) -> Option<u32> {
+    registry
+        .string_value(key_path, "CurrentBuildNumber")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Joins a Windows path below `drive`. `..` stops at the drive, as it does at the root
+/// of a volume, so that paths cannot leave the root.
+fn join_ignore_case(drive: &Path, path: &str) -> PathBuf {
+    let components = path
+        .split(['\\', '/'])
+        // Windows names cannot contain `:`, and joining `D:` would replace the path
+        .filter(|part| !part.is_empty() && *part != "." && !part.contains(':'));
+
+    let mut path = drive.to_path_buf();
+    for component in components {
+        if component == ".." {
+            if path != drive {
+                path.pop();
+            }
+            continue;
+        }
+        let component = if component.eq_ignore_ascii_case("Sysnative") {
+            "System32"
+        } else {
+            component
+        };
+        let exact = path.join(component);
+        path = if exact.exists() {
+            exact
+        } else {
+            fs::read_dir(&path)
+                .ok()
+                .and_then(|entries| {
+                    entries.filter_map(Result::ok).find(|entry| {
+                        entry
+                            .file_name()
+                            .to_string_lossy()
+                            .eq_ignore_ascii_case(component)
+                    })
+                })
+                .map(|entry| entry.path())
+                .unwrap_or(exact)
+        };
+    }
+    path
+}
+
+fn is_packaged_app(path: &Path) -> bool {
+    path.components().any(|c| {
+        c.as_os_str()
+            .eq_ignore_ascii_case(OsStr::new("WindowsApps"))
+    })
+}
+
+pub fn get_root_icon(root: &WindowsRoot, path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
+    root_icon(root, path, MAX_SHELL_FILE_HOPS)
+}
+
+/// `hops` is how many more shell files may be followed, as in [`crate::get_icon_by_path`].
+fn root_icon(root: &WindowsRoot, path: &Path, hops: usize) -> Result<RgbaImage, Box<dyn Error>> {
+    let host_path = root
+        .to_host_path(&path.to_string_lossy())
+        .filter(|path| path.exists())
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!("not found in Windows root: {path:?}"),
+            )
+        })?;
+
+    if hops > 0
+        && let Some(source) = shell_file_icon_source(&host_path)
+        && let Ok(icon) = root_icon_by_source(root, source, hops - 1)
+    {
+        return Ok(icon);
+    }
+
+    if is_packaged_app(&host_path) {
+        get_uwp_icon(&host_path)
+    } else if host_path.is_dir() {
+        root.extract(&DllIcon::stock(StockIcon::Folder))
+            .map(|icon| icon.image)
+    } else if has_own_icon(&host_path) {
+        root.extract(&DllIcon::new().with_icon_location(&host_path, 0))
+            .map(|icon| icon.image)
+    } else {
+        match get_root_file_associations(root)?.icon_source_for_file(&host_path)? {
+            // A type whose files are their own icon, but not one of the known formats
+            IconSource::File(_) => Err(Box::new(io::Error::new(
+                ErrorKind::Unsupported,
+                format!("cannot read the icon of: {host_path:?}"),
+            ))),
+            source => root_icon_by_source(root, source, hops),
+        }
+    }
+}
+
+pub fn get_root_icon_by_source(
+    root: &WindowsRoot,
+    source: IconSource,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    root_icon_by_source(root, source, MAX_SHELL_FILE_HOPS)
+}
+
+fn root_icon_by_source(
+    root: &WindowsRoot,
+    source: IconSource,
+    hops: usize,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    match source {
+        IconSource::Resource(dll_icon) => root.extract(&dll_icon).map(|icon| icon.image),
+        IconSource::File(path) => root_icon(root, &path, hops),
+        IconSource::AppUserModelId(app_id) => {
+            let package_folder = root.package_folder(&app_id)?;
+            let icon_path = get_package_icon_file_path(&package_folder)?;
+            icon_file_to_image(&icon_path)
+        }
+        IconSource::Remote(url) => Err(Box::new(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("remote icons cannot be resolved offline: {url}"),
+        ))),
+    }
+}
+
+pub fn get_root_volume_icon(root: &WindowsRoot) -> Result<RgbaImage, Box<dyn Error>> {
+    let volume = root.system_drive();
+    if let Some(source) = AutorunInf::open(volume)
+        .ok()
+        .and_then(|autorun| autorun.icon_source(volume))
+        && let Ok(icon) = get_root_icon_by_source(root, source)
+    {
+        return Ok(icon);
+    }
+    get_root_icon_by_source(root, DriveType::Fixed.icon_source())
+}
+
+pub fn get_root_file_associations(root: &WindowsRoot) -> Result<&FileAssociations, Box<dyn Error>> {
+    root.file_associations().ok_or_else(|| {
+        Box::new(io::Error::new(
+            ErrorKind::NotFound,
+            "no file associations loaded for the Windows root",
+        )) as Box<dyn Error>
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("windows-root-{name}-{}", std::process::id()));
+        fs::create_dir_all(root.join("Windows").join("System32")).unwrap();
+        root
+    }
+
+    #[test]
+    fn maps_windows_paths_below_the_drive() {
+        let dir = temp_root("paths");
+        let root = WindowsRoot::new(&dir);
+        let system32 = dir.join("Windows").join("System32");
+
+        assert_eq!(
+            root.to_host_path("%SystemRoot%\\SYSTEM32\\shell32.dll"),
+            Some(system32.join("shell32.dll"))
+        );
+        assert_eq!(
+            root.to_host_path("c:/windows/sysnative/."),
+            Some(system32.clone())
+        );
+        assert_eq!(root.to_host_path("D:\\data"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_paths_inside_the_root() {
+        let dir = temp_root("escape");
+        let root = WindowsRoot::new(&dir);
+
+        assert_eq!(
+            root.to_host_path("C:\\Windows\\..\\..\\..\\etc\\passwd"),
+            Some(dir.join("etc").join("passwd"))
+        );
+        let host_path = format!("{}/Windows/../../../etc/passwd", dir.display());
+        assert_eq!(
+            root.to_host_path(&host_path),
+            Some(dir.join("etc").join("passwd"))
+        );
+        assert_eq!(root.to_host_path("C:\\D:\\x"), Some(dir.join("x")));
+        assert_eq!(root.to_host_path("/etc/passwd"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shortcut_loops_end() {
+        let dir = temp_root("loop");
+        fs::write(dir.join("self.lnk"), crate::tests::self_link("self.lnk")).unwrap();
+        fs::write(dir.join("a.lnk"), crate::tests::self_link("b.lnk")).unwrap();
+        fs::write(dir.join("b.lnk"), crate::tests::self_link("a.lnk")).unwrap();
+        let root = WindowsRoot::new(&dir);
+
+        assert!(get_root_icon(&root, Path::new("C:\\self.lnk")).is_err());
+        assert!(get_root_icon(&root, Path::new("C:\\a.lnk")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use glob::{MatchOptions, Pattern, glob_with};
+
+use crate::{
+    FileAssociations, RegistrySource, WindowsRoot, WineRegistry, utils::path_utils::unquote,
+    windows_root::read_build,
+};
+
+const ENVIRONMENT: &str = "System\\CurrentControlSet\\Control\\Session Manager\\Environment";
+const CURRENT_VERSION: &str = "Software\\Microsoft\\Windows\\CurrentVersion";
+const APP_PATHS: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\App Paths";
+const SHELL_FOLDERS: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\Shell Folders";
+
+/// A Wine (or Proton) prefix treated as a Windows installation: `C:\` is `drive_c`,
+/// other drives follow `dosdevices`, and `system.reg`/`user.reg` stand in for the
+/// machine and user registry.
+#[derive(Clone, Debug)]
+pub struct WinePrefix {
+    root: PathBuf,
+    system: WineRegistry,
+    user: WineRegistry,
+    windows_root: WindowsRoot,
+}
+
+impl WinePrefix {
+    /// Opens the prefix at `root`, the folder holding `drive_c` and `system.reg`
+    /// (`~/.wine`, or `pfx` inside a Proton compatdata folder).
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, Box<dyn Error>> {
+        let root = root.as_ref().to_path_buf();
+        if !root.join("drive_c").is_dir() {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                format!("not a Wine prefix: {root:?}"),
+            )));
+        }
+        let read_registry = |name| match root.join(name) {
+            path if path.is_file() => WineRegistry::open(path),
+            _ => Ok(WineRegistry::default()),
+        };
+        let system = read_registry("system.reg")?;
+        let user = read_registry("user.reg")?;
+
+        let windows_root = build_windows_root(&root, &system, &user);
+        Ok(WinePrefix {
+            root,
+            system,
+            user,
+            windows_root,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The prefix as a [`WindowsRoot`], for the `*_in_root` functions.
+    pub fn windows_root(&self) -> &WindowsRoot {
+        &self.windows_root
+    }
+
+    /// `HKEY_LOCAL_MACHINE`, from `system.reg`.
+    pub fn system_registry(&self) -> &WineRegistry {
+        &self.system
+    }
+
+    /// `HKEY_CURRENT_USER`, from `user.reg`.
+    pub fn user_registry(&self) -> &WineRegistry {
+        &self.user
+    }
+
+    pub fn env_var(&self, name: &str) -> Option<&str> {
+        self.windows_root.env_var(name)
+    }
+
+    /// Expands `%NAME%` references with the prefix's environment, not the host's.
+    pub fn expand_env_vars(&self, value: &str) -> String {
+        self.windows_root.expand_env_vars(value)
+    }
+
+    /// Maps a Windows path inside the prefix to the host path, matching components
+    /// case-insensitively.
+    pub fn to_unix_path(&self, windows_path: &str) -> Option<PathBuf> {
+        self.windows_root.to_host_path(windows_path)
+    }
+
+    /// Finds a module the way the loader would for a bare or relative name.
+    pub fn find_module(&self, name: &str) -> Option<PathBuf> {
+        self.windows_root.find_module(name)
+    }
+
+    /// The host path registered for `exe_name` under `App Paths`, per-user first.
+    pub fn app_path(&self, exe_name: &str) -> Option<PathBuf> {
+        let key = format!("{APP_PATHS}\\{exe_name}");
+        [&self.user, &self.system]
+            .into_iter()
+            .find_map(|registry| registry.string_value(&key, ""))
+            .and_then(|path| self.to_unix_path(unquote(&path)))
+    }
+
+    /// Every `.lnk` file in the common and per-user Start Menu `Programs` folders.
+    pub fn start_menu_shortcuts(&self) -> Vec<PathBuf> {
+        let folders = [
+            self.system.string_value(SHELL_FOLDERS, "Common Programs"),
+            self.user.string_value(SHELL_FOLDERS, "Programs"),
+            Some("%ProgramData%\\Microsoft\\Windows\\Start Menu\\Programs".to_owned()),
+            Some("%APPDATA%\\Microsoft\\Windows\\Start Menu\\Programs".to_owned()),
+        ];
+
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        let mut shortcuts: Vec<PathBuf> = folders
+            .into_iter()
+            .flatten()
+            .filter_map(|folder| self.to_unix_path(&folder))
+            .filter(|folder| folder.is_dir())
+            .filter_map(|folder| {
+                let pattern = format!("{}/**/*.lnk", Pattern::escape(&folder.to_string_lossy()));
+                glob_with(&pattern, options).ok()
+            })
+            .flat_map(|paths| paths.filter_map(Result::ok))
+            .collect();
+        shortcuts.sort();
+        shortcuts.dedup();
+        shortcuts
+    }
+
+    /// File associations from the prefix registry: `HKLM\Software\Classes` and the
+    /// user's `Software\Classes` and `FileExts`.
+    pub fn file_associations(&self) -> Option<&FileAssociations> {
+        self.windows_root.file_associations()
+    }
+}
+
+fn build_windows_root(root: &Path, system: &WineRegistry, user: &WineRegistry) -> WindowsRoot {
+    let mut windows_root = WindowsRoot::new(root.join("drive_c"));
+    for letter in 'a'..='z' {
+        let device = root.join("dosdevices").join(format!("{letter}:"));
+        if letter != 'c' && device.exists() {
+            windows_root = windows_root.with_drive(letter, device);
+        }
+    }
+
+    if let Some(build) = read_build(system, "Software\\Microsoft\\Windows NT\\CurrentVersion") {
+        windows_root = windows_root.with_build(build);
+    }
+
+    let current_version = |name| system.string_value(CURRENT_VERSION, name);
+    let system_root = system
+        .string_value(
+            "Software\\Microsoft\\Windows NT\\CurrentVersion",
+            "SystemRoot",
+        )
+        .unwrap_or_else(|| "C:\\windows".to_owned());
+    let program_files =
+        current_version("ProgramFilesDir").unwrap_or_else(|| "C:\\Program Files".to_owned());
+    let program_files_x86 =
+        current_version("ProgramFilesDir (x86)").unwrap_or_else(|| program_files.clone());
+
+    windows_root = windows_root
+        .with_env_var("SystemRoot", &system_root)
+        .with_env_var("windir", &system_root)
+        .with_env_var("ProgramFiles", &program_files)
+        .with_env_var("ProgramW6432", &program_files)
+        .with_env_var("ProgramFiles(x86)", &program_files_x86)
+        .with_env_var(
+            "CommonProgramFiles",
+            &current_version("CommonFilesDir")
+                .unwrap_or_else(|| format!("{program_files}\\Common Files")),
+        );
+
+    if let Some(user_name) = user_name(root) {
+        windows_root = windows_root.with_user(&user_name);
+    }
+    for (name, value_name) in [("APPDATA", "AppData"), ("LOCALAPPDATA", "Local AppData")] {
+        if let Some(folder) = user.string_value(SHELL_FOLDERS, value_name) {
+            windows_root = windows_root.with_env_var(name, &folder);
+        }
+    }
+
+    let registry_environment = [system.values(ENVIRONMENT), user.values("Environment")];
+    for value in registry_environment.into_iter().flatten().flatten() {
+        if let Some(text) = value.as_string() {
+            windows_root = windows_root.with_env_var(&value.name, &text);
+        }
+    }
+
+    windows_root.with_file_associations(
+        FileAssociations::new()
+            .with_software(system.subtree("Software"))
+            .with_ntuser(user.clone()),
+    )
+}
+
+/// The profile folder name under `drive_c\users` (`steamuser` under Proton).
+fn user_name(root: &Path) -> Option<String> {
+    let users = root.join("drive_c").join("users");
+    let mut names: Vec<String> = fs::read_dir(users)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.eq_ignore_ascii_case("Public"))
+        .collect();
+    names.sort();
+    names.into_iter().next()
+}
@@ -0,0 +1,300 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use crate::{
+    REG_BINARY, REG_DWORD, REG_SZ, RegistrySource, RegistryValue, utils::binary_utils::invalid_data,
+};
+
+/// A registry file in Wine's text format (`system.reg`, `user.reg`), keyed by path
+/// relative to the hive it was saved from (`HKLM` and `HKCU` respectively).
+#[derive(Clone, Debug, Default)]
+pub struct WineRegistry {
+    keys: HashMap<String, Vec<RegistryValue>>,
+}
+
+impl WineRegistry {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&fs::read(path.as_ref())?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        // Wine escapes everything outside ASCII, but older files may contain raw UTF-8
+        Self::parse(&String::from_utf8_lossy(data))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        if !text.trim_start().starts_with("WINE REGISTRY") {
+            return Err(invalid_data("not a Wine registry file"));
+        }
+
+        let mut registry = WineRegistry::default();
+        let mut current: Option<String> = None;
+        for line in logical_lines(text) {
+            let line = line.trim();
+            if let Some(header) = line.strip_prefix('[') {
+                current = parse_key_header(header).map(|path| normalize_key(&path));
+                if let Some(path) = &current {
+                    registry.keys.entry(path.clone()).or_default();
+                }
+            } else if let Some(path) = &current
+                && let Some(value) = parse_value(line)
+            {
+                let values = registry.keys.entry(path.clone()).or_default();
+                values.retain(|v| !v.name.eq_ignore_ascii_case(&value.name));
+                values.push(value);
+            }
+        }
+        Ok(registry)
+    }
+
+    /// The keys below `path`, re-rooted at it. `system.reg` holds all of `HKLM`, so
+    /// `subtree("Software")` is the equivalent of an offline `SOFTWARE` hive.
+    pub fn subtree(&self, path: &str) -> WineRegistry {
+        let prefix = format!("{}\\", normalize_key(path));
+        let keys = self
+            .keys
+            .iter()
+            .filter_map(|(key, values)| {
+                let rest = key.strip_prefix(&prefix)?;
+                Some((rest.to_owned(), values.clone()))
+            })
+            .collect();
+        WineRegistry { keys }
+    }
+
+    pub fn contains_key(&self, path: &str) -> bool {
+        self.keys.contains_key(&normalize_key(path))
+    }
+}
+
+impl RegistrySource for WineRegistry {
+    fn values(&self, key_path: &str) -> Option<Vec<RegistryValue>> {
+        self.keys.get(&normalize_key(key_path)).cloned()
+    }
+}
+
+fn normalize_key(path: &str) -> String {
+    path.split('\\')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\\")
+        .to_lowercase()
+}
+
+/// Joins lines continued with a trailing backslash, as long `hex:` values are written.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    for line in text.lines() {
+        let line = if pending.is_empty() {
+            line
+        } else {
+            line.trim_start()
+        };
+        match line.strip_suffix('\\') {
+            Some(continued) if !pending.is_empty() || is_hex_value(line) => {
+                pending.push_str(continued)
+            }
+            _ => {
+                pending.push_str(line);
+                lines.push(std::mem::take(&mut pending));
+            }
+        }
+    }
+    if !pending.is_empty() {
+        lines.push(pending);
+    }
+    lines
+}
+
+fn is_hex_value(line: &str) -> bool {
+    let line = line.trim_start();
+    (line.starts_with('"') || line.starts_with('@')) && line.contains("=hex")
+}
+
+fn parse_key_header(header: &str) -> Option<String> {
+    let (path, rest) = unescape(header, ']')?;
+    rest.starts_with(']').then_some(path)
+}
+
+fn parse_value(line: &str) -> Option<RegistryValue> {
+    let (name, rest) = if let Some(rest) = line.strip_prefix('@') {
+        (String::new(), rest)
+    } else {
+        let (name, rest) = unescape(line.strip_prefix('"')?, '"')?;
+        (name, rest.strip_prefix('"')?)
+    };
+    let data = rest.trim_start().strip_prefix('=')?.trim();
+
+    let (value_type, data) = if let Some(text) = data.strip_prefix('"') {
+        (REG_SZ, string_data(text)?)
+    } else if let Some(rest) = data.strip_prefix("str(") {
+        let (value_type, text) = rest.split_once("):\"")?;
+        (value_type.parse().ok()?, string_data(text)?)
+    } else if let Some(hex) = data.strip_prefix("dword:") {
+        let dword = u32::from_str_radix(hex.trim(), 16).ok()?;
+        (REG_DWORD, dword.to_le_bytes().to_vec())
+    } else if let Some(hex) = data.strip_prefix("hex:") {
+        (REG_BINARY, hex_data(hex)?)
+    } else if let Some(rest) = data.strip_prefix("hex(") {
+        let (value_type, hex) = rest.split_once("):")?;
+        (u32::from_str_radix(value_type, 16).ok()?, hex_data(hex)?)
+    } else {
+        // "-" marks a deleted value
+        return None;
+    };
+
+    Some(RegistryValue {
+        name,
+        value_type,
+        data,
+    })
+}
+
+fn string_data(text: &str) -> Option<Vec<u8>> {
+    let (text, _) = unescape(text, '"')?;
+    let mut data: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    data.extend_from_slice(&[0, 0]);
+    Some(data)
+}
+
+fn hex_data(hex: &str) -> Option<Vec<u8>> {
+    hex.split(',')
+        .map(str::trim)
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect()
+}
+
+/// Unescapes until the unescaped `terminator`, returning the text and the rest of
+/// the input starting at the terminator.
+fn unescape(input: &str, terminator: char) -> Option<(String, &str)> {
+    let mut text = String::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == terminator {
+            return Some((text, &input[i..]));
+        }
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        let (_, escaped) = chars.next()?;
+        match escaped {
+            'a' => text.push('\x07'),
+            'b' => text.push('\x08'),
+            'e' => text.push('\x1b'),
+            'f' => text.push('\x0c'),
+            'n' => text.push('\n'),
+            'r' => text.push('\r'),
+            't' => text.push('\t'),
+            'v' => text.push('\x0b'),
+            'x' => {
+                let mut code = 0;
+                for _ in 0..4 {
+                    match chars.peek().and_then(|(_, c)| c.to_digit(16)) {
+                        Some(digit) => {
+                            code = code * 16 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            '0'..='7' => {
+                let mut code = escaped.to_digit(8)?;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|(_, c)| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            other => text.push(other),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::REG_EXPAND_SZ;
+
+    const SYSTEM_REG: &str = r#"WINE REGISTRY Version 2
+;; All keys relative to \\Machine
+
+#arch=win64
+
+[Software\\Classes\\.txt] 1700000000
+#time=1da0000000000000
+@="txtfile"
+"Content Type"="text/plain"
+
+[Software\\Classes\\txtfile\\DefaultIcon] 1700000000
+@=str(2):"%SystemRoot%\\system32\\imageres.dll,-102"
+
+[Software\\Wine\\Test] 1700000000
+"Caf\xe9"="Gr\x00fc\x00df \"quoted\""
+"Flags"=dword:0000002a
+"Blob"=hex:01,02,\
+  03,ff
+"Multi"=hex(7):61,00,00,00,62,00,00,00,00,00
+"Deleted"=-
+"Flags"=dword:00000001
+"#;
+
+    #[test]
+    fn parses_keys_and_value_types() {
+        let registry = WineRegistry::parse(SYSTEM_REG).unwrap();
+
+        assert_eq!(
+            registry
+                .string_value("software\\CLASSES\\.txt", "")
+                .as_deref(),
+            Some("txtfile")
+        );
+        let icon = registry
+            .value("Software\\Classes\\txtfile\\DefaultIcon", "")
+            .unwrap();
+        assert_eq!(icon.value_type, REG_EXPAND_SZ);
+        assert_eq!(
+            icon.as_string().as_deref(),
+            Some("%SystemRoot%\\system32\\imageres.dll,-102")
+        );
+
+        let value = |name| registry.value("Software\\Wine\\Test", name).unwrap();
+        assert_eq!(
+            value("café").as_string().as_deref(),
+            Some("Grüß \"quoted\"")
+        );
+        assert_eq!(value("Flags").as_u32(), Some(1));
+        assert_eq!(value("Blob").data, [1, 2, 3, 0xff]);
+        assert_eq!(value("Multi").as_string().as_deref(), Some("a"));
+        assert!(registry.value("Software\\Wine\\Test", "Deleted").is_none());
+    }
+
+    #[test]
+    fn re_roots_subtrees() {
+        let registry = WineRegistry::parse(SYSTEM_REG).unwrap();
+        let software = registry.subtree("Software");
+
+        assert!(software.contains_key("Classes\\.txt"));
+        assert!(!software.contains_key("Software\\Classes\\.txt"));
+        assert_eq!(
+            software.string_value("Classes\\.txt", "").as_deref(),
+            Some("txtfile")
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(WineRegistry::parse("Windows Registry Editor Version 5.00\r\n").is_err());
+        assert!(WineRegistry::from_bytes(b"regf").is_err());
+    }
+}